@@ -4,12 +4,17 @@ use rust_decimal::Decimal;
 use uuid::Uuid;
 
 use drive_deposits_proto_grpc_types::generated::{AccountType, PeriodUnit};
+use drive_deposits_rest_types::auth::TenantId;
 
 // Request sections
 #[derive(Debug)]
 pub struct PortfolioRequest {
     pub new_banks: Vec<NewBank>,
     pub new_delta: NewDelta,
+    // who the portfolio is calculated for; carried into the events so results are stored per tenant
+    pub tenant_id: TenantId,
+    // from the originating request, if it sent one; otherwise the events get a new one
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +47,7 @@ pub struct PortfolioResponse {
     pub banks: Vec<Bank>,
     pub outcome: Option<Outcome>,
     pub created_at: String,
+    pub tenant_id: TenantId,
 }
 
 #[derive(Debug, Clone)]
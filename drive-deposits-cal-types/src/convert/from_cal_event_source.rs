@@ -84,6 +84,8 @@ impl From<CalBank> for EventSourceBank {
             bank_tz: cal.bank_tz.to_string(),
             deposits: cal.deposits.into_iter().map(|x| x.into()).collect(),
             outcome: cal.outcome.map(|x| x.into()),
+            // set by the engine on the bank-level event only, not on the banks of a portfolio
+            tenant_id: None,
         }
     }
 }
@@ -95,6 +97,7 @@ impl From<CalBankResponse> for EventSourceCalculatePortfolioResponse {
             uuid: cal.uuid.to_string(),
             outcome: cal.outcome.map(|x| x.into()),
             created_at: cal.created_at,
+            tenant_id: Some(cal.tenant_id.to_string()),
         }
     }
 }
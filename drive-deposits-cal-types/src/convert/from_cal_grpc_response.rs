@@ -1,7 +1,8 @@
 use drive_deposits_proto_grpc_types::generated::{
     Bank as GrpcBank, CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
     Delta as GrpcDelta, Deposit as GrpcDeposit, Maturity as GrpcMaturity, Outcome as GrpcOutcome,
-    OutcomeWithDates as GrpcOutcomeWithDates, ProcessingError as GrpcProcessingError,
+    OutcomeWithDates as GrpcOutcomeWithDates, PortfolioSummary as GrpcPortfolioSummary,
+    ProcessingError as GrpcProcessingError,
 };
 
 use crate::cal_types::{
@@ -101,3 +102,15 @@ impl From<CalBankResponse> for GrpcCalculatePortfolioResponse {
         }
     }
 }
+
+// banks are streamed one at a time before the summary so only their count is carried here
+impl From<CalBankResponse> for GrpcPortfolioSummary {
+    fn from(cal: CalBankResponse) -> Self {
+        Self {
+            uuid: cal.uuid.to_string(),
+            outcome: cal.outcome.map(|x| x.into()),
+            created_at: cal.created_at,
+            bank_count: cal.banks.len() as u32,
+        }
+    }
+}
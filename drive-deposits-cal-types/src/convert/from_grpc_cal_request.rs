@@ -1,58 +1,259 @@
+use std::fmt::{Display, Formatter};
+
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
+use thiserror::Error;
+use validator::ValidationError;
 
 use drive_deposits_proto_grpc_types::generated::{
     AccountType as GrpcAccountType, CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
     NewBank as GrpcNewBank, NewDelta as GrpcNewDelta, NewDeposit as GrpcNewDeposit,
     PeriodUnit as GrpcPeriodUnit,
 };
+use drive_deposits_rest_types::auth::TenantId;
+use drive_deposits_rest_types::rest_types::{
+    validate_bank_tz, validate_decimal, validate_iso8601_date, validate_positive_decimal,
+};
 
 use crate::cal_types::{
     NewBank as CalNewBank, NewDelta as CalNewDelta, NewDeposit as CalNewDeposit,
     PortfolioRequest as CalBankRequest,
 };
 
-impl From<GrpcNewDeposit> for CalNewDeposit {
-    fn from(grpc: GrpcNewDeposit) -> Self {
-        Self {
+// same minimums as the #[validate(length(min = ...))] attributes on the rest types
+const MIN_ACCOUNT_LENGTH: usize = 4;
+const MIN_BANK_NAME_LENGTH: usize = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldViolation {
+    // full path to the field, for example new_banks[2].new_deposits[0].apy
+    pub field: String,
+    pub code: String,
+    pub description: String,
+}
+
+impl Display for FieldViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.description)
+    }
+}
+
+// collects every violation instead of stopping at the first one, like validator::ValidationErrors
+#[derive(Debug, Default, Error)]
+#[error("Request conversion errors: [{}]", .violations.iter().map(|violation| violation.to_string()).collect::<Vec<String>>().join(", "))]
+pub struct RequestConversionError {
+    pub violations: Vec<FieldViolation>,
+}
+
+impl RequestConversionError {
+    fn add(&mut self, field: &str, code: &str, description: String) {
+        self.violations.push(FieldViolation {
+            field: field.to_string(),
+            code: code.to_string(),
+            description,
+        });
+    }
+
+    fn add_validation_error(&mut self, field: &str, error: ValidationError) {
+        let description = error
+            .message
+            .map(|message| message.trim_end().to_string())
+            .unwrap_or_else(|| format!("Invalid value for {}", field));
+        self.add(field, &error.code, description);
+    }
+
+    fn check(&mut self, field: &str, result: Result<(), ValidationError>) {
+        if let Err(error) = result {
+            self.add_validation_error(field, error);
+        }
+    }
+
+    // true when long enough, so checks of the value itself can be skipped otherwise
+    fn check_min_length(&mut self, field: &str, actual: usize, min: usize) -> bool {
+        if actual < min {
+            self.add(
+                field,
+                "length",
+                format!("Incorrect length: {}. Must be at least {}.", actual, min),
+            );
+            return false;
+        }
+        true
+    }
+
+    // nests the violations of a child under its parent path
+    fn extend_prefixed(&mut self, prefix: &str, child: RequestConversionError) {
+        self.violations.extend(
+            child
+                .violations
+                .into_iter()
+                .map(|violation| FieldViolation {
+                    field: format!("{}.{}", prefix, violation.field),
+                    ..violation
+                }),
+        );
+    }
+
+    fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+// only called after the matching validate_* check passed, so the default is never used
+fn parse_decimal(value: &str) -> Decimal {
+    value.parse::<Decimal>().unwrap_or_default()
+}
+
+impl TryFrom<GrpcNewDeposit> for CalNewDeposit {
+    type Error = RequestConversionError;
+
+    fn try_from(grpc: GrpcNewDeposit) -> Result<Self, Self::Error> {
+        let mut errors = RequestConversionError::default();
+        errors.check_min_length("account", grpc.account.chars().count(), MIN_ACCOUNT_LENGTH);
+        // unset is 0, which prost accepts as ACCOUNT_TYPE_UNSPECIFIED
+        let account_type = GrpcAccountType::try_from(grpc.account_type)
+            .ok()
+            .filter(|account_type| *account_type != GrpcAccountType::Unspecified);
+        if account_type.is_none() {
+            errors.add(
+                "account_type",
+                "invalid_account_type",
+                format!(
+                    "Incorrect account_type: {}. Must be Checking, Savings, CertificateOfDeposit, or BrokerageCertificateOfDeposit.",
+                    grpc.account_type
+                ),
+            );
+        }
+        errors.check("apy", validate_decimal(&grpc.apy));
+        errors.check("years", validate_positive_decimal(&grpc.years));
+        errors.check("amount", validate_decimal(&grpc.amount));
+        errors.check(
+            "start_date_in_bank_tz",
+            validate_iso8601_date(&grpc.start_date_in_bank_tz),
+        );
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
             account: grpc.account,
-            account_type: GrpcAccountType::try_from(grpc.account_type).unwrap_or_default(),
-            apy: grpc.apy.parse::<Decimal>().unwrap_or_default(),
-            years: grpc.years.parse::<Decimal>().unwrap_or_default(),
-            amount: grpc.amount.parse::<Decimal>().unwrap_or_default(),
+            account_type: account_type.unwrap_or_default(),
+            apy: parse_decimal(&grpc.apy),
+            years: parse_decimal(&grpc.years),
+            amount: parse_decimal(&grpc.amount),
             start_date_in_bank_tz: NaiveDate::parse_from_str(
                 &grpc.start_date_in_bank_tz,
                 "%Y-%m-%d",
             )
             .unwrap_or_default(),
-        }
+        })
     }
 }
 
-impl From<GrpcNewBank> for CalNewBank {
-    fn from(grpc: GrpcNewBank) -> Self {
-        Self {
+impl TryFrom<GrpcNewBank> for CalNewBank {
+    type Error = RequestConversionError;
+
+    fn try_from(grpc: GrpcNewBank) -> Result<Self, Self::Error> {
+        let mut errors = RequestConversionError::default();
+        errors.check_min_length("name", grpc.name.chars().count(), MIN_BANK_NAME_LENGTH);
+        errors.check("bank_tz", validate_bank_tz(&grpc.bank_tz));
+        errors.check_min_length("new_deposits", grpc.new_deposits.len(), 1);
+
+        let mut new_deposits = Vec::with_capacity(grpc.new_deposits.len());
+        for (index, grpc_new_deposit) in grpc.new_deposits.into_iter().enumerate() {
+            match CalNewDeposit::try_from(grpc_new_deposit) {
+                Ok(new_deposit) => new_deposits.push(new_deposit),
+                Err(err) => errors.extend_prefixed(&format!("new_deposits[{}]", index), err),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
             name: grpc.name,
             bank_tz: grpc.bank_tz.parse().unwrap_or_default(),
-            new_deposits: grpc.new_deposits.into_iter().map(|x| x.into()).collect(),
-        }
+            new_deposits,
+        })
     }
 }
 
-impl From<GrpcNewDelta> for CalNewDelta {
-    fn from(grpc: GrpcNewDelta) -> Self {
-        Self {
-            period: grpc.period.parse::<Decimal>().unwrap_or_default(),
-            period_unit: GrpcPeriodUnit::try_from(grpc.period_unit).unwrap_or_default(),
+impl TryFrom<GrpcNewDelta> for CalNewDelta {
+    type Error = RequestConversionError;
+
+    fn try_from(grpc: GrpcNewDelta) -> Result<Self, Self::Error> {
+        let mut errors = RequestConversionError::default();
+        // one violation per field: an empty period is not also reported as not a decimal
+        if errors.check_min_length("period", grpc.period.chars().count(), 1) {
+            errors.check("period", validate_positive_decimal(&grpc.period));
         }
+        let period_unit = GrpcPeriodUnit::try_from(grpc.period_unit)
+            .ok()
+            .filter(|period_unit| *period_unit != GrpcPeriodUnit::Unspecified);
+        if period_unit.is_none() {
+            errors.add(
+                "period_unit",
+                "invalid_period_unit",
+                format!(
+                    "Incorrect period_unit: {}. Must be Day, Week, Month, or Year.",
+                    grpc.period_unit
+                ),
+            );
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Self {
+            period: parse_decimal(&grpc.period),
+            period_unit: period_unit.unwrap_or_default(),
+        })
     }
 }
 
-impl From<GrpcCalculatePortfolioRequest> for CalBankRequest {
-    fn from(grpc: GrpcCalculatePortfolioRequest) -> Self {
-        Self {
-            new_banks: grpc.new_banks.into_iter().map(|x| x.into()).collect(),
-            new_delta: grpc.new_delta.unwrap_or_default().into(),
+impl TryFrom<GrpcCalculatePortfolioRequest> for CalBankRequest {
+    type Error = RequestConversionError;
+
+    fn try_from(grpc: GrpcCalculatePortfolioRequest) -> Result<Self, Self::Error> {
+        let mut errors = RequestConversionError::default();
+        errors.check_min_length("new_banks", grpc.new_banks.len(), 1);
+
+        let mut new_banks = Vec::with_capacity(grpc.new_banks.len());
+        for (index, grpc_new_bank) in grpc.new_banks.into_iter().enumerate() {
+            match CalNewBank::try_from(grpc_new_bank) {
+                Ok(new_bank) => new_banks.push(new_bank),
+                Err(err) => errors.extend_prefixed(&format!("new_banks[{}]", index), err),
+            }
+        }
+
+        let new_delta = match grpc.new_delta {
+            Some(grpc_new_delta) => match CalNewDelta::try_from(grpc_new_delta) {
+                Ok(new_delta) => Some(new_delta),
+                Err(err) => {
+                    errors.extend_prefixed("new_delta", err);
+                    None
+                }
+            },
+            None => {
+                errors.add(
+                    "new_delta",
+                    "required",
+                    "Missing new_delta. Must be provided with period and period_unit.".to_string(),
+                );
+                None
+            }
+        };
+
+        match new_delta {
+            // tenant and correlation id are not part of the request; callers that know them set
+            // them afterwards
+            Some(new_delta) if errors.is_empty() => Ok(Self {
+                new_banks,
+                new_delta,
+                tenant_id: TenantId::default(),
+                correlation_id: None,
+            }),
+            _ => Err(errors),
         }
     }
 }
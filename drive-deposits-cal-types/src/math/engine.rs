@@ -1,23 +1,31 @@
 use std::sync::Arc;
 
 use chrono::SecondsFormat;
+use serde::Serialize;
 use serde_json::to_string;
 use thiserror::Error;
+use tokio::sync::mpsc::Sender;
 use tokio::task::{spawn_blocking, JoinSet};
+use tokio::time::{sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, debug_span, info, instrument, Instrument, Span};
 use uuid::Uuid;
 
 use drive_deposits_event_source::{
-    eb::{
-        send_bank_level_event_to_event_bridge, send_portfolio_level_event_to_event_bridge,
-        DriveDepositsEventBridge, DriveDepositsEventBridgeError,
-    },
     payload_types::{
         Bank as EventSourceBank,
         CalculatePortfolioResponse as EventSourceCalculatePortfolioResponse,
+        Deposit as EventSourceDeposit, DepositLevel as EventSourceDepositLevel,
+    },
+    publisher::{
+        EventPublisherError, SharedEventPublisher, BANK_LEVEL, DEPOSIT_LEVEL, PORTFOLIO_LEVEL,
     },
 };
 
+use drive_deposits_rest_types::auth::TenantId;
+use drive_deposits_rest_types::cloud_event::{CloudEvent, EVENT_SOURCE};
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
 use crate::cal_types::{
     Bank, Deposit, NewBank, NewDelta, NewDeposit, PortfolioRequest, PortfolioResponse,
 };
@@ -35,11 +43,104 @@ pub enum CalculationHaltError {
     #[error("Join error all calculations could not proceed: {0}")]
     Join(#[from] tokio::task::JoinError),
 
-    #[error("Drive Deposits SEND_CAL_EVENTS is true but could not send events for processing as desired: {0}")]
-    DriveDepositsEventBridgeError(#[from] DriveDepositsEventBridgeError),
+    #[error("Drive Deposits EVENT_PUBLISHER is set but could not publish events for processing as desired: {0}")]
+    EventPublisherError(#[from] EventPublisherError),
 
-    #[error("Drive Deposits SEND_CAL_EVENTS is true but could not serialize events for sending as desired: {0}")]
+    #[error("Drive Deposits EVENT_PUBLISHER is set but could not serialize events for sending as desired: {0}")]
     EventSourceJsonSerializationError(#[from] serde_json::Error),
+
+    #[error("Calculation stopped before completing, no events sent: {0}")]
+    Cancelled(CancelReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CancelReason {
+    #[error("caller cancelled")]
+    Cancelled,
+
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+}
+
+// Stops a calculation once the caller goes away or its deadline passes; the default never does
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    pub token: CancellationToken,
+    pub deadline: Option<Instant>,
+}
+
+impl Cancellation {
+    pub fn new(deadline: Option<Instant>) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            deadline,
+        }
+    }
+
+    // a passed deadline wins, so the caller sees DEADLINE_EXCEEDED rather than CANCELLED
+    pub fn reason(&self) -> Option<CancelReason> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Some(CancelReason::DeadlineExceeded)
+        } else if self.token.is_cancelled() {
+            Some(CancelReason::Cancelled)
+        } else {
+            None
+        }
+    }
+
+    pub async fn cancelled(&self) -> CancelReason {
+        // timers are coarse, so an already passed deadline is not left to sleep_until
+        if let Some(reason) = self.reason() {
+            return reason;
+        }
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = self.token.cancelled() => self.reason().unwrap_or(CancelReason::Cancelled),
+            _ = deadline => CancelReason::DeadlineExceeded,
+        }
+    }
+
+    fn check(&self) -> Result<(), CalculationHaltError> {
+        match self.reason() {
+            Some(reason) => Err(CalculationHaltError::Cancelled(reason)),
+            None => Ok(()),
+        }
+    }
+}
+
+// What the events of a calculation look like, from the event publisher config
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventOptions {
+    // CloudEvents source of every event
+    pub source: String,
+    // one event per calculated deposit as well, before the bank-level events
+    pub deposit_level_events: bool,
+}
+
+impl Default for EventOptions {
+    fn default() -> Self {
+        Self {
+            source: EVENT_SOURCE.to_string(),
+            deposit_level_events: false,
+        }
+    }
+}
+
+impl From<&EventPublisherConfig> for EventOptions {
+    fn from(config: &EventPublisherConfig) -> Self {
+        Self {
+            source: config.source.clone(),
+            deposit_level_events: config.deposit_level_events,
+        }
+    }
 }
 
 // same from style not directly using though since more complex with async function
@@ -77,10 +178,13 @@ fn build_from_new_deposits(
     }
     Ok(deposits)
 }
+// deposit_publisher is only passed when deposit level events are opted in
 async fn build_from_new_bank(
     new_bank: NewBank,
     new_delta: Arc<NewDelta>,
-    eb: Arc<Option<DriveDepositsEventBridge>>,
+    deposit_publisher: Option<SharedEventPublisher>,
+    event_context: Arc<EventContext>,
+    cancellation: Cancellation,
 ) -> Result<Bank, CalculationHaltError> {
     // using spawn blocking for synchronous calculation code
     let bank_with_outcome = spawn_blocking(move || -> Result<Bank, CalculationHaltError> {
@@ -101,19 +205,98 @@ async fn build_from_new_bank(
     })
     .await??;
 
-    // this method is executed in joinset spawn task already
-    if eb.is_some() {
-        debug!("send event to event bridge at the bank level");
-        let event_source_bank: EventSourceBank = bank_with_outcome.clone().into();
-        let event_source_bank_json = to_string(&event_source_bank)?;
-        let eb_access = eb.as_ref().as_ref();
-
-        send_bank_level_event_to_event_bridge(eb_access, event_source_bank_json).await?
+    if let Some(publisher) = deposit_publisher {
+        publish_deposit_level(
+            &publisher,
+            &bank_with_outcome,
+            &event_context,
+            &cancellation,
+        )
+        .await?;
     }
 
     Ok(bank_with_outcome)
 }
 
+// what the events of one portfolio calculation have in common
+#[derive(Debug, Clone)]
+struct EventContext {
+    source: String,
+    tenant_id: TenantId,
+    portfolio_uuid: Uuid,
+    correlation_id: String,
+}
+
+impl EventContext {
+    // the payload wrapped in a CloudEvents envelope, with a new event id
+    fn cloud_event_json<T: Serialize>(
+        &self,
+        detail_type: &str,
+        data: T,
+    ) -> Result<String, serde_json::Error> {
+        to_string(&CloudEvent::new(
+            Uuid::new_v4().to_string(),
+            &self.source,
+            detail_type,
+            self.portfolio_uuid.to_string(),
+            self.correlation_id.clone(),
+            data,
+        ))
+    }
+}
+
+// deposit level events for the deposits of one bank, as soon as that bank is calculated; unlike
+// the other events they can go out for a calculation cancelled later on
+async fn publish_deposit_level(
+    publisher: &SharedEventPublisher,
+    bank: &Bank,
+    event_context: &EventContext,
+    cancellation: &Cancellation,
+) -> Result<(), CalculationHaltError> {
+    cancellation.check()?;
+    debug!(
+        "publish events at the deposit level for {} deposits of bank {}",
+        bank.deposits.len(),
+        bank.name
+    );
+    let mut deposit_level_jsons = Vec::with_capacity(bank.deposits.len());
+    for deposit in &bank.deposits {
+        let deposit_level = EventSourceDepositLevel {
+            portfolio_uuid: event_context.portfolio_uuid.to_string(),
+            bank_uuid: bank.uuid.to_string(),
+            bank_name: bank.name.clone(),
+            bank_tz: bank.bank_tz.to_string(),
+            tenant_id: Some(event_context.tenant_id.to_string()),
+            deposit: EventSourceDeposit::from(deposit.clone()),
+        };
+        deposit_level_jsons.push(event_context.cloud_event_json(DEPOSIT_LEVEL, deposit_level)?);
+    }
+    publisher
+        .publish_batch(DEPOSIT_LEVEL, deposit_level_jsons)
+        .await?;
+    Ok(())
+}
+
+// bank level events for all banks go out together, so the publisher can batch them
+async fn publish_bank_level(
+    publisher: &SharedEventPublisher,
+    banks: &[Bank],
+    event_context: &EventContext,
+) -> Result<(), CalculationHaltError> {
+    debug!("publish events at the bank level for {} banks", banks.len());
+    let mut event_source_bank_jsons = Vec::with_capacity(banks.len());
+    for bank in banks {
+        let mut event_source_bank: EventSourceBank = bank.clone().into();
+        event_source_bank.tenant_id = Some(event_context.tenant_id.to_string());
+        event_source_bank_jsons
+            .push(event_context.cloud_event_json(BANK_LEVEL, event_source_bank)?);
+    }
+    publisher
+        .publish_batch(BANK_LEVEL, event_source_bank_jsons)
+        .await?;
+    Ok(())
+}
+
 // The code doesn't need Mutex here because it's using immutable shared state with Arc, and there's no mutable state that needs protection. Here's why:
 //
 // The new_delta is wrapped in Arc and only shared for reading across multiple tasks
@@ -133,28 +316,56 @@ async fn build_from_new_bank(
 async fn build_from_new_banks(
     new_banks: Vec<NewBank>,
     new_delta: Arc<NewDelta>,
-    eb: Arc<Option<DriveDepositsEventBridge>>,
+    deposit_publisher: Option<SharedEventPublisher>,
+    event_context: &EventContext,
+    bank_sender: Option<Sender<Bank>>,
+    cancellation: &Cancellation,
 ) -> Result<Vec<Bank>, CalculationHaltError> {
     let mut banks: Vec<Bank> = Vec::new();
     let mut join_set = JoinSet::new();
+    let shared_event_context = Arc::new(event_context.clone());
 
     for new_bank in new_banks {
         // Correctly create a new span with the bank name
         let bank_span = debug_span!(parent: &Span::current(), "bank_level_spawned_task_for_processing_all_deposits", bank_name = %new_bank.name);
         let delta_clone = new_delta.clone();
-        let eb_clone = eb.clone();
+        let deposit_publisher_clone = deposit_publisher.clone();
+        let event_context_clone = shared_event_context.clone();
+        let cancellation_clone = cancellation.clone();
         join_set.spawn(
             async move {
                 info!("task spawned for new_bank: {:?}", new_bank.name);
-                let bank = build_from_new_bank(new_bank, delta_clone, eb_clone);
+                let bank = build_from_new_bank(
+                    new_bank,
+                    delta_clone,
+                    deposit_publisher_clone,
+                    event_context_clone,
+                    cancellation_clone,
+                );
                 bank.await
             }
             .instrument(bank_span),
         );
     }
 
-    while let Some(res) = join_set.join_next().await {
+    loop {
+        let res = tokio::select! {
+            biased;
+            reason = cancellation.cancelled() => {
+                info!("calculation cancelled: {}, aborting outstanding bank tasks", reason);
+                join_set.abort_all();
+                return Err(CalculationHaltError::Cancelled(reason));
+            }
+            res = join_set.join_next() => res,
+        };
+        let Some(res) = res else { break };
         let bank = res??;
+        // streaming callers get each bank in completion order, as soon as its task is done
+        if let Some(sender) = bank_sender.as_ref() {
+            if sender.send(bank.clone()).await.is_err() {
+                debug!("bank receiver dropped, so continuing calculation without streaming banks");
+            }
+        }
         banks.push(bank);
     }
 
@@ -163,15 +374,36 @@ async fn build_from_new_banks(
 
 async fn build_from_portfolio_request(
     portfolio_req: PortfolioRequest,
-    eb: Arc<Option<DriveDepositsEventBridge>>,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    bank_sender: Option<Sender<Bank>>,
+    cancellation: Cancellation,
 ) -> Result<PortfolioResponse, CalculationHaltError> {
     let uuid = Uuid::new_v4();
     info!("build_from_portfolio_request uuid created: {:?}", uuid);
+    let event_context = EventContext {
+        source: event_options.source.clone(),
+        tenant_id: portfolio_req.tenant_id.clone(),
+        portfolio_uuid: uuid,
+        correlation_id: portfolio_req
+            .correlation_id
+            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+    };
     let created_at = chrono::Utc::now();
     let created_at_iso8061 = created_at.to_rfc3339_opts(SecondsFormat::Micros, true);
-    let eb_clone = eb.clone();
     let new_delta = Arc::new(portfolio_req.new_delta);
-    let banks = build_from_new_banks(portfolio_req.new_banks, new_delta.clone(), eb).await?;
+    let deposit_publisher = publisher
+        .clone()
+        .filter(|_| event_options.deposit_level_events);
+    let banks = build_from_new_banks(
+        portfolio_req.new_banks,
+        new_delta.clone(),
+        deposit_publisher,
+        &event_context,
+        bank_sender,
+        &cancellation,
+    )
+    .await?;
     let outcome = build_outcome_from_banks(&banks, new_delta.clone().as_ref());
 
     let bank_response = PortfolioResponse {
@@ -179,20 +411,24 @@ async fn build_from_portfolio_request(
         banks,
         outcome,
         created_at: created_at_iso8061,
+        tenant_id: portfolio_req.tenant_id,
     };
 
-    if eb_clone.is_some() {
-        debug!("send event to event bridge at the banks level");
+    if let Some(publisher) = publisher {
+        // checked once before the bank level events: nobody wants the result anymore, so it is not
+        // persisted either; past this point every remaining event of the calculation goes out
+        cancellation.check()?;
+        publish_bank_level(&publisher, &bank_response.banks, &event_context).await?;
+        debug!("publish event at the banks level");
         let event_source_response: EventSourceCalculatePortfolioResponse =
             bank_response.clone().into();
-        let event_source_response_json = to_string(&event_source_response)?;
+        let event_source_response_json =
+            event_context.cloud_event_json(PORTFOLIO_LEVEL, event_source_response)?;
 
         let handle = tokio::spawn(async move {
-            send_portfolio_level_event_to_event_bridge(
-                eb_clone.as_ref().as_ref(),
-                event_source_response_json,
-            )
-            .await
+            publisher
+                .publish(PORTFOLIO_LEVEL, event_source_response_json)
+                .await
         });
         handle.await??;
     }
@@ -200,23 +436,61 @@ async fn build_from_portfolio_request(
     Ok(bank_response)
 }
 
-#[instrument(skip(portfolio_req, eb))]
+#[instrument(skip(portfolio_req, publisher, event_options, cancellation))]
 pub async fn calculate_portfolio(
     portfolio_req: PortfolioRequest,
-    eb: Option<DriveDepositsEventBridge>,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    cancellation: Cancellation,
+) -> Result<PortfolioResponse, CalculationHaltError> {
+    calculate(portfolio_req, publisher, event_options, None, cancellation).await
+}
+
+// Same calculation as calculate_portfolio, additionally sending each Bank to bank_sender as soon as
+// its task completes. The returned PortfolioResponse still has all the banks for the summary.
+#[instrument(skip(portfolio_req, publisher, event_options, bank_sender, cancellation))]
+pub async fn calculate_portfolio_streaming(
+    portfolio_req: PortfolioRequest,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    bank_sender: Sender<Bank>,
+    cancellation: Cancellation,
+) -> Result<PortfolioResponse, CalculationHaltError> {
+    calculate(
+        portfolio_req,
+        publisher,
+        event_options,
+        Some(bank_sender),
+        cancellation,
+    )
+    .await
+}
+
+async fn calculate(
+    portfolio_req: PortfolioRequest,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    bank_sender: Option<Sender<Bank>>,
+    cancellation: Cancellation,
 ) -> Result<PortfolioResponse, CalculationHaltError> {
     debug!(
         "Starting calculation by period per PortfolioRequest overall: {:?}",
         portfolio_req
     );
     info!(
-        "Drive Deposits SEND_CAL_EVENTS is {}, so events will {}be sent",
-        eb.is_some(),
-        if eb.is_some() { "" } else { "not " }
+        "Drive Deposits event publisher is {}, so events will {}be sent",
+        publisher.is_some(),
+        if publisher.is_some() { "" } else { "not " }
     );
 
-    let eb_access = Arc::new(eb);
-    let bank_resp = build_from_portfolio_request(portfolio_req, eb_access).await?;
+    let bank_resp = build_from_portfolio_request(
+        portfolio_req,
+        publisher,
+        event_options,
+        bank_sender,
+        cancellation,
+    )
+    .await?;
 
     Ok(bank_resp)
 }
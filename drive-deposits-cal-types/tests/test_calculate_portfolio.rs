@@ -1,10 +1,26 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+use tokio::sync::mpsc::channel;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, Instrument};
 
-use drive_deposits_cal_types::cal_types::NewDelta;
 use drive_deposits_cal_types::cal_types::PortfolioRequest;
-use drive_deposits_cal_types::math::engine::calculate_portfolio;
-use drive_deposits_proto_grpc_types::generated::PeriodUnit;
+use drive_deposits_cal_types::cal_types::{NewBank, NewDelta, NewDeposit};
+use drive_deposits_cal_types::math::engine::{
+    calculate_portfolio, calculate_portfolio_streaming, CalculationHaltError, CancelReason,
+    Cancellation, EventOptions,
+};
+use drive_deposits_event_source::publisher::{
+    ChannelPublisher, EventPublisher, EventPublisherError, BANK_LEVEL, DEPOSIT_LEVEL,
+    PORTFOLIO_LEVEL,
+};
+use drive_deposits_proto_grpc_types::generated::{AccountType, PeriodUnit};
+use drive_deposits_rest_types::auth::TenantId;
 use helper::enable_tracing::initialize_test_span;
+use helper::test_data::naive_date_2023_11_23;
 
 mod helper;
 #[tokio::test]
@@ -17,9 +33,371 @@ async fn test_calculate_by_period_empty_no_new_banks_without_events() {
             period: Default::default(),
             period_unit: PeriodUnit::Day,
         },
+        tenant_id: TenantId::default(),
+        correlation_id: None,
     };
 
     // don't have to spawn a task necessarily or even async move since test is async already
-    let result = calculate_portfolio(bank_req, None).instrument(span).await;
+    let result = calculate_portfolio(
+        bank_req,
+        None,
+        EventOptions::default(),
+        Cancellation::default(),
+    )
+    .instrument(span)
+    .await;
     debug!("finally result: {:?}", result);
 }
+
+#[tokio::test]
+async fn test_calculate_portfolio_streaming_sends_each_bank_without_events() {
+    let span =
+        initialize_test_span("test_calculate_portfolio_streaming_sends_each_bank_without_events");
+
+    let portfolio_req = three_bank_request();
+
+    let (bank_sender, mut bank_receiver) = channel(1);
+    let calculation = tokio::spawn(
+        calculate_portfolio_streaming(
+            portfolio_req,
+            None,
+            EventOptions::default(),
+            bank_sender,
+            Cancellation::default(),
+        )
+        .instrument(span),
+    );
+    let mut streamed_bank_names = vec![];
+    while let Some(bank) = bank_receiver.recv().await {
+        streamed_bank_names.push(bank.name);
+    }
+    let portfolio_resp = calculation.await.unwrap().unwrap();
+
+    streamed_bank_names.sort();
+    assert_eq!(
+        streamed_bank_names,
+        vec!["BRAVE-BANK", "PRIME-BANK", "VISION-BANK"]
+    );
+    assert_eq!(portfolio_resp.banks.len(), 3);
+    assert!(portfolio_resp.outcome.is_some());
+    assert_eq!(portfolio_resp.tenant_id.as_str(), "tenant-a");
+}
+
+fn three_bank_request() -> PortfolioRequest {
+    let new_banks = ["VISION-BANK", "BRAVE-BANK", "PRIME-BANK"]
+        .into_iter()
+        .map(|name| NewBank {
+            name: name.to_string(),
+            bank_tz: chrono_tz::America::New_York,
+            new_deposits: vec![NewDeposit {
+                account: "1234".to_string(),
+                account_type: AccountType::Savings,
+                apy: dec!(2.4),
+                years: dec!(1),
+                amount: dec!(1000),
+                start_date_in_bank_tz: naive_date_2023_11_23(),
+            }],
+        })
+        .collect();
+    PortfolioRequest {
+        new_banks,
+        new_delta: NewDelta {
+            period: dec!(1),
+            period_unit: PeriodUnit::Month,
+        },
+        tenant_id: "tenant-a".parse().unwrap(),
+        correlation_id: None,
+    }
+}
+
+fn one_bank_request() -> PortfolioRequest {
+    PortfolioRequest {
+        new_banks: vec![NewBank {
+            name: "VISION-BANK".to_string(),
+            bank_tz: chrono_tz::America::New_York,
+            new_deposits: vec![NewDeposit {
+                account: "1234".to_string(),
+                account_type: AccountType::Savings,
+                apy: dec!(2.4),
+                years: dec!(1),
+                amount: dec!(1000),
+                start_date_in_bank_tz: naive_date_2023_11_23(),
+            }],
+        }],
+        new_delta: NewDelta {
+            period: dec!(1),
+            period_unit: PeriodUnit::Month,
+        },
+        tenant_id: TenantId::default(),
+        correlation_id: None,
+    }
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_cancelled_by_caller() {
+    let span = initialize_test_span("test_calculate_portfolio_cancelled_by_caller");
+
+    let cancellation = Cancellation::default();
+    cancellation.token.cancel();
+    let result = calculate_portfolio(
+        one_bank_request(),
+        None,
+        EventOptions::default(),
+        cancellation,
+    )
+    .instrument(span)
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(CalculationHaltError::Cancelled(CancelReason::Cancelled))
+    ));
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_past_deadline() {
+    let span = initialize_test_span("test_calculate_portfolio_past_deadline");
+
+    let cancellation = Cancellation::new(Some(tokio::time::Instant::now()));
+    let result = calculate_portfolio(
+        one_bank_request(),
+        None,
+        EventOptions::default(),
+        cancellation,
+    )
+    .instrument(span)
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(CalculationHaltError::Cancelled(
+            CancelReason::DeadlineExceeded
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_publishes_bank_then_portfolio_events() {
+    let span =
+        initialize_test_span("test_calculate_portfolio_publishes_bank_then_portfolio_events");
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let mut portfolio_req = one_bank_request();
+    portfolio_req.tenant_id = "tenant-a".parse().unwrap();
+    let portfolio_resp = calculate_portfolio(
+        portfolio_req,
+        Some(Arc::new(publisher)),
+        EventOptions::default(),
+        Cancellation::default(),
+    )
+    .instrument(span)
+    .await
+    .unwrap();
+
+    let bank_event = events.recv().await.unwrap();
+    assert_eq!(bank_event.detail_type, BANK_LEVEL);
+    let bank_detail: serde_json::Value = serde_json::from_str(&bank_event.detail).unwrap();
+    assert_eq!(bank_detail["data"]["name"], "VISION-BANK");
+    assert_eq!(bank_detail["data"]["tenant_id"], "tenant-a");
+    assert_eq!(bank_detail["subject"], portfolio_resp.uuid.to_string());
+
+    let portfolio_event = events.recv().await.unwrap();
+    assert_eq!(portfolio_event.detail_type, PORTFOLIO_LEVEL);
+    let portfolio_detail: serde_json::Value =
+        serde_json::from_str(&portfolio_event.detail).unwrap();
+    assert_eq!(
+        portfolio_detail["data"]["uuid"],
+        portfolio_resp.uuid.to_string()
+    );
+    assert_eq!(
+        portfolio_detail["type"],
+        "drive-deposits.portfolio-level.v1"
+    );
+    // without a correlation id from the request, both events still share a generated one
+    assert_eq!(
+        portfolio_detail["correlationid"],
+        bank_detail["correlationid"]
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_events_use_configured_source() {
+    let span = initialize_test_span("test_calculate_portfolio_events_use_configured_source");
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let event_options = EventOptions {
+        source: "drive-deposits-staging".to_string(),
+        ..EventOptions::default()
+    };
+    calculate_portfolio(
+        one_bank_request(),
+        Some(Arc::new(publisher)),
+        event_options,
+        Cancellation::default(),
+    )
+    .instrument(span)
+    .await
+    .unwrap();
+
+    for detail_type in [BANK_LEVEL, PORTFOLIO_LEVEL] {
+        let event = events.recv().await.unwrap();
+        let detail: serde_json::Value = serde_json::from_str(&event.detail).unwrap();
+        assert_eq!(detail["source"], "drive-deposits-staging");
+        assert_eq!(
+            detail["type"],
+            format!("drive-deposits-staging.{}.v1", detail_type)
+        );
+        assert_eq!(
+            detail["dataschema"],
+            format!("urn:drive-deposits-staging:schema:{}:v1", detail_type)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_publishes_deposit_events_when_opted_in() {
+    let span =
+        initialize_test_span("test_calculate_portfolio_publishes_deposit_events_when_opted_in");
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let mut portfolio_req = one_bank_request();
+    portfolio_req.tenant_id = "tenant-a".parse().unwrap();
+    let event_options = EventOptions {
+        deposit_level_events: true,
+        ..EventOptions::default()
+    };
+    let portfolio_resp = calculate_portfolio(
+        portfolio_req,
+        Some(Arc::new(publisher)),
+        event_options,
+        Cancellation::default(),
+    )
+    .instrument(span)
+    .await
+    .unwrap();
+
+    // sent as soon as the bank is calculated, before the bank-level events
+    let deposit_event = events.recv().await.unwrap();
+    assert_eq!(deposit_event.detail_type, DEPOSIT_LEVEL);
+    let deposit_detail: serde_json::Value = serde_json::from_str(&deposit_event.detail).unwrap();
+    let bank = &portfolio_resp.banks[0];
+    assert_eq!(deposit_detail["type"], "drive-deposits.deposit-level.v1");
+    assert_eq!(
+        deposit_detail["data"]["portfolio_uuid"],
+        portfolio_resp.uuid.to_string()
+    );
+    assert_eq!(deposit_detail["data"]["bank_uuid"], bank.uuid.to_string());
+    assert_eq!(deposit_detail["data"]["bank_name"], "VISION-BANK");
+    assert_eq!(deposit_detail["data"]["bank_tz"], "America/New_York");
+    assert_eq!(deposit_detail["data"]["tenant_id"], "tenant-a");
+    assert_eq!(
+        deposit_detail["data"]["deposit"]["uuid"],
+        bank.deposits[0].uuid.to_string()
+    );
+
+    assert_eq!(events.recv().await.unwrap().detail_type, BANK_LEVEL);
+    let portfolio_event = events.recv().await.unwrap();
+    assert_eq!(portfolio_event.detail_type, PORTFOLIO_LEVEL);
+    let portfolio_detail: serde_json::Value =
+        serde_json::from_str(&portfolio_event.detail).unwrap();
+    assert_eq!(
+        portfolio_detail["correlationid"],
+        deposit_detail["correlationid"]
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_calculate_portfolio_publishes_deposit_events_of_each_bank_when_calculated() {
+    let span = initialize_test_span(
+        "test_calculate_portfolio_publishes_deposit_events_of_each_bank_when_calculated",
+    );
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let event_options = EventOptions {
+        deposit_level_events: true,
+        ..EventOptions::default()
+    };
+    let (bank_sender, mut bank_receiver) = channel(1);
+    let calculation = tokio::spawn(
+        calculate_portfolio_streaming(
+            three_bank_request(),
+            Some(Arc::new(publisher)),
+            event_options,
+            bank_sender,
+            Cancellation::default(),
+        )
+        .instrument(span),
+    );
+    // a bank is streamed after its task is done, by then its deposit events are out
+    while let Some(bank) = bank_receiver.recv().await {
+        let deposit_event = events.try_recv().unwrap();
+        assert_eq!(deposit_event.detail_type, DEPOSIT_LEVEL);
+        let deposit_detail: serde_json::Value =
+            serde_json::from_str(&deposit_event.detail).unwrap();
+        assert_eq!(deposit_detail["data"]["bank_uuid"], bank.uuid.to_string());
+    }
+    calculation.await.unwrap().unwrap();
+
+    assert_eq!(events.recv().await.unwrap().detail_type, BANK_LEVEL);
+}
+
+#[tokio::test]
+async fn test_cancelled_calculation_publishes_no_events() {
+    let span = initialize_test_span("test_cancelled_calculation_publishes_no_events");
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let cancellation = Cancellation::default();
+    cancellation.token.cancel();
+    let result = calculate_portfolio(
+        one_bank_request(),
+        Some(Arc::new(publisher)),
+        EventOptions::default(),
+        cancellation,
+    )
+    .instrument(span)
+    .await;
+
+    assert!(result.is_err());
+    assert!(events.recv().await.is_none());
+}
+
+// the caller goes away while the first event is being sent
+struct CancellingPublisher {
+    inner: ChannelPublisher,
+    token: CancellationToken,
+}
+
+#[async_trait]
+impl EventPublisher for CancellingPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.token.cancel();
+        self.inner.publish(detail_type, detail).await
+    }
+}
+
+#[tokio::test]
+async fn test_calculation_cancelled_while_publishing_sends_every_event() {
+    let span =
+        initialize_test_span("test_calculation_cancelled_while_publishing_sends_every_event");
+
+    let (publisher, mut events) = ChannelPublisher::new();
+    let cancellation = Cancellation::default();
+    let publisher = CancellingPublisher {
+        inner: publisher,
+        token: cancellation.token.clone(),
+    };
+    let result = calculate_portfolio(
+        one_bank_request(),
+        Some(Arc::new(publisher)),
+        EventOptions::default(),
+        cancellation,
+    )
+    .instrument(span)
+    .await;
+
+    assert!(result.is_ok());
+    assert_eq!(events.recv().await.unwrap().detail_type, BANK_LEVEL);
+    assert_eq!(events.recv().await.unwrap().detail_type, PORTFOLIO_LEVEL);
+}
@@ -0,0 +1,161 @@
+use pretty_assertions::assert_eq;
+use rust_decimal_macros::dec;
+
+use drive_deposits_cal_types::cal_types::PortfolioRequest;
+use drive_deposits_cal_types::convert::from_grpc_cal_request::RequestConversionError;
+use drive_deposits_proto_grpc_types::generated::{
+    AccountType, CalculatePortfolioRequest, NewBank, NewDelta, NewDeposit, PeriodUnit,
+};
+use helper::enable_tracing::initialize_test_span;
+use helper::test_data::naive_date_2023_11_23;
+
+mod helper;
+
+fn valid_new_deposit() -> NewDeposit {
+    NewDeposit {
+        account: "1234".to_string(),
+        account_type: AccountType::Checking as i32,
+        apy: "2.4".to_string(),
+        years: "1".to_string(),
+        amount: "1000".to_string(),
+        start_date_in_bank_tz: "2023-11-23".to_string(),
+    }
+}
+
+fn valid_new_bank() -> NewBank {
+    NewBank {
+        name: "VISION-BANK".to_string(),
+        bank_tz: "America/New_York".to_string(),
+        new_deposits: vec![valid_new_deposit()],
+    }
+}
+
+fn request_with_banks(new_banks: Vec<NewBank>) -> CalculatePortfolioRequest {
+    CalculatePortfolioRequest {
+        new_banks,
+        new_delta: Some(NewDelta {
+            period: "1".to_string(),
+            period_unit: PeriodUnit::Month as i32,
+        }),
+    }
+}
+
+#[test]
+fn test_try_from_grpc_request_valid() {
+    initialize_test_span("test_try_from_grpc_request_valid").in_scope(|| {
+        let grpc_request = request_with_banks(vec![valid_new_bank()]);
+        let cal_request = PortfolioRequest::try_from(grpc_request).unwrap();
+        let new_deposit = &cal_request.new_banks[0].new_deposits[0];
+        assert_eq!(new_deposit.apy, dec!(2.4));
+        assert_eq!(new_deposit.start_date_in_bank_tz, naive_date_2023_11_23());
+        assert_eq!(cal_request.new_delta.period_unit, PeriodUnit::Month);
+    });
+}
+
+#[test]
+fn test_try_from_grpc_request_collects_every_violation_with_full_path() {
+    initialize_test_span("test_try_from_grpc_request_collects_every_violation_with_full_path")
+        .in_scope(|| {
+            let mut invalid_bank = valid_new_bank();
+            invalid_bank.bank_tz = "Mars/Base".to_string();
+            invalid_bank.new_deposits[0].apy = "abc".to_string();
+            invalid_bank.new_deposits[0].start_date_in_bank_tz = "tomorrow".to_string();
+            invalid_bank.new_deposits[0].account_type = 42;
+            let mut grpc_request =
+                request_with_banks(vec![valid_new_bank(), valid_new_bank(), invalid_bank]);
+            grpc_request.new_delta = None;
+
+            let err: RequestConversionError = PortfolioRequest::try_from(grpc_request).unwrap_err();
+            let fields = err
+                .violations
+                .iter()
+                .map(|violation| violation.field.as_str())
+                .collect::<Vec<&str>>();
+            assert_eq!(
+                fields,
+                vec![
+                    "new_banks[2].bank_tz",
+                    "new_banks[2].new_deposits[0].account_type",
+                    "new_banks[2].new_deposits[0].apy",
+                    "new_banks[2].new_deposits[0].start_date_in_bank_tz",
+                    "new_delta",
+                ]
+            );
+            assert!(err.violations[2]
+                .description
+                .contains("Must be a valid decimal number"));
+        });
+}
+
+#[test]
+fn test_try_from_grpc_request_empty_banks_and_deposits() {
+    initialize_test_span("test_try_from_grpc_request_empty_banks_and_deposits").in_scope(|| {
+        let err = PortfolioRequest::try_from(request_with_banks(vec![])).unwrap_err();
+        assert_eq!(err.violations[0].field, "new_banks");
+
+        let mut bank_without_deposits = valid_new_bank();
+        bank_without_deposits.new_deposits = vec![];
+        let err = PortfolioRequest::try_from(request_with_banks(vec![bank_without_deposits]))
+            .unwrap_err();
+        assert_eq!(err.violations[0].field, "new_banks[0].new_deposits");
+    });
+}
+
+#[test]
+fn test_try_from_grpc_request_rejects_unspecified_account_type() {
+    initialize_test_span("test_try_from_grpc_request_rejects_unspecified_account_type").in_scope(
+        || {
+            let mut bank = valid_new_bank();
+            bank.new_deposits[0].account_type = AccountType::Unspecified as i32;
+            let err = PortfolioRequest::try_from(request_with_banks(vec![bank])).unwrap_err();
+            assert_eq!(err.violations.len(), 1);
+            assert_eq!(
+                err.violations[0].field,
+                "new_banks[0].new_deposits[0].account_type"
+            );
+            assert_eq!(err.violations[0].code, "invalid_account_type");
+        },
+    );
+}
+
+#[test]
+fn test_try_from_grpc_request_rejects_unspecified_period_unit() {
+    initialize_test_span("test_try_from_grpc_request_rejects_unspecified_period_unit").in_scope(
+        || {
+            let mut grpc_request = request_with_banks(vec![valid_new_bank()]);
+            grpc_request.new_delta = Some(NewDelta {
+                period: "1".to_string(),
+                period_unit: PeriodUnit::Unspecified as i32,
+            });
+            let err = PortfolioRequest::try_from(grpc_request).unwrap_err();
+            assert_eq!(err.violations.len(), 1);
+            assert_eq!(err.violations[0].field, "new_delta.period_unit");
+            assert_eq!(err.violations[0].code, "invalid_period_unit");
+        },
+    );
+}
+
+#[test]
+fn test_try_from_grpc_request_reports_empty_period_once() {
+    initialize_test_span("test_try_from_grpc_request_reports_empty_period_once").in_scope(|| {
+        let mut grpc_request = request_with_banks(vec![valid_new_bank()]);
+        grpc_request.new_delta = Some(NewDelta {
+            period: "".to_string(),
+            period_unit: PeriodUnit::Month as i32,
+        });
+        let err = PortfolioRequest::try_from(grpc_request).unwrap_err();
+        assert_eq!(err.violations.len(), 1);
+        assert_eq!(err.violations[0].field, "new_delta.period");
+        assert_eq!(err.violations[0].code, "length");
+
+        let mut grpc_request = request_with_banks(vec![valid_new_bank()]);
+        grpc_request.new_delta = Some(NewDelta {
+            period: "-1".to_string(),
+            period_unit: PeriodUnit::Month as i32,
+        });
+        let err = PortfolioRequest::try_from(grpc_request).unwrap_err();
+        assert_eq!(err.violations.len(), 1);
+        assert_eq!(err.violations[0].field, "new_delta.period");
+        assert_ne!(err.violations[0].code, "length");
+    });
+}
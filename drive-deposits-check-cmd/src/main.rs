@@ -1,12 +1,30 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Result};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args as ClapArgs, Parser, Subcommand};
 use tracing::{debug, info, info_span, Instrument};
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
 
-use drive_deposits_check_cmd::portfolio::calculate::process_input;
+use drive_deposits_check_cmd::portfolio::calculate::{
+    import_csv, process_input, process_rest_request,
+};
+use drive_deposits_check_cmd::portfolio::replay::{
+    dynamodb, jsonl, replay, DateRange, ReplayOptions,
+};
+use drive_deposits_event_source::publisher::create_publisher_without_outbox;
+use drive_deposits_rest_types::auth::{TenantId, DEFAULT_TENANT_ID};
+use drive_deposits_rest_types::report::ReportFormat;
+use drive_deposits_rest_types::rest_types::NewDelta;
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+use strum::VariantNames;
 
 #[derive(Debug, Parser)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 /// The `drive-deposits-cal-types` command performs comprehensive local calculations. It mirrors the calculations done by the REST gateway, which forwards requests to the gRPC deposits service for identical processing. This dual functionality allows `drive-deposits-cal-types` to serve as a reliable tool for verifying calculations from REST and gRPC submissions.
 ///
 /// The data types used in this command are consistent with those in the actual services, ensuring a dependable method for rapid local verification of calculations.
@@ -17,7 +35,116 @@ use drive_deposits_check_cmd::portfolio::calculate::process_input;
 struct Args {
     /// Input json file path
     #[arg(required(true))]
-    json_request_file_path: String,
+    json_request_file_path: Option<String>,
+
+    #[command(flatten)]
+    report: ReportArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Import deposits from a flat CSV file, one row per deposit, grouped into banks by the bank
+    /// column, and calculate them the same way as a json request file.
+    ///
+    /// Default header names are bank, bank_tz, account, account_type, apy, years, amount and
+    /// start_date_in_bank_tz; errors report the CSV row and column.
+    ImportCsv {
+        /// Input csv file path
+        csv_file_path: String,
+
+        /// Delta period, for example 1
+        #[arg(long)]
+        period: String,
+
+        /// Delta period unit: Day, Week, Month or Year
+        #[arg(long)]
+        period_unit: String,
+
+        /// Header names for fields that differ from the defaults, as field=Header pairs, for
+        /// example "bank=Bank Name,bank_tz=Time Zone,apy=APY %"
+        #[arg(long, default_value = "")]
+        columns: String,
+
+        /// Write the imported json request to this file instead of calculating it, for use as a
+        /// json request file later
+        #[arg(long)]
+        request_output: Option<String>,
+
+        #[command(flatten)]
+        report: ReportArgs,
+    },
+
+    /// Republish the events of stored portfolios through the configured event publisher, for a
+    /// new downstream consumer or after a writer fix.
+    ///
+    /// Reads the portfolios of a tenant from the DynamoDB table, or from a JSONL export with one
+    /// portfolio per line (a portfolio-level response, or the file publisher's output).
+    Replay {
+        /// JSONL export to read instead of DynamoDB
+        #[arg(long, conflicts_with_all = ["table", "tenant_id"])]
+        jsonl: Option<String>,
+
+        /// DynamoDB table; DRIVE_DEPOSITS_TABLE_NAME when not given
+        #[arg(long)]
+        table: Option<String>,
+
+        /// Tenant whose portfolios are read from DynamoDB
+        #[arg(long, default_value = DEFAULT_TENANT_ID)]
+        tenant_id: String,
+
+        /// Only portfolios created at or after this date (YYYY-MM-DD, midnight UTC) or RFC 3339 time
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Only portfolios created before this date (YYYY-MM-DD, midnight UTC) or RFC 3339 time
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Print the events that would be published, one json line each, and publish nothing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Publish at most this many events per second
+        #[arg(long)]
+        rate: Option<u32>,
+
+        /// Correlation id of every replayed event; a new one when not given
+        #[arg(long)]
+        correlation_id: Option<String>,
+    },
+}
+
+#[derive(Debug, ClapArgs)]
+struct ReportArgs {
+    /// Print a report of the calculation instead of the json response: deposits with bank
+    /// subtotals and portfolio totals, processing errors inline
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(ReportFormat::VARIANTS)
+            .try_map(|report_format| report_format.parse::<ReportFormat>())
+    )]
+    report: Option<ReportFormat>,
+
+    /// Write the report to this file instead of printing it
+    #[arg(long, requires = "report")]
+    report_output: Option<String>,
+}
+
+impl ReportArgs {
+    fn output(&self, response: String) -> Result<()> {
+        match (&self.report, &self.report_output) {
+            (Some(_), Some(report_output)) => {
+                std::fs::write(report_output, response)?;
+                info!("report written to: {}", report_output);
+            }
+            (Some(_), None) => println!("{}", response),
+            (None, _) => info!("response after processing request locally is: {}", response),
+        }
+        Ok(())
+    }
 }
 
 #[tokio::main]
@@ -30,13 +157,99 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
     debug!("args: {:?}", args);
-    let span = info_span!(
-        "drive_deposits_check_cmd",
-        json_request_file_path = args.json_request_file_path.as_str()
-    );
-    let response = process_input(args.json_request_file_path)
-        .instrument(span)
-        .await?;
-    info!("response after processing request locally is: {}", response);
+    match (args.command, args.json_request_file_path) {
+        (
+            Some(Command::ImportCsv {
+                csv_file_path,
+                period,
+                period_unit,
+                columns,
+                request_output,
+                report,
+            }),
+            _,
+        ) => {
+            let span = info_span!(
+                "drive_deposits_check_cmd_import_csv",
+                csv_file_path = csv_file_path.as_str()
+            );
+            let new_delta = NewDelta {
+                period,
+                period_unit,
+            };
+            let rest_req = span.in_scope(|| import_csv(csv_file_path, &columns, new_delta))?;
+            if let Some(request_output) = request_output {
+                std::fs::write(&request_output, serde_json::to_string_pretty(&rest_req)?)?;
+                info!("imported csv request written to: {}", request_output);
+                return Ok(());
+            }
+            let response = process_rest_request(rest_req, report.report)
+                .instrument(span)
+                .await?;
+            report.output(response)?;
+        }
+        (
+            Some(Command::Replay {
+                jsonl,
+                table,
+                tenant_id,
+                from,
+                to,
+                dry_run,
+                rate,
+                correlation_id,
+            }),
+            _,
+        ) => {
+            let span = info_span!("drive_deposits_check_cmd_replay", dry_run);
+            let date_range = DateRange::new(from.as_deref(), to.as_deref())?;
+            let portfolios = match jsonl {
+                Some(jsonl) => span.in_scope(|| jsonl::read_portfolios(&jsonl, &date_range))?,
+                None => {
+                    let table = match table {
+                        Some(table) => table,
+                        None => std::env::var("DRIVE_DEPOSITS_TABLE_NAME")?,
+                    };
+                    let tenant_id: TenantId = tenant_id.parse()?;
+                    let client = dynamodb::client().await;
+                    dynamodb::read_portfolios(&client, &table, &tenant_id, &date_range)
+                        .instrument(span.clone())
+                        .await?
+                }
+            };
+            let options = ReplayOptions {
+                dry_run,
+                rate,
+                correlation_id: correlation_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+                publisher_config: EventPublisherConfig::load()?,
+            };
+            info!(
+                "replaying {} portfolios with correlation id {}",
+                portfolios.len(),
+                options.correlation_id
+            );
+            // replayed events are published before exiting, never left in an outbox
+            let publisher = if dry_run {
+                None
+            } else {
+                create_publisher_without_outbox().await?
+            };
+            replay(portfolios, &options, publisher)
+                .instrument(span)
+                .await?;
+        }
+        (None, Some(json_request_file_path)) => {
+            let span = info_span!(
+                "drive_deposits_check_cmd",
+                json_request_file_path = json_request_file_path.as_str()
+            );
+            let response = process_input(json_request_file_path, args.report.report)
+                .instrument(span)
+                .await?;
+            args.report.output(response)?;
+        }
+        // clap normally requires the json file path when there is no subcommand
+        (None, None) => bail!("Either a json request file path or a subcommand is required"),
+    }
     Ok(())
 }
@@ -1 +1,2 @@
 pub mod calculate;
+pub mod replay;
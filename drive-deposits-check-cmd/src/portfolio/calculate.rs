@@ -3,16 +3,21 @@ use tracing::{debug, instrument};
 use validator::Validate;
 
 use drive_deposits_cal_types::cal_types::PortfolioRequest as CalBankRequest;
-use drive_deposits_cal_types::math::engine::calculate_portfolio;
-use drive_deposits_event_source::eb::create_eb;
+use drive_deposits_cal_types::math::engine::{calculate_portfolio, Cancellation, EventOptions};
+use drive_deposits_event_source::publisher::create_publisher;
 use drive_deposits_proto_grpc_types::generated::{
     CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
     CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
 };
+use drive_deposits_rest_types::csv_import::{
+    import_portfolio_request, CsvColumnMapping, Error as CsvImportError,
+};
+use drive_deposits_rest_types::report::{render_report, Error as ReportError, ReportFormat};
 use drive_deposits_rest_types::rest_types::{
     CalculatePortfolioRequest as RestCalculatePortfolioRequest,
-    CalculatePortfolioResponse as RestCalculatePortfolioResponse,
+    CalculatePortfolioResponse as RestCalculatePortfolioResponse, NewDelta as RestNewDelta,
 };
+use drive_deposits_rest_types::server_config::{Error as ServerConfigError, EventPublisherConfig};
 
 #[derive(Default, Debug, Error)]
 pub enum Error {
@@ -29,17 +34,36 @@ pub enum Error {
     #[error("Validation errors: {0}")]
     Validation(#[from] validator::ValidationErrors),
 
+    #[error(transparent)]
+    CsvImport(#[from] CsvImportError),
+
+    #[error(transparent)]
+    Report(#[from] ReportError),
+
+    #[error("Rest to grpc request conversion error: {0}")]
+    RestToGrpc(#[from] drive_deposits_proto_grpc_types::convert::from_rest_grpc_request::Error),
+
+    #[error("Request conversion error: {0}")]
+    RequestConversion(
+        #[from] drive_deposits_cal_types::convert::from_grpc_cal_request::RequestConversionError,
+    ),
+
     #[error("Calculation halt error could not progress, so had to halt: errors: {0}")]
     CalculationHalt(#[from] drive_deposits_cal_types::math::engine::CalculationHaltError),
 
-    #[error("Drive Deposits EventBridge error: {0}")]
-    DriveDepositsEventBridgeError(
-        #[from] drive_deposits_event_source::eb::DriveDepositsEventBridgeError,
-    ),
+    #[error("Drive Deposits event publisher error: {0}")]
+    EventPublisherError(#[from] drive_deposits_event_source::publisher::EventPublisherError),
+
+    #[error("Drive Deposits event publisher config error: {0}")]
+    EventPublisherConfig(#[from] ServerConfigError),
 }
 
+// json response, or the report of it when a report format is given
 #[instrument]
-pub async fn process_input(file_path: String) -> Result<String, Error> {
+pub async fn process_input(
+    file_path: String,
+    report_format: Option<ReportFormat>,
+) -> Result<String, Error> {
     let data = std::fs::read_to_string(file_path)?;
 
     // deserialize file into RestCalculatePortfolioRequest request and validate input data
@@ -48,18 +72,48 @@ pub async fn process_input(file_path: String) -> Result<String, Error> {
     validate(&rest_req)?;
     debug!("Validated rest: {:?}", rest_req);
 
+    process_rest_request(rest_req, report_format).await
+}
+
+// rows are grouped into banks and validated with the same rest_types validators as a json file
+#[instrument]
+pub fn import_csv(
+    csv_file_path: String,
+    columns: &str,
+    new_delta: RestNewDelta,
+) -> Result<RestCalculatePortfolioRequest, Error> {
+    let mapping: CsvColumnMapping = columns.parse()?;
+    let file = std::fs::File::open(csv_file_path)?;
+    let rest_req = import_portfolio_request(file, &mapping, new_delta)?;
+    debug!("Imported and validated rest from csv: {:?}", rest_req);
+    Ok(rest_req)
+}
+
+pub async fn process_rest_request(
+    rest_req: RestCalculatePortfolioRequest,
+    report_format: Option<ReportFormat>,
+) -> Result<String, Error> {
     // convert from rest CalculatePortfolioRequest to grpc CalculatePortfolioRequest
-    let grpc_req: GrpcCalculatePortfolioRequest = rest_req.into();
+    let grpc_req: GrpcCalculatePortfolioRequest = rest_req.try_into()?;
     debug!("Converted from rest to grpc: {:?}", grpc_req);
 
     // convert grpc CalculatePortfolioRequest to calculator CalculatePortfolioRequest
-    let cal_req: CalBankRequest = grpc_req.into();
+    let cal_req: CalBankRequest = grpc_req.try_into()?;
     debug!("Converted from grpc to cal: {:?}", cal_req);
 
-    let drive_deposits_eb = create_eb().await?;
+    let event_publisher = create_publisher().await?;
+
+    let event_options = EventOptions::from(&EventPublisherConfig::load()?);
 
     // process calculation for calculator CalculatePortfolioRequest
-    let cal_resp = calculate_portfolio(cal_req, drive_deposits_eb).await?;
+    // a command line run is never cancelled
+    let cal_resp = calculate_portfolio(
+        cal_req,
+        event_publisher,
+        event_options,
+        Cancellation::default(),
+    )
+    .await?;
     debug!("calculated response: {:?}", cal_resp);
 
     // convert response fom calculator CalculatePortfolioResponse to grpc CalculatePortfolioResponse
@@ -70,6 +124,10 @@ pub async fn process_input(file_path: String) -> Result<String, Error> {
 
     debug!("rest response: {:?}", rest_resp);
 
+    if let Some(report_format) = report_format {
+        return Ok(render_report(&rest_resp, report_format)?);
+    }
+
     let json_resp = serde_json::to_string(&rest_resp)?;
     // pretty print serialized json
     let pretty_json_resp = serde_json::to_string_pretty(&rest_resp)?;
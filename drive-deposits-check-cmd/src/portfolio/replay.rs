@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::{json, to_string, Value};
+use thiserror::Error;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+use drive_deposits_event_source::payload_types::{CalculatePortfolioResponse, DepositLevel};
+use drive_deposits_event_source::publisher::{
+    EventPublisherError, SharedEventPublisher, BANK_LEVEL, DEPOSIT_LEVEL, PORTFOLIO_LEVEL,
+};
+use drive_deposits_rest_types::cloud_event::CloudEvent;
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+pub mod dynamodb;
+pub mod jsonl;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("JSON parsing error: {0}")]
+    JsonParse(#[from] serde_json::Error),
+
+    #[error("Line {line} of the JSONL export: {message}")]
+    JsonlLine { line: usize, message: String },
+
+    #[error("Invalid date {0:?}; expected YYYY-MM-DD or an RFC 3339 timestamp")]
+    InvalidDate(String),
+
+    #[error("Portfolio {uuid} has an invalid created_at {created_at:?}")]
+    InvalidCreatedAt { uuid: String, created_at: String },
+
+    #[error("Nothing to replay to: set EVENT_PUBLISHER or use --dry-run")]
+    MissingPublisher,
+
+    #[error("DynamoDB error: {0}")]
+    DynamoDb(#[from] dynamodb::Error),
+
+    #[error("Drive Deposits event publisher error: {0}")]
+    EventPublisher(#[from] EventPublisherError),
+}
+
+// portfolios created in [from, to); either end can be open
+#[derive(Debug, Default, Clone)]
+pub struct DateRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    pub fn new(from: Option<&str>, to: Option<&str>) -> Result<Self, Error> {
+        Ok(Self {
+            from: from.map(parse_date_bound).transpose()?,
+            to: to.map(parse_date_bound).transpose()?,
+        })
+    }
+
+    pub fn contains(&self, portfolio_uuid: &str, created_at: &str) -> Result<bool, Error> {
+        let created_at = DateTime::parse_from_rfc3339(created_at)
+            .map_err(|_| Error::InvalidCreatedAt {
+                uuid: portfolio_uuid.to_string(),
+                created_at: created_at.to_string(),
+            })?
+            .with_timezone(&Utc);
+        Ok(self.from.is_none_or(|from| created_at >= from)
+            && self.to.is_none_or(|to| created_at < to))
+    }
+}
+
+// a plain date is midnight UTC of that day
+fn parse_date_bound(value: &str) -> Result<DateTime<Utc>, Error> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .map_err(|_| Error::InvalidDate(value.to_string()))
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub dry_run: bool,
+    // events per second sent to the publisher; unlimited when unset
+    pub rate: Option<u32>,
+    // shared by every replayed event, so consumers can tell a replay apart from the original run
+    pub correlation_id: String,
+    // source, detail-type prefix and deposit-level opt in of the replayed events
+    pub publisher_config: EventPublisherConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayEvent {
+    pub detail_type: &'static str,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub portfolios: usize,
+    pub events: usize,
+}
+
+// the events the engine publishes for a calculation, in the same order: deposit-level when opted
+// in, bank-level for each bank, then portfolio-level
+pub fn portfolio_events(
+    portfolio: &CalculatePortfolioResponse,
+    source: &str,
+    correlation_id: &str,
+    deposit_level: bool,
+) -> Result<Vec<ReplayEvent>, Error> {
+    let mut events = Vec::new();
+    if deposit_level {
+        for bank in &portfolio.banks {
+            for deposit in &bank.deposits {
+                let deposit_level = DepositLevel {
+                    portfolio_uuid: portfolio.uuid.clone(),
+                    bank_uuid: bank.uuid.clone(),
+                    bank_name: bank.name.clone(),
+                    bank_tz: bank.bank_tz.clone(),
+                    tenant_id: portfolio.tenant_id.clone(),
+                    deposit: deposit.clone(),
+                };
+                events.push(cloud_event(
+                    DEPOSIT_LEVEL,
+                    source,
+                    portfolio,
+                    correlation_id,
+                    deposit_level,
+                )?);
+            }
+        }
+    }
+    for bank in &portfolio.banks {
+        let mut bank = bank.clone();
+        bank.tenant_id = portfolio.tenant_id.clone();
+        events.push(cloud_event(
+            BANK_LEVEL,
+            source,
+            portfolio,
+            correlation_id,
+            bank,
+        )?);
+    }
+    events.push(cloud_event(
+        PORTFOLIO_LEVEL,
+        source,
+        portfolio,
+        correlation_id,
+        portfolio,
+    )?);
+    Ok(events)
+}
+
+// wrapped in a CloudEvents envelope with a new event id, like the engine's events
+fn cloud_event<T: Serialize>(
+    detail_type: &'static str,
+    source: &str,
+    portfolio: &CalculatePortfolioResponse,
+    correlation_id: &str,
+    data: T,
+) -> Result<ReplayEvent, Error> {
+    Ok(ReplayEvent {
+        detail_type,
+        detail: to_string(&CloudEvent::new(
+            Uuid::new_v4().to_string(),
+            source,
+            detail_type,
+            portfolio.uuid.clone(),
+            correlation_id.to_string(),
+            data,
+        ))?,
+    })
+}
+
+// a dry run prints each event as the file publisher would write it, and publishes nothing
+#[instrument(skip(portfolios, publisher))]
+pub async fn replay(
+    portfolios: Vec<CalculatePortfolioResponse>,
+    options: &ReplayOptions,
+    publisher: Option<SharedEventPublisher>,
+) -> Result<ReplaySummary, Error> {
+    let publisher = match (options.dry_run, publisher) {
+        (true, _) => None,
+        (false, Some(publisher)) => Some(publisher),
+        (false, None) => return Err(Error::MissingPublisher),
+    };
+    let mut rate_limit = options.rate.filter(|rate| *rate > 0).map(|rate| {
+        let mut rate_limit = interval(Duration::from_secs(1) / rate);
+        rate_limit.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        rate_limit
+    });
+    let mut summary = ReplaySummary::default();
+    for portfolio in &portfolios {
+        let events = portfolio_events(
+            portfolio,
+            &options.publisher_config.source,
+            &options.correlation_id,
+            options.publisher_config.deposit_level_events,
+        )?;
+        debug!(
+            "replaying {} events for portfolio {}",
+            events.len(),
+            portfolio.uuid
+        );
+        for event in events {
+            match publisher.as_ref() {
+                Some(publisher) => {
+                    if let Some(rate_limit) = rate_limit.as_mut() {
+                        rate_limit.tick().await;
+                    }
+                    publisher.publish(event.detail_type, event.detail).await?;
+                }
+                None => println!(
+                    "{}",
+                    json!({
+                        "source": options.publisher_config.source,
+                        "detail-type": options.publisher_config.detail_type(event.detail_type),
+                        "detail": serde_json::from_str::<Value>(&event.detail)?,
+                    })
+                ),
+            }
+            summary.events += 1;
+        }
+        summary.portfolios += 1;
+    }
+    info!(
+        "replayed {} events for {} portfolios{}",
+        summary.events,
+        summary.portfolios,
+        if options.dry_run { " (dry run)" } else { "" }
+    );
+    Ok(summary)
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::env::var;
+
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::query::QueryError;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use serde_json::from_str;
+use thiserror::Error as ThisError;
+use tracing::{debug, instrument};
+
+use drive_deposits_event_source::payload_types::{Bank, CalculatePortfolioResponse, Deposit};
+use drive_deposits_lambda_db_types::convert::reader::with_level_context::LevelSpecificItemReaderError;
+use drive_deposits_lambda_db_types::db_item_types::{
+    portfolio_partition_key, portfolios_partition_key, BankLevelItem, DepositLevelItem,
+    PortfolioLevelItem,
+};
+use drive_deposits_rest_types::auth::TenantId;
+use drive_deposits_rest_types::server_config::LOCALSTACK_ENDPOINT;
+
+use super::{DateRange, Error as ReplayError};
+
+// each deposit is written once per sort criteria; the growth ones are enough to rebuild from
+const BANK_SORT_KEY_PREFIX: &str = "BANK#PERIOD#";
+const DEPOSIT_SORT_KEY_PREFIX: &str = "DEPOSIT#PERIOD#";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    // boxed, the sdk error would make every Result carrying this error very large
+    #[error("Query error: {0}")]
+    Query(Box<SdkError<QueryError>>),
+
+    #[error("Item reader error: {0}")]
+    ItemReader(#[from] LevelSpecificItemReaderError),
+
+    #[error("Stored outcome of portfolio {uuid} is not valid json: {source}")]
+    Outcome {
+        uuid: String,
+        source: serde_json::Error,
+    },
+}
+
+impl From<SdkError<QueryError>> for Error {
+    fn from(err: SdkError<QueryError>) -> Self {
+        Self::Query(Box::new(err))
+    }
+}
+
+// same table and localstack switch as the writer lambda
+pub async fn client() -> Client {
+    let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
+    if var("USE_LOCALSTACK").is_ok_and(|value| value.trim().eq_ignore_ascii_case("true")) {
+        config_loader = config_loader.endpoint_url(LOCALSTACK_ENDPOINT);
+    }
+    Client::new(&config_loader.load().await)
+}
+
+// the tenant's portfolios created in the date range, rebuilt from their portfolio, bank and
+// deposit level items
+#[instrument(skip(client, date_range))]
+pub async fn read_portfolios(
+    client: &Client,
+    table: &str,
+    tenant_id: &TenantId,
+    date_range: &DateRange,
+) -> Result<Vec<CalculatePortfolioResponse>, ReplayError> {
+    let portfolio_items = query_all(
+        client,
+        table,
+        portfolios_partition_key(tenant_id.as_str()),
+        None,
+    )
+    .await?
+    .into_iter()
+    .map(PortfolioLevelItem::try_from)
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(Error::from)?;
+    debug!("{} portfolios stored for the tenant", portfolio_items.len());
+
+    let mut portfolios = Vec::new();
+    for portfolio_item in portfolio_items {
+        if !date_range.contains(&portfolio_item.portfolio_uuid, &portfolio_item.created_at)? {
+            continue;
+        }
+        portfolios.push(read_portfolio(client, table, tenant_id, portfolio_item).await?);
+    }
+    Ok(portfolios)
+}
+
+async fn read_portfolio(
+    client: &Client,
+    table: &str,
+    tenant_id: &TenantId,
+    portfolio_item: PortfolioLevelItem,
+) -> Result<CalculatePortfolioResponse, Error> {
+    let uuid = portfolio_item.portfolio_uuid;
+    let partition_key = portfolio_partition_key(tenant_id.as_str(), &uuid);
+    let outcome_error = |source| Error::Outcome {
+        uuid: uuid.clone(),
+        source,
+    };
+    let bank_items = query_all(
+        client,
+        table,
+        partition_key.clone(),
+        Some(BANK_SORT_KEY_PREFIX),
+    )
+    .await?
+    .into_iter()
+    .map(BankLevelItem::try_from)
+    .collect::<Result<Vec<_>, _>>()?;
+    let deposit_items = query_all(client, table, partition_key, Some(DEPOSIT_SORT_KEY_PREFIX))
+        .await?
+        .into_iter()
+        .map(DepositLevelItem::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut banks = Vec::with_capacity(bank_items.len());
+    for bank_item in bank_items {
+        let mut deposits = Vec::new();
+        for deposit_item in deposit_items
+            .iter()
+            .filter(|deposit_item| deposit_item.bank_uuid == bank_item.bank_uuid)
+        {
+            deposits.push(Deposit {
+                uuid: deposit_item.deposit_uuid.clone(),
+                account: deposit_item.account.clone(),
+                account_type: deposit_item.account_type.clone(),
+                apy: deposit_item.apy.clone(),
+                years: deposit_item.years.clone(),
+                outcome: from_str(&deposit_item.outcome_as_json).map_err(outcome_error)?,
+                outcome_with_dates: from_str(&deposit_item.outcome_with_dates_as_json)
+                    .map_err(outcome_error)?,
+            });
+        }
+        banks.push(Bank {
+            uuid: bank_item.bank_uuid,
+            name: bank_item.bank_name,
+            bank_tz: bank_item.bank_tz,
+            deposits,
+            outcome: from_str(&bank_item.outcome_as_json).map_err(outcome_error)?,
+            tenant_id: None,
+        });
+    }
+    Ok(CalculatePortfolioResponse {
+        outcome: from_str(&portfolio_item.outcome_as_json).map_err(outcome_error)?,
+        uuid: uuid.clone(),
+        banks,
+        created_at: portfolio_item.created_at,
+        tenant_id: Some(tenant_id.to_string()),
+    })
+}
+
+// every page of the partition, or of the items whose sort key starts with the prefix
+async fn query_all(
+    client: &Client,
+    table: &str,
+    partition_key: String,
+    sort_key_prefix: Option<&str>,
+) -> Result<Vec<HashMap<String, AttributeValue>>, Error> {
+    let mut items = Vec::new();
+    let mut exclusive_start_key = None;
+    loop {
+        let mut request = client
+            .query()
+            .table_name(table)
+            .expression_attribute_names("#partitionKeyName", "PK")
+            .expression_attribute_values(
+                ":partitionKeyValue",
+                AttributeValue::S(partition_key.clone()),
+            )
+            .set_exclusive_start_key(exclusive_start_key);
+        request = match sort_key_prefix {
+            Some(sort_key_prefix) => request
+                .key_condition_expression(
+                    "#partitionKeyName = :partitionKeyValue AND begins_with(#sortKeyName, :sortKeyValue)",
+                )
+                .expression_attribute_names("#sortKeyName", "SK")
+                .expression_attribute_values(
+                    ":sortKeyValue",
+                    AttributeValue::S(sort_key_prefix.to_string()),
+                ),
+            None => request.key_condition_expression("#partitionKeyName = :partitionKeyValue"),
+        };
+        let query_resp = request.send().await?;
+        items.extend(query_resp.items.unwrap_or_default());
+        match query_resp.last_evaluated_key {
+            Some(last_evaluated_key) => exclusive_start_key = Some(last_evaluated_key),
+            None => break,
+        }
+    }
+    Ok(items)
+}
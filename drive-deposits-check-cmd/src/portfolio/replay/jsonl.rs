@@ -0,0 +1,68 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use serde_json::{from_value, Value};
+use tracing::{debug, instrument};
+
+use drive_deposits_event_source::payload_types::CalculatePortfolioResponse;
+use drive_deposits_event_source::publisher::PORTFOLIO_LEVEL;
+
+use super::{DateRange, Error};
+
+// One portfolio per line: a CalculatePortfolioResponse, or an event line as the file publisher
+// writes it, where only portfolio-level events are used and the others skipped
+#[instrument(skip(date_range))]
+pub fn read_portfolios(
+    path: &str,
+    date_range: &DateRange,
+) -> Result<Vec<CalculatePortfolioResponse>, Error> {
+    let mut portfolios = Vec::new();
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(portfolio) = portfolio_of_line(&line).map_err(|message| Error::JsonlLine {
+            line: index + 1,
+            message,
+        })?
+        else {
+            continue;
+        };
+        if date_range.contains(&portfolio.uuid, &portfolio.created_at)? {
+            portfolios.push(portfolio);
+        } else {
+            debug!(
+                "portfolio {} created at {} is outside the date range",
+                portfolio.uuid, portfolio.created_at
+            );
+        }
+    }
+    Ok(portfolios)
+}
+
+fn portfolio_of_line(line: &str) -> Result<Option<CalculatePortfolioResponse>, String> {
+    let mut value: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    if let Some(detail_type) = value.get("detail-type") {
+        // detail types can be prefixed
+        if !detail_type
+            .as_str()
+            .is_some_and(|detail_type| detail_type.ends_with(PORTFOLIO_LEVEL))
+        {
+            return Ok(None);
+        }
+        value = value["detail"].take();
+    }
+    if value.get("specversion").is_some() {
+        if value.get("data").is_none() && value.get("dataref").is_some() {
+            return Err(format!(
+                "event data is claim checked at {}; replay from DynamoDB instead",
+                value["dataref"]
+            ));
+        }
+        value = value["data"].take();
+    }
+    from_value(value)
+        .map(Some)
+        .map_err(|err| format!("not a portfolio: {}", err))
+}
@@ -17,11 +17,11 @@ fn test_portfolio_request_two_banks_json_valid() -> Result<()> {
             //     "RUST_LOG",
             //     "test=debug,drive_deposits_check_cmd=debug,drive_deposits_cal_types=debug",
             // )
-            // SEND_CAL_EVENTS = "true"
+            // EVENT_PUBLISHER = "eventbridge"
             // only when manually want to test
             // actually sends events to localstack aws event bridge
-            // test will fail if SEND_CAL_EVENTS is true and event bridge is not set up
-            .env("SEND_CAL_EVENTS", "true")
+            // test will fail if EVENT_PUBLISHER is eventbridge and event bridge is not set up
+            .env("EVENT_PUBLISHER", "eventbridge")
             .env("USE_LOCALSTACK", "true")
             .arg(json_request_file_path)
             .assert()
@@ -79,3 +79,286 @@ fn test_portfolio_request_two_banks_json_invalid() -> Result<()> {
         Ok(())
     })
 }
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_import_csv_invalid_reports_row_and_column() -> Result<()> {
+    initialize_test_span("test_import_csv_invalid_reports_row_and_column").in_scope(|| {
+        let csv_file_path = "tests/data/portfolio_deposits_invalid.csv";
+        Command::cargo_bin("drive-deposits-check-cmd")?
+            .args(["import-csv", csv_file_path, "--period", "1"])
+            .args(["--period-unit", "Month"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "row 3, column 5 (apy): Incorrect value: Hello. Must be a valid decimal number.",
+            ))
+            .stderr(predicate::str::contains(
+                "row 4, column 2 (bank_tz): Error: failed to parse timezone",
+            ));
+
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_import_csv_with_column_mapping_groups_rows_into_banks() -> Result<()> {
+    initialize_test_span("test_import_csv_with_column_mapping_groups_rows_into_banks").in_scope(
+        || {
+            let csv_file_path = "tests/data/portfolio_deposits_valid.csv";
+            let request_output = std::env::temp_dir().join("portfolio_deposits_valid_request.json");
+            Command::cargo_bin("drive-deposits-check-cmd")?
+                .args(["import-csv", csv_file_path, "--period", "1"])
+                .args(["--period-unit", "Month", "--request-output"])
+                .arg(&request_output)
+                .args([
+                    "--columns",
+                    "bank=Bank Name,bank_tz=Time Zone,account_type=Type,apy=APY %,start_date_in_bank_tz=Start Date",
+                ])
+                .assert()
+                .success();
+
+            let rest_request: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&request_output)?)?;
+            let new_banks = rest_request["new_banks"].as_array().unwrap();
+            assert_eq!(new_banks.len(), 2);
+            assert_eq!(new_banks[0]["name"], "VISION-BANK");
+            assert_eq!(new_banks[0]["new_deposits"].as_array().unwrap().len(), 3);
+            assert_eq!(new_banks[1]["name"], "PEACEMAKER");
+            assert_eq!(rest_request["new_delta"]["period_unit"], "Month");
+
+            Ok(())
+        },
+    )
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_csv_report_has_bank_subtotals_and_portfolio_total() -> Result<()> {
+    initialize_test_span("test_csv_report_has_bank_subtotals_and_portfolio_total").in_scope(|| {
+        let csv_file_path = "tests/data/portfolio_deposits_valid.csv";
+        let report_output = std::env::temp_dir().join("portfolio_deposits_valid_report.csv");
+        Command::cargo_bin("drive-deposits-check-cmd")?
+            .env("EVENT_PUBLISHER", "none")
+            .args(["import-csv", csv_file_path, "--period", "1"])
+            .args([
+                "--period-unit",
+                "Month",
+                "--columns",
+                "bank=Bank Name,bank_tz=Time Zone,account_type=Type,apy=APY %,start_date_in_bank_tz=Start Date",
+                "--report",
+                "csv",
+                "--report-output",
+            ])
+            .arg(&report_output)
+            .assert()
+            .success();
+
+        let report = std::fs::read_to_string(&report_output)?;
+        // banks are calculated concurrently so only the last row has a fixed position
+        let mut row_types = report
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').next().unwrap_or_default())
+            .collect::<Vec<&str>>();
+        assert_eq!(row_types.pop(), Some("portfolio_total"));
+        row_types.sort();
+        assert_eq!(
+            row_types,
+            vec![
+                "bank_subtotal",
+                "bank_subtotal",
+                "deposit",
+                "deposit",
+                "deposit",
+                "deposit"
+            ]
+        );
+        assert!(report.contains("PEACEMAKER,America/New_York,1234,BrokerageCertificateOfDeposit,2.4,7,2024-02-16,2031-02-14,1 Month,21.68,10990,1846.32,12836.32,"));
+        assert!(report.contains(
+            "portfolio_total,,,Total,2 banks,,,,,1 Month,121.60,37480.50,16696.98,54177.48,"
+        ));
+
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_file_event_publisher_appends_ndjson_events() -> Result<()> {
+    initialize_test_span("test_file_event_publisher_appends_ndjson_events").in_scope(|| {
+        let json_request_file_path = "tests/data/portfolio_request_two_banks_json_valid.json";
+        let events_path = std::env::temp_dir().join("portfolio_request_two_banks_events.ndjson");
+        let _ = std::fs::remove_file(&events_path);
+        Command::cargo_bin("drive-deposits-check-cmd")?
+            .env("EVENT_PUBLISHER", "file")
+            .env("EVENT_PUBLISHER_FILE_PATH", &events_path)
+            .arg(json_request_file_path)
+            .assert()
+            .success();
+
+        let events = std::fs::read_to_string(&events_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        // bank-level events in completion order, then the portfolio-level one
+        let detail_types = events
+            .iter()
+            .map(|event| event["detail-type"].as_str().unwrap_or_default())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            detail_types,
+            vec!["bank-level", "bank-level", "bank-level", "portfolio-level"]
+        );
+        assert_eq!(events[3]["source"], "drive-deposits");
+        assert_eq!(
+            events[3]["detail"]["data"]["banks"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_replay_dry_run_prints_events_of_portfolios_in_date_range() -> Result<()> {
+    initialize_test_span("test_replay_dry_run_prints_events_of_portfolios_in_date_range").in_scope(
+        || {
+            let cmd = Command::cargo_bin("drive-deposits-check-cmd")?
+                .env_remove("EVENT_PUBLISHER")
+                .args([
+                    "replay",
+                    "--jsonl",
+                    "tests/data/portfolios_export.jsonl",
+                    "--from",
+                    "2024-03-01",
+                    "--correlation-id",
+                    "replay-1",
+                    "--dry-run",
+                ])
+                .assert()
+                .success();
+
+            let stdout = String::from_utf8_lossy(&cmd.get_output().stdout).to_string();
+            let events = stdout
+                .lines()
+                .filter(|line| line.starts_with('{'))
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<serde_json::Value>, _>>()?;
+            // only the portfolio created in March, read from its portfolio-level event line
+            let detail_types = events
+                .iter()
+                .map(|event| event["detail-type"].as_str().unwrap_or_default())
+                .collect::<Vec<&str>>();
+            assert_eq!(detail_types, vec!["bank-level", "portfolio-level"]);
+            assert_eq!(
+                events[1]["detail"]["data"]["uuid"],
+                "12222222-2222-4222-8222-222222222222"
+            );
+            assert_eq!(events[1]["detail"]["correlationid"], "replay-1");
+            assert_eq!(events[0]["detail"]["data"]["tenant_id"], "tenant-a");
+
+            Ok(())
+        },
+    )
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_replay_republishes_through_file_event_publisher() -> Result<()> {
+    initialize_test_span("test_replay_republishes_through_file_event_publisher").in_scope(|| {
+        let events_path = std::env::temp_dir().join("portfolios_export_replayed.ndjson");
+        let _ = std::fs::remove_file(&events_path);
+        Command::cargo_bin("drive-deposits-check-cmd")?
+            .env("EVENT_PUBLISHER", "file")
+            .env("EVENT_PUBLISHER_FILE_PATH", &events_path)
+            .env("EVENT_PUBLISHER_DEPOSIT_LEVEL_EVENTS", "true")
+            .args([
+                "replay",
+                "--jsonl",
+                "tests/data/portfolios_export.jsonl",
+                "--to",
+                "2024-03-01",
+                "--rate",
+                "100",
+            ])
+            .assert()
+            .success();
+
+        let events = std::fs::read_to_string(&events_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        let detail_types = events
+            .iter()
+            .map(|event| event["detail-type"].as_str().unwrap_or_default())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            detail_types,
+            vec!["deposit-level", "bank-level", "portfolio-level"]
+        );
+        assert_eq!(
+            events[0]["detail"]["data"]["deposit"]["uuid"],
+            "31111111-1111-4111-8111-111111111111"
+        );
+        assert_eq!(
+            events[2]["detail"]["correlationid"],
+            events[0]["detail"]["correlationid"]
+        );
+
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_replay_publishes_before_exiting_with_outbox_dir_set() -> Result<()> {
+    initialize_test_span("test_replay_publishes_before_exiting_with_outbox_dir_set").in_scope(
+        || {
+            let events_path = std::env::temp_dir().join("portfolios_export_replayed_outbox.ndjson");
+            let outbox_dir = std::env::temp_dir().join("drive-deposits-replay-outbox");
+            let _ = std::fs::remove_file(&events_path);
+            let _ = std::fs::remove_dir_all(&outbox_dir);
+            Command::cargo_bin("drive-deposits-check-cmd")?
+                .env("EVENT_PUBLISHER", "file")
+                .env("EVENT_PUBLISHER_FILE_PATH", &events_path)
+                .env("EVENT_OUTBOX_DIR", &outbox_dir)
+                .args([
+                    "replay",
+                    "--jsonl",
+                    "tests/data/portfolios_export.jsonl",
+                    "--to",
+                    "2024-03-01",
+                ])
+                .assert()
+                .success();
+
+            let events = std::fs::read_to_string(&events_path)?;
+            assert_eq!(events.lines().count(), 2);
+            assert!(!outbox_dir.exists());
+
+            Ok(())
+        },
+    )
+}
+
+#[cfg(not(feature = "localstack_aws_deploy"))]
+#[test]
+fn test_replay_without_publisher_needs_dry_run() -> Result<()> {
+    initialize_test_span("test_replay_without_publisher_needs_dry_run").in_scope(|| {
+        Command::cargo_bin("drive-deposits-check-cmd")?
+            .env_remove("EVENT_PUBLISHER")
+            .env_remove("SEND_CAL_EVENTS")
+            .args(["replay", "--jsonl", "tests/data/portfolios_export.jsonl"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("use --dry-run"));
+
+        Ok(())
+    })
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::{Client, Config};
+use aws_smithy_runtime::client::http::test_util::infallible_client_fn;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Map, Value};
+
+use drive_deposits_check_cmd::portfolio::replay::{dynamodb, DateRange};
+use drive_deposits_lambda_db_types::db_item_types::{
+    BankLevelItemsWrapper, CalculatePortfolioRestWrapper, DepositLevelItemsWrapper,
+    DepositSortCriteria, PortfolioLevelItem,
+};
+use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
+
+const TABLE_NAME: &str = "drive-deposits-test";
+
+fn exported_portfolio() -> CalculatePortfolioResponse {
+    let export = std::fs::read_to_string("tests/data/portfolios_export.jsonl").unwrap();
+    serde_json::from_str(export.lines().next().unwrap()).unwrap()
+}
+
+fn portfolio(uuid: &str, tenant_id: &str, created_at: &str) -> CalculatePortfolioResponse {
+    let mut portfolio = exported_portfolio();
+    portfolio.uuid = uuid.to_string();
+    portfolio.tenant_id = Some(tenant_id.to_string());
+    portfolio.created_at = created_at.to_string();
+    portfolio
+}
+
+// every item the writer lambda puts for the portfolio, with both deposit sort criteria
+fn written_items(portfolio: CalculatePortfolioResponse) -> Vec<HashMap<String, AttributeValue>> {
+    let mut items: Vec<HashMap<String, AttributeValue>> =
+        BankLevelItemsWrapper::try_from(&portfolio)
+            .unwrap()
+            .items
+            .into_iter()
+            .map(HashMap::from)
+            .collect();
+    items.push(PortfolioLevelItem::try_from(&portfolio).unwrap().into());
+    let mut rest_wrapper = CalculatePortfolioRestWrapper {
+        calculate_portfolio_response: portfolio,
+        deposit_sort_criteria: DepositSortCriteria::DeltaPeriodGrowth,
+    };
+    for deposit_sort_criteria in [
+        DepositSortCriteria::DeltaPeriodGrowth,
+        DepositSortCriteria::MaturityDate,
+    ] {
+        rest_wrapper.deposit_sort_criteria = deposit_sort_criteria;
+        items.extend(
+            DepositLevelItemsWrapper::try_from(&rest_wrapper)
+                .unwrap()
+                .deposit_level_items
+                .into_iter()
+                .map(HashMap::from),
+        );
+    }
+    items
+}
+
+// the written items are all strings
+fn wire_item(item: &HashMap<String, AttributeValue>) -> Value {
+    let mut wire_item = Map::new();
+    for (name, value) in item {
+        wire_item.insert(name.clone(), json!({"S": value.as_s().unwrap()}));
+    }
+    Value::Object(wire_item)
+}
+
+// answers each Query from the items like DynamoDB would, sorted by SK, one item per page so
+// every query has to follow LastEvaluatedKey
+fn table_with(items: Vec<HashMap<String, AttributeValue>>) -> Client {
+    let mut items = items.iter().map(wire_item).collect::<Vec<Value>>();
+    items.sort_by(|a, b| a["SK"]["S"].as_str().cmp(&b["SK"]["S"].as_str()));
+    let http_client = infallible_client_fn(move |request| {
+        let query: Value = serde_json::from_slice(request.body().bytes().unwrap()).unwrap();
+        let values = &query["ExpressionAttributeValues"];
+        let partition_key = values[":partitionKeyValue"]["S"].as_str().unwrap();
+        let sort_key_prefix = values[":sortKeyValue"]["S"].as_str().unwrap_or_default();
+        let start_after = query["ExclusiveStartKey"]["SK"]["S"].as_str();
+        let mut matching = items.iter().filter(|item| {
+            item["PK"]["S"] == partition_key
+                && item["SK"]["S"]
+                    .as_str()
+                    .is_some_and(|sort_key| sort_key.starts_with(sort_key_prefix))
+                && start_after
+                    .is_none_or(|start_after| item["SK"]["S"].as_str() > Some(start_after))
+        });
+        let mut body = json!({"Items": [], "Count": 0, "ScannedCount": 0});
+        if let Some(item) = matching.next() {
+            body["Items"] = json!([item]);
+            body["Count"] = json!(1);
+            if matching.next().is_some() {
+                body["LastEvaluatedKey"] = json!({"PK": item["PK"], "SK": item["SK"]});
+            }
+        }
+        http_02x::Response::builder()
+            .status(200)
+            .body(body.to_string())
+            .unwrap()
+    });
+    let config = Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+        .http_client(http_client)
+        .build();
+    Client::from_conf(config)
+}
+
+#[tokio::test]
+async fn test_read_portfolios_rebuilds_stored_portfolios() {
+    let stored = portfolio(
+        "11111111-1111-4111-8111-111111111111",
+        "tenant-a",
+        "2024-01-10T15:30:00.000000Z",
+    );
+    let expected = serde_json::to_value(&stored).unwrap();
+    let client = table_with(written_items(stored));
+
+    let portfolios = dynamodb::read_portfolios(
+        &client,
+        TABLE_NAME,
+        &"tenant-a".parse().unwrap(),
+        &DateRange::new(None, None).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(portfolios.len(), 1);
+    assert_eq!(serde_json::to_value(&portfolios[0]).unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_read_portfolios_of_tenant_in_date_range() {
+    let mut items = written_items(portfolio(
+        "11111111-1111-4111-8111-111111111111",
+        "tenant-a",
+        "2024-01-10T15:30:00.000000Z",
+    ));
+    items.extend(written_items(portfolio(
+        "12222222-2222-4222-8222-222222222222",
+        "tenant-a",
+        "2024-06-01T09:00:00.000000Z",
+    )));
+    items.extend(written_items(portfolio(
+        "13333333-3333-4333-8333-333333333333",
+        "tenant-b",
+        "2024-01-11T09:00:00.000000Z",
+    )));
+    let client = table_with(items);
+
+    let portfolios = dynamodb::read_portfolios(
+        &client,
+        TABLE_NAME,
+        &"tenant-a".parse().unwrap(),
+        &DateRange::new(None, Some("2024-03-01")).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let uuids = portfolios
+        .iter()
+        .map(|portfolio| portfolio.uuid.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(uuids, vec!["11111111-1111-4111-8111-111111111111"]);
+    assert_eq!(portfolios[0].tenant_id.as_deref(), Some("tenant-a"));
+    assert_eq!(portfolios[0].banks[0].deposits.len(), 1);
+}
+
+#[tokio::test]
+async fn test_read_portfolios_of_tenant_without_portfolios() {
+    let client = table_with(written_items(portfolio(
+        "11111111-1111-4111-8111-111111111111",
+        "tenant-a",
+        "2024-01-10T15:30:00.000000Z",
+    )));
+
+    let portfolios = dynamodb::read_portfolios(
+        &client,
+        TABLE_NAME,
+        &"tenant-b".parse().unwrap(),
+        &DateRange::new(None, None).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    assert!(portfolios.is_empty());
+}
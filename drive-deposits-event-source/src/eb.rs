@@ -1,102 +1,292 @@
-use std::env::var;
+use std::ops::Range;
+use std::time::Duration;
 
-use aws_config::BehaviorVersion;
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region, SdkConfig};
 use aws_sdk_eventbridge::error::SdkError;
 use aws_sdk_eventbridge::operation::list_rules::ListRulesError;
 use aws_sdk_eventbridge::operation::put_events::{PutEventsError, PutEventsOutput};
 use aws_sdk_eventbridge::types::builders::PutEventsRequestEntryBuilder;
 use aws_sdk_eventbridge::Client;
 use thiserror::Error;
-use tracing::{debug, error, instrument};
+use tokio::time::sleep;
+use tracing::{debug, error, warn};
 
-const LOCALSTACK_ENDPOINT: &str = "http://localhost.localstack.cloud:4566/";
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+use crate::publisher::{EventPublisher, EventPublisherError};
+
+// PutEvents limits per request
+pub const PUT_EVENTS_MAX_ENTRIES: usize = 10;
+pub const PUT_EVENTS_MAX_BYTES: usize = 256 * 1024;
+
+// attempts for an entry failing with a retryable error code, counting the first send
+const PUT_EVENTS_MAX_ATTEMPTS: u32 = 4;
+// error code of the entries of a PutEvents request that failed as a whole, and of the entries of
+// the batches after it, which are not sent
+pub const PUT_EVENTS_REQUEST_FAILED: &str = "PutEventsRequestFailed";
+const MISSING_RESULT_ENTRY: &str = "MissingResultEntry";
+const PUT_EVENTS_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
 
 #[derive(Debug, Error)]
 pub enum DriveDepositsEventBridgeError {
-    #[error("Missing EventBridge")]
-    MissingEventBridge,
     #[error("No rule found {0}")]
     NoRuleErrorForEventBridgeEventBus(String),
+    #[error("Event bus {bus_name} is missing rules {rules}")]
+    MissingRules { bus_name: String, rules: String },
     #[error("Failed to send event to event bridge {0}")]
     PuEventsSdkError(#[from] SdkError<PutEventsError>),
     #[error("Failed to list event rules {0}")]
     ListRulesSdkError(#[from] SdkError<ListRulesError>),
-    #[error("Failed Entry Count Error is {0}")]
-    FailedEntryCountError(String),
+    #[error("Failed Entry Count Error is {summary}")]
+    FailedEntries {
+        summary: String,
+        // of every entry, so the ones already delivered are not sent again
+        outcomes: Vec<EntryOutcome>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryOutcome {
+    Delivered {
+        event_id: Option<String>,
+        attempts: u32,
+    },
+    Failed {
+        error_code: String,
+        error_message: Option<String>,
+        attempts: u32,
+    },
+}
+
+// size of an entry as EventBridge counts it against PUT_EVENTS_MAX_BYTES
+pub fn entry_size(source: &str, detail_type: &str, detail: &str) -> usize {
+    source.len() + detail_type.len() + detail.len()
+}
+
+// region and endpoint from the publisher config, everything else from the AWS defaults
+pub(crate) async fn load_aws_config(config: &EventPublisherConfig) -> SdkConfig {
+    let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(region) = config.region.clone() {
+        config_loader = config_loader.region(Region::new(region));
+    }
+    if let Some(endpoint_url) = config.endpoint_url.as_deref() {
+        debug!("aws endpoint overridden with {}", endpoint_url);
+        config_loader = config_loader.endpoint_url(endpoint_url);
+    }
+    config_loader.load().await
 }
 
 #[derive(Debug, Clone)]
 pub struct DriveDepositsEventBridge {
     pub(crate) eb_client: Client,
-    pub(crate) bus_name: String,
+    pub(crate) config: EventPublisherConfig,
 }
 
 impl DriveDepositsEventBridge {
-    pub async fn new(bus_name: String) -> Self {
-        // let config = aws_config::load_from_env().await;
-        let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
-        if use_localstack() {
-            config_loader = config_loader.endpoint_url(LOCALSTACK_ENDPOINT);
-        };
-        let config = config_loader.load().await;
-
-        let client = aws_sdk_eventbridge::Client::new(&config);
-        Self {
-            eb_client: client,
-            bus_name,
+    pub async fn new(config: EventPublisherConfig) -> Self {
+        let client = aws_sdk_eventbridge::Client::new(&load_aws_config(&config).await);
+        Self::from_client(client, config)
+    }
+
+    pub fn from_client(eb_client: Client, config: EventPublisherConfig) -> Self {
+        Self { eb_client, config }
+    }
+
+    // the same check as at startup, repeated by the grpc health service
+    pub async fn check_rules(&self) -> Result<(), DriveDepositsEventBridgeError> {
+        check_rules_exist_for_bus_name(
+            &self.eb_client,
+            self.config.event_bus.as_str(),
+            &self.config.required_rules,
+        )
+        .await
+    }
+
+    // Sends details in as few PutEvents requests as the limits allow and returns each entry's
+    // outcome, in the same order. Only entries failing with a retryable error code are sent again.
+    // Once a request fails as a whole its entries and those of the later batches fail with
+    // PUT_EVENTS_REQUEST_FAILED, keeping the outcomes of the batches already sent.
+    pub async fn put_events(&self, detail_type: &str, details: &[String]) -> Vec<EntryOutcome> {
+        let entry_sizes: Vec<usize> = details
+            .iter()
+            .map(|detail| {
+                entry_size(
+                    &self.config.source,
+                    &self.config.detail_type(detail_type),
+                    detail,
+                )
+            })
+            .collect();
+        let mut outcomes = Vec::with_capacity(details.len());
+        let mut request_error = None;
+        for batch in put_events_batches(&entry_sizes) {
+            if let Some(error_message) = &request_error {
+                outcomes.extend(batch.map(|_| EntryOutcome::Failed {
+                    error_code: PUT_EVENTS_REQUEST_FAILED.to_string(),
+                    error_message: Some(format!("not sent after {}", error_message)),
+                    attempts: 0,
+                }));
+                continue;
+            }
+            let (batch_outcomes, err) = self
+                .put_events_with_retry(detail_type, &details[batch])
+                .await;
+            outcomes.extend(batch_outcomes);
+            request_error = err.map(|err| err.to_string());
         }
+        outcomes
     }
-}
 
-fn env_var_bool(name: &str) -> bool {
-    let env_val = var(name).unwrap_or_else(|_| "false".to_string());
-    env_val.eq_ignore_ascii_case("true")
+    // the outcome of every entry, with the error of the request that failed as a whole, if any
+    async fn put_events_with_retry(
+        &self,
+        detail_type: &str,
+        details: &[String],
+    ) -> (Vec<EntryOutcome>, Option<DriveDepositsEventBridgeError>) {
+        let mut outcomes: Vec<Option<EntryOutcome>> = vec![None; details.len()];
+        let mut pending: Vec<usize> = (0..details.len()).collect();
+        let mut attempt = 0;
+        while !pending.is_empty() {
+            attempt += 1;
+            let put_events_output = match send_events_to_event_bridge(
+                self,
+                pending.iter().map(|&index| details[index].clone()),
+                detail_type,
+            )
+            .await
+            {
+                Ok(put_events_output) => put_events_output,
+                Err(err) => {
+                    for index in pending {
+                        outcomes[index] = Some(EntryOutcome::Failed {
+                            error_code: PUT_EVENTS_REQUEST_FAILED.to_string(),
+                            error_message: Some(err.to_string()),
+                            attempts: attempt,
+                        });
+                    }
+                    return (outcomes.into_iter().flatten().collect(), Some(err));
+                }
+            };
+            // result entries are in the same order as the request entries
+            let results = put_events_output.entries.unwrap_or_default();
+            let mut retry = vec![];
+            for (position, index) in pending.into_iter().enumerate() {
+                let result = results.get(position);
+                let error_code = match result {
+                    Some(result) => result.error_code.clone(),
+                    None => Some(MISSING_RESULT_ENTRY.to_string()),
+                };
+                outcomes[index] = match error_code {
+                    None => Some(EntryOutcome::Delivered {
+                        event_id: result.and_then(|result| result.event_id.clone()),
+                        attempts: attempt,
+                    }),
+                    Some(error_code)
+                        if is_retryable_entry_error(&error_code)
+                            && attempt < PUT_EVENTS_MAX_ATTEMPTS =>
+                    {
+                        retry.push(index);
+                        None
+                    }
+                    Some(error_code) => Some(EntryOutcome::Failed {
+                        error_code,
+                        error_message: result.and_then(|result| result.error_message.clone()),
+                        attempts: attempt,
+                    }),
+                };
+            }
+            if !retry.is_empty() {
+                let backoff = entry_retry_backoff(attempt);
+                warn!(
+                    "{} of {} {} entries failed attempt {} with retryable errors, retrying after {:?}",
+                    retry.len(),
+                    details.len(),
+                    detail_type,
+                    attempt,
+                    backoff
+                );
+                sleep(backoff).await;
+            }
+            pending = retry;
+        }
+        // every entry is either delivered or failed once nothing is pending
+        (outcomes.into_iter().flatten().collect(), None)
+    }
 }
 
-#[instrument]
-fn use_localstack() -> bool {
-    let use_local = env_var_bool("USE_LOCALSTACK");
-    debug!("use_localstack based on USE_LOCALSTACK: {}", use_local);
-    use_local
-}
+#[async_trait]
+impl EventPublisher for DriveDepositsEventBridge {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.publish_batch(detail_type, vec![detail]).await
+    }
 
-#[instrument]
-fn should_send_events() -> bool {
-    env_var_bool("SEND_CAL_EVENTS")
-}
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        let outcomes = self.put_events(detail_type, &details).await;
+        for (index, outcome) in outcomes.iter().enumerate() {
+            match outcome {
+                EntryOutcome::Delivered { event_id, attempts } => debug!(
+                    "{} entry {} delivered as event {:?} after {} attempts",
+                    detail_type, index, event_id, attempts
+                ),
+                EntryOutcome::Failed {
+                    error_code,
+                    error_message,
+                    attempts,
+                } => error!(
+                    "{} entry {} failed with {} after {} attempts: {:?}",
+                    detail_type, index, error_code, attempts, error_message
+                ),
+            }
+        }
+        match failed_entries_summary(detail_type, &outcomes) {
+            Some(summary) => {
+                Err(DriveDepositsEventBridgeError::FailedEntries { summary, outcomes }.into())
+            }
+            None => Ok(()),
+        }
+    }
 
-#[instrument]
-pub async fn create_eb() -> Result<Option<DriveDepositsEventBridge>, DriveDepositsEventBridgeError>
-{
-    let send_events = should_send_events();
-    debug!("send_events based on SEND_CAL_EVENTS: {}", send_events);
-    if !send_events {
-        return Ok(None);
+    async fn check(&self) -> Result<(), EventPublisherError> {
+        Ok(self.check_rules().await?)
     }
-    let eb = DriveDepositsEventBridge::new("DriveDepositsEventBus".to_string()).await;
-    check_rules_exist_for_bus_name(&eb.eb_client, eb.bus_name.as_str())
-        .await
-        .inspect_err(|err| {
-            error!("check rules exist for bus name err is {}", err);
-        })?;
-    Ok(Some(eb))
 }
 
 pub async fn check_rules_exist_for_bus_name(
     client: &Client,
     bus_name: &str,
+    required_rules: &[String],
 ) -> Result<(), DriveDepositsEventBridgeError> {
-    let list_rules = client
-        .list_rules()
-        .event_bus_name(bus_name)
-        .send()
-        .await
-        .inspect_err(|err| {
-            error!("list_rules send err is {:?}", err);
-        })?;
-    // more checks to be sure
-    let rules = list_rules.rules.unwrap_or_default();
-    if rules.is_empty() {
+    let mut rule_names = vec![];
+    let mut next_token = None;
+    loop {
+        let list_rules = client
+            .list_rules()
+            .event_bus_name(bus_name)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .inspect_err(|err| {
+                error!("list_rules send err is {:?}", err);
+            })?;
+        rule_names.extend(
+            list_rules
+                .rules
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|rule| rule.name),
+        );
+        next_token = list_rules.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    debug!("event bus {} has rules {:?}", bus_name, rule_names);
+    if rule_names.is_empty() {
         error!(
             "No rules found error in EventBridge for event bus {}",
             bus_name
@@ -105,65 +295,122 @@ pub async fn check_rules_exist_for_bus_name(
             DriveDepositsEventBridgeError::NoRuleErrorForEventBridgeEventBus(bus_name.to_string()),
         );
     }
+    let missing = missing_rules(required_rules, &rule_names);
+    if !missing.is_empty() {
+        error!("event bus {} is missing rules {:?}", bus_name, missing);
+        return Err(DriveDepositsEventBridgeError::MissingRules {
+            bus_name: bus_name.to_string(),
+            rules: missing.join(", "),
+        });
+    }
     Ok(())
 }
 
-pub async fn send_bank_level_event_to_event_bridge(
-    eb: Option<&DriveDepositsEventBridge>,
-    json_payload: String,
-) -> Result<(), DriveDepositsEventBridgeError> {
-    let level = "bank-level";
-    let put_events_output = send_event_to_event_bridge(eb, json_payload, level).await?;
-    puts_event_failed_entry_count(put_events_output, level)?;
+// required rule names not among the rules of the bus, in the order they are required
+pub fn missing_rules(required_rules: &[String], rule_names: &[String]) -> Vec<String> {
+    required_rules
+        .iter()
+        .filter(|required| !rule_names.contains(required))
+        .cloned()
+        .collect()
+}
 
-    Ok(())
+// consecutive ranges of entries, by their sizes, that each fit in a single PutEvents request
+pub fn put_events_batches(entry_sizes: &[usize]) -> Vec<Range<usize>> {
+    let mut batches = vec![];
+    let mut start = 0;
+    let mut batch_bytes = 0;
+    for (index, size) in entry_sizes.iter().enumerate() {
+        let full =
+            index - start == PUT_EVENTS_MAX_ENTRIES || batch_bytes + size > PUT_EVENTS_MAX_BYTES;
+        // an oversized entry still goes on its own, so EventBridge reports it as that entry's failure
+        if index > start && full {
+            batches.push(start..index);
+            start = index;
+            batch_bytes = 0;
+        }
+        batch_bytes += size;
+    }
+    if start < entry_sizes.len() {
+        batches.push(start..entry_sizes.len());
+    }
+    batches
 }
 
-pub async fn send_portfolio_level_event_to_event_bridge(
-    eb: Option<&DriveDepositsEventBridge>,
-    json_payload: String,
-) -> Result<(), DriveDepositsEventBridgeError> {
-    let level = "portfolio-level";
-    let put_events_output = send_event_to_event_bridge(eb, json_payload, level).await?;
-    puts_event_failed_entry_count(put_events_output, level)?;
-    Ok(())
+// per-entry error codes where sending the same entry again can succeed
+pub fn is_retryable_entry_error(error_code: &str) -> bool {
+    matches!(
+        error_code,
+        "ThrottlingException" | "InternalFailure" | "InternalException" | "ServiceUnavailable"
+    )
 }
 
-pub fn puts_event_failed_entry_count(
-    put_events_output: PutEventsOutput,
-    level: &str,
-) -> Result<(), DriveDepositsEventBridgeError> {
-    debug!(
-        "At {} detail-type, PutEventsOutput is {:?}",
-        level, put_events_output
-    );
-    if put_events_output.failed_entry_count > 0 {
-        return Err(DriveDepositsEventBridgeError::FailedEntryCountError(
-            format!(
-                "{} for sending events to event bridge detail type {}",
-                put_events_output.failed_entry_count, level
-            ),
-        ));
+// per-entry error codes where sending the same entry again, even later, cannot succeed
+pub fn is_permanent_entry_error(error_code: &str) -> bool {
+    !is_retryable_entry_error(error_code)
+        && error_code != PUT_EVENTS_REQUEST_FAILED
+        && error_code != MISSING_RESULT_ENTRY
+}
+
+fn entry_retry_backoff(attempt: u32) -> Duration {
+    PUT_EVENTS_INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+}
+
+fn failed_entries_summary(detail_type: &str, outcomes: &[EntryOutcome]) -> Option<String> {
+    let failed: Vec<String> = outcomes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, outcome)| match outcome {
+            EntryOutcome::Failed {
+                error_code,
+                error_message,
+                ..
+            } => Some(format!(
+                "entry {} {} ({})",
+                index,
+                error_code,
+                error_message.as_deref().unwrap_or_default()
+            )),
+            EntryOutcome::Delivered { .. } => None,
+        })
+        .collect();
+    if failed.is_empty() {
+        return None;
     }
-    Ok(())
+    Some(format!(
+        "{} of {} {} entries failed: {}",
+        failed.len(),
+        outcomes.len(),
+        detail_type,
+        failed.join(", ")
+    ))
 }
 
-async fn send_event_to_event_bridge(
-    eb: Option<&DriveDepositsEventBridge>,
-    json_payload: String,
+// one PutEvents request for a batch; retries within the batch are left to put_events_with_retry
+async fn send_events_to_event_bridge(
+    bridge: &DriveDepositsEventBridge,
+    json_payloads: impl Iterator<Item = String>,
     detail_type: &str,
 ) -> Result<PutEventsOutput, DriveDepositsEventBridgeError> {
-    let bridge = eb.ok_or_else(|| DriveDepositsEventBridgeError::MissingEventBridge)?;
-    let bus_name = &bridge.bus_name;
-    let request = PutEventsRequestEntryBuilder::default()
-        .set_source(Some(String::from("drive-deposits")))
-        .set_detail_type(Some(detail_type.into()))
-        .set_detail(Some(json_payload))
-        .set_event_bus_name(Some(bus_name.into()))
-        .build();
-    debug!("request being sent to event bridge: {:?}", request);
+    let config = &bridge.config;
+    let entries = json_payloads
+        .map(|json_payload| {
+            PutEventsRequestEntryBuilder::default()
+                .set_source(Some(config.source.clone()))
+                .set_detail_type(Some(config.detail_type(detail_type)))
+                .set_detail(Some(json_payload))
+                .set_event_bus_name(Some(config.event_bus.clone()))
+                .build()
+        })
+        .collect::<Vec<_>>();
+    debug!(
+        "{} {} entries being sent to event bus {}",
+        entries.len(),
+        detail_type,
+        config.event_bus
+    );
     let aws_eb_client = &bridge.eb_client;
-    let request = aws_eb_client.put_events().entries(request);
+    let request = aws_eb_client.put_events().set_entries(Some(entries));
 
     let put_events_output = request.send().await.inspect_err(|err| {
         error!("put_events_output send err is {}", err);
@@ -1,2 +1,5 @@
 pub mod eb;
+pub mod object_store;
 pub mod payload_types;
+pub mod publisher;
+pub mod schema;
@@ -0,0 +1,105 @@
+use std::env::var;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::primitives::ByteStreamError;
+use thiserror::Error;
+
+use drive_deposits_rest_types::server_config::{Error as ServerConfigError, EventPublisherConfig};
+use tracing::{debug, instrument};
+
+pub mod file;
+pub mod s3;
+
+pub use file::FileObjectStore;
+pub use s3::S3ObjectStore;
+
+#[derive(Debug, Error)]
+pub enum ObjectStoreError {
+    #[error("Invalid EVENT_CLAIM_CHECK_STORE {0:?}; must be none, s3 or file")]
+    InvalidStore(String),
+
+    #[error("EVENT_CLAIM_CHECK_STORE {store} needs {setting} to be set")]
+    MissingSetting {
+        store: &'static str,
+        setting: &'static str,
+    },
+
+    #[error("Object store config error: {0}")]
+    Config(#[from] ServerConfigError),
+
+    #[error("Object reference {0:?} does not belong to this object store")]
+    ForeignReference(String),
+
+    #[error("File object store error: {0}")]
+    File(#[from] std::io::Error),
+
+    // boxed, the s3 sdk errors would make every Result carrying this error very large
+    #[error("S3 put object error: {0}")]
+    S3Put(Box<SdkError<PutObjectError>>),
+
+    #[error("S3 get object error: {0}")]
+    S3Get(Box<SdkError<GetObjectError>>),
+
+    #[error("S3 object body error: {0}")]
+    S3Body(#[from] ByteStreamError),
+}
+
+impl From<SdkError<PutObjectError>> for ObjectStoreError {
+    fn from(err: SdkError<PutObjectError>) -> Self {
+        Self::S3Put(Box::new(err))
+    }
+}
+
+impl From<SdkError<GetObjectError>> for ObjectStoreError {
+    fn from(err: SdkError<GetObjectError>) -> Self {
+        Self::S3Get(Box::new(err))
+    }
+}
+
+// Where event payloads too large for an event are kept; the event carries the reference put
+// returns, and the consumer gets the payload back with it
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<String, ObjectStoreError>;
+
+    async fn get(&self, reference: &str) -> Result<Vec<u8>, ObjectStoreError>;
+}
+
+pub type SharedObjectStore = Arc<dyn ObjectStore>;
+
+// EVENT_CLAIM_CHECK_STORE picks the store: none, s3 (EVENT_CLAIM_CHECK_S3_BUCKET) or
+// file (EVENT_CLAIM_CHECK_DIR). Publishers and the DynamoDB writer need the same settings.
+#[instrument]
+pub async fn create_object_store() -> Result<Option<SharedObjectStore>, ObjectStoreError> {
+    let store = var("EVENT_CLAIM_CHECK_STORE").unwrap_or_else(|_| "none".to_string());
+    debug!("object store based on EVENT_CLAIM_CHECK_STORE: {}", store);
+    match store.trim().to_lowercase().as_str() {
+        "none" | "" => Ok(None),
+        "s3" => {
+            let bucket = required_setting("s3", "EVENT_CLAIM_CHECK_S3_BUCKET")?;
+            // same region and endpoint as the event publisher
+            let config = EventPublisherConfig::load()?;
+            Ok(Some(Arc::new(S3ObjectStore::new(bucket, &config).await)))
+        }
+        "file" => {
+            let dir = required_setting("file", "EVENT_CLAIM_CHECK_DIR")?;
+            Ok(Some(Arc::new(FileObjectStore::new(PathBuf::from(dir)))))
+        }
+        _ => Err(ObjectStoreError::InvalidStore(store)),
+    }
+}
+
+fn required_setting(
+    store: &'static str,
+    setting: &'static str,
+) -> Result<String, ObjectStoreError> {
+    var(setting)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or(ObjectStoreError::MissingSetting { store, setting })
+}
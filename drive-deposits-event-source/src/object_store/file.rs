@@ -0,0 +1,57 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use tokio::fs;
+use tracing::debug;
+
+use super::{ObjectStore, ObjectStoreError};
+
+const FILE_SCHEME: &str = "file://";
+
+// Objects as files under dir, referenced as file://<dir>/<key>; for local development, where
+// the publisher and the writer share a filesystem
+#[derive(Debug, Clone)]
+pub struct FileObjectStore {
+    dir: PathBuf,
+}
+
+impl FileObjectStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // only paths under dir, so a crafted reference cannot read any other file
+    fn path_of(&self, reference: &str) -> Result<PathBuf, ObjectStoreError> {
+        let path = reference
+            .strip_prefix(FILE_SCHEME)
+            .map(Path::new)
+            .filter(|path| path.starts_with(&self.dir))
+            .filter(|path| {
+                path.components()
+                    .all(|component| component != Component::ParentDir)
+            })
+            .ok_or_else(|| ObjectStoreError::ForeignReference(reference.to_string()))?;
+        Ok(path.to_path_buf())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FileObjectStore {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<String, ObjectStoreError> {
+        let reference = format!("{}{}", FILE_SCHEME, self.dir.join(key).display());
+        let path = self.path_of(&reference)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // written under another name and renamed, so a reader never sees a partial object
+        let partial_path = path.with_extension("partial");
+        fs::write(&partial_path, body).await?;
+        fs::rename(&partial_path, &path).await?;
+        debug!("object stored at {}", reference);
+        Ok(reference)
+    }
+
+    async fn get(&self, reference: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        Ok(fs::read(self.path_of(reference)?).await?)
+    }
+}
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use tracing::{debug, error};
+
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+use super::{ObjectStore, ObjectStoreError};
+use crate::eb::load_aws_config;
+
+// Objects in a single bucket, referenced as s3://<bucket>/<key>
+#[derive(Debug, Clone)]
+pub struct S3ObjectStore {
+    s3_client: Client,
+    bucket: String,
+}
+
+impl S3ObjectStore {
+    pub async fn new(bucket: String, config: &EventPublisherConfig) -> Self {
+        let aws_config = load_aws_config(config).await;
+        // localstack and other custom endpoints serve buckets by path rather than by subdomain
+        let s3_config = aws_sdk_s3::config::Builder::from(&aws_config)
+            .force_path_style(config.endpoint_url.is_some())
+            .build();
+        Self {
+            s3_client: Client::from_conf(s3_config),
+            bucket,
+        }
+    }
+
+    fn key_of<'a>(&self, reference: &'a str) -> Result<&'a str, ObjectStoreError> {
+        reference
+            .strip_prefix("s3://")
+            .and_then(|bucket_key| bucket_key.strip_prefix(self.bucket.as_str()))
+            .and_then(|key| key.strip_prefix('/'))
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| ObjectStoreError::ForeignReference(reference.to_string()))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<String, ObjectStoreError> {
+        self.s3_client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type("application/json")
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .inspect_err(|err| error!("s3 put_object {} err is {}", key, err))?;
+        let reference = format!("s3://{}/{}", self.bucket, key);
+        debug!("object stored at {}", reference);
+        Ok(reference)
+    }
+
+    async fn get(&self, reference: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        let object = self
+            .s3_client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key_of(reference)?)
+            .send()
+            .await
+            .inspect_err(|err| error!("s3 get_object {} err is {}", reference, err))?;
+        Ok(object.body.collect().await?.into_bytes().to_vec())
+    }
+}
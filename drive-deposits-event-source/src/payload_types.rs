@@ -7,3 +7,17 @@ pub use drive_deposits_rest_types::rest_types::Maturity;
 pub use drive_deposits_rest_types::rest_types::Outcome;
 pub use drive_deposits_rest_types::rest_types::OutcomeWithDates;
 pub use drive_deposits_rest_types::rest_types::ProcessingError;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// deposit-level event payload: one deposit, with the portfolio and bank it was calculated in
+#[derive(Default, Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct DepositLevel {
+    pub portfolio_uuid: String,
+    pub bank_uuid: String,
+    pub bank_name: String,
+    pub bank_tz: String,
+    pub tenant_id: Option<String>,
+    pub deposit: Deposit,
+}
@@ -0,0 +1,308 @@
+use std::env::var;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tracing::{debug, error, instrument, warn};
+
+use drive_deposits_rest_types::server_config::{Error as ServerConfigError, EventPublisherConfig};
+
+use crate::eb::{
+    is_permanent_entry_error, DriveDepositsEventBridge, DriveDepositsEventBridgeError, EntryOutcome,
+};
+use crate::object_store::{create_object_store, ObjectStoreError};
+use crate::schema::SchemaValidationError;
+
+pub mod channel;
+pub mod claim_check;
+pub mod file;
+pub mod outbox;
+pub mod validating;
+pub mod webhook;
+
+pub use channel::{ChannelPublisher, PublishedEvent};
+pub use claim_check::ClaimCheckPublisher;
+pub use file::FilePublisher;
+pub use outbox::{OutboxConfig, OutboxMetricsSnapshot, OutboxPublisher};
+pub use validating::ValidatingPublisher;
+pub use webhook::WebhookPublisher;
+
+pub use drive_deposits_rest_types::cloud_event::EVENT_SOURCE;
+pub const BANK_LEVEL: &str = "bank-level";
+pub const PORTFOLIO_LEVEL: &str = "portfolio-level";
+// opt in with EVENT_PUBLISHER_DEPOSIT_LEVEL_EVENTS, since a portfolio has many deposits
+pub const DEPOSIT_LEVEL: &str = "deposit-level";
+
+#[derive(Debug, Error)]
+pub enum EventPublisherError {
+    #[error("Invalid EVENT_PUBLISHER {0:?}; must be none, eventbridge, file or webhook")]
+    InvalidPublisher(String),
+
+    #[error("Invalid value {value:?} for env variable {setting}")]
+    InvalidSetting {
+        setting: &'static str,
+        value: String,
+    },
+
+    #[error("EVENT_PUBLISHER {publisher} needs {setting} to be set")]
+    MissingSetting {
+        publisher: &'static str,
+        setting: &'static str,
+    },
+
+    #[error("Event publisher config error: {0}")]
+    Config(#[from] ServerConfigError),
+
+    // boxed, the sdk errors inside would make every Result carrying this error very large
+    #[error("EventBridge publisher error: {0}")]
+    EventBridge(Box<DriveDepositsEventBridgeError>),
+
+    #[error("Event payload is not JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("File publisher error: {0}")]
+    File(#[from] std::io::Error),
+
+    #[error("Webhook publisher error: {0}")]
+    Webhook(#[from] reqwest::Error),
+
+    #[error("Webhook {url} responded with status {status}")]
+    WebhookStatus { url: String, status: u16 },
+
+    #[error("Channel publisher receiver was dropped")]
+    ChannelClosed,
+
+    #[error("Claim check object store error: {0}")]
+    ObjectStore(#[from] ObjectStoreError),
+
+    #[error("Event payload schema error: {0}")]
+    SchemaValidation(#[from] SchemaValidationError),
+}
+
+impl EventPublisherError {
+    // sending the same events again cannot succeed, so the outbox dead letters them right away
+    // instead of holding up the events after them
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::EventBridge(err) => match err.as_ref() {
+                DriveDepositsEventBridgeError::FailedEntries { outcomes, .. } => {
+                    outcomes.iter().all(|outcome| match outcome {
+                        EntryOutcome::Failed { error_code, .. } => {
+                            is_permanent_entry_error(error_code)
+                        }
+                        EntryOutcome::Delivered { .. } => true,
+                    })
+                }
+                _ => false,
+            },
+            // a client error, other than timing out or being told to slow down
+            Self::WebhookStatus { status, .. } => {
+                (400..500).contains(status) && !matches!(status, 408 | 425 | 429)
+            }
+            Self::Json(_) | Self::SchemaValidation(_) => true,
+            _ => false,
+        }
+    }
+
+    // positions of the batch entries that were not delivered, when the sink reports each entry
+    pub fn undelivered_entries(&self) -> Option<Vec<usize>> {
+        let Self::EventBridge(err) = self else {
+            return None;
+        };
+        let DriveDepositsEventBridgeError::FailedEntries { outcomes, .. } = err.as_ref() else {
+            return None;
+        };
+        Some(
+            outcomes
+                .iter()
+                .enumerate()
+                .filter(|(_, outcome)| matches!(outcome, EntryOutcome::Failed { .. }))
+                .map(|(index, _)| index)
+                .collect(),
+        )
+    }
+}
+
+impl From<DriveDepositsEventBridgeError> for EventPublisherError {
+    fn from(err: DriveDepositsEventBridgeError) -> Self {
+        Self::EventBridge(Box::new(err))
+    }
+}
+
+// Where calculation events go; the engine only knows about this trait
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError>;
+
+    // events of one detail type that can go out together; sinks without a batch API send them in order
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        for detail in details {
+            self.publish(detail_type, detail).await?;
+        }
+        Ok(())
+    }
+
+    // for health checks; a sink without anything to check is always healthy
+    async fn check(&self) -> Result<(), EventPublisherError> {
+        Ok(())
+    }
+
+    // backlog and delivery counts when events go through the durable outbox
+    fn outbox_metrics(&self) -> Option<OutboxMetricsSnapshot> {
+        None
+    }
+}
+
+pub type SharedEventPublisher = Arc<dyn EventPublisher>;
+
+// same source, detail-type and detail fields as an EventBridge event, for the file and webhook sinks
+pub(crate) fn event_envelope(
+    config: &EventPublisherConfig,
+    detail_type: &str,
+    detail: &str,
+) -> Result<Value, serde_json::Error> {
+    Ok(json!({
+        "source": config.source,
+        "detail-type": config.detail_type(detail_type),
+        "detail": serde_json::from_str::<Value>(detail)?,
+    }))
+}
+
+// EVENT_PUBLISHER picks the sink: none, eventbridge, file (EVENT_PUBLISHER_FILE_PATH) or
+// webhook (EVENT_PUBLISHER_WEBHOOK_URL). Without it, SEND_CAL_EVENTS=true still means eventbridge.
+// EVENT_CLAIM_CHECK_STORE moves the data of events too large for EventBridge into an object store,
+// and EVENT_OUTBOX_DIR puts a durable outbox in front of the sink. Payloads are checked against their
+// JSON Schemas first in debug builds, or with EVENT_PUBLISHER_VALIDATE_PAYLOADS.
+#[instrument]
+pub async fn create_publisher() -> Result<Option<SharedEventPublisher>, EventPublisherError> {
+    publisher_with_outbox(outbox_config()?).await
+}
+
+// Same as create_publisher but ignoring EVENT_OUTBOX_DIR, for one-off runs such as replay that exit
+// once they are done: an outbox would only queue their events, and each publish has to reach the
+// sink or fail the run
+#[instrument]
+pub async fn create_publisher_without_outbox(
+) -> Result<Option<SharedEventPublisher>, EventPublisherError> {
+    publisher_with_outbox(None).await
+}
+
+async fn publisher_with_outbox(
+    outbox_config: Option<OutboxConfig>,
+) -> Result<Option<SharedEventPublisher>, EventPublisherError> {
+    let publisher_config = EventPublisherConfig::load()?;
+    let publisher = match var("EVENT_PUBLISHER") {
+        Ok(publisher) => publisher,
+        Err(_) if legacy_send_cal_events() => {
+            warn!("SEND_CAL_EVENTS is deprecated, set EVENT_PUBLISHER=eventbridge instead");
+            "eventbridge".to_string()
+        }
+        Err(_) => "none".to_string(),
+    };
+    debug!("event publisher based on EVENT_PUBLISHER: {}", publisher);
+    let publisher: SharedEventPublisher = match publisher.trim().to_lowercase().as_str() {
+        "none" | "" => return Ok(None),
+        "eventbridge" => {
+            let eb = DriveDepositsEventBridge::new(publisher_config.clone()).await;
+            match eb.check_rules().await {
+                Ok(()) => {}
+                // the outbox holds events until EventBridge is reachable again
+                Err(err) if outbox_config.is_some() => {
+                    warn!("check rules exist for bus name err is {}", err);
+                }
+                Err(err) => {
+                    error!("check rules exist for bus name err is {}", err);
+                    return Err(err.into());
+                }
+            }
+            Arc::new(eb)
+        }
+        "file" => {
+            let path = required_setting("file", "EVENT_PUBLISHER_FILE_PATH")?;
+            Arc::new(FilePublisher::open(PathBuf::from(path), publisher_config.clone()).await?)
+        }
+        "webhook" => {
+            let url = required_setting("webhook", "EVENT_PUBLISHER_WEBHOOK_URL")?;
+            Arc::new(WebhookPublisher::new(url, publisher_config.clone())?)
+        }
+        _ => return Err(EventPublisherError::InvalidPublisher(publisher)),
+    };
+    let publisher: SharedEventPublisher = match create_object_store().await? {
+        Some(store) => {
+            let mut claim_check = ClaimCheckPublisher::new(store, publisher, &publisher_config);
+            if let Ok(value) = var("EVENT_CLAIM_CHECK_MAX_EVENT_BYTES") {
+                claim_check = claim_check.with_max_event_bytes(parsed_setting(
+                    "EVENT_CLAIM_CHECK_MAX_EVENT_BYTES",
+                    value,
+                )?);
+            }
+            Arc::new(claim_check)
+        }
+        None => publisher,
+    };
+    let publisher: SharedEventPublisher = match outbox_config {
+        Some(outbox_config) => Arc::new(OutboxPublisher::start(outbox_config, publisher).await?),
+        None => publisher,
+    };
+    // before the claim check and the outbox, so a bad payload fails the publish that made it
+    let publisher: SharedEventPublisher = if publisher_config.validate_payloads {
+        debug!("event payloads are validated against their schemas");
+        Arc::new(ValidatingPublisher::new(publisher))
+    } else {
+        publisher
+    };
+    Ok(Some(publisher))
+}
+
+fn outbox_config() -> Result<Option<OutboxConfig>, EventPublisherError> {
+    let Some(dir) = var("EVENT_OUTBOX_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    let mut config = OutboxConfig::new(PathBuf::from(dir));
+    if let Ok(value) = var("EVENT_OUTBOX_MAX_ATTEMPTS") {
+        config.max_attempts = parsed_setting::<u32>("EVENT_OUTBOX_MAX_ATTEMPTS", value)?.max(1);
+    }
+    if let Ok(value) = var("EVENT_OUTBOX_BACKOFF_MS") {
+        config.initial_backoff =
+            Duration::from_millis(parsed_setting("EVENT_OUTBOX_BACKOFF_MS", value)?);
+    }
+    if let Ok(value) = var("EVENT_OUTBOX_REPLAY_DEAD_LETTERS") {
+        config.replay_dead_letters = parsed_setting("EVENT_OUTBOX_REPLAY_DEAD_LETTERS", value)?;
+    }
+    Ok(Some(config))
+}
+
+fn parsed_setting<T: FromStr>(
+    setting: &'static str,
+    value: String,
+) -> Result<T, EventPublisherError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| EventPublisherError::InvalidSetting { setting, value })
+}
+
+fn legacy_send_cal_events() -> bool {
+    var("SEND_CAL_EVENTS").is_ok_and(|send| send.eq_ignore_ascii_case("true"))
+}
+
+fn required_setting(
+    publisher: &'static str,
+    setting: &'static str,
+) -> Result<String, EventPublisherError> {
+    var(setting)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or(EventPublisherError::MissingSetting { publisher, setting })
+}
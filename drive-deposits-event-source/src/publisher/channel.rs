@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use super::{EventPublisher, EventPublisherError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedEvent {
+    pub detail_type: String,
+    pub detail: String,
+}
+
+// In-memory sink, so tests can assert on exactly what was published
+#[derive(Debug, Clone)]
+pub struct ChannelPublisher {
+    sender: UnboundedSender<PublishedEvent>,
+}
+
+impl ChannelPublisher {
+    pub fn new() -> (Self, UnboundedReceiver<PublishedEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self { sender }, receiver)
+    }
+}
+
+#[async_trait]
+impl EventPublisher for ChannelPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.sender
+            .send(PublishedEvent {
+                detail_type: detail_type.to_string(),
+                detail,
+            })
+            .map_err(|_| EventPublisherError::ChannelClosed)
+    }
+}
@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use serde_json::{from_str, to_string, to_vec, Value};
+use tracing::{info, warn};
+
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+use super::{EventPublisher, EventPublisherError, OutboxMetricsSnapshot, SharedEventPublisher};
+use crate::eb::{entry_size, PUT_EVENTS_MAX_BYTES};
+use crate::object_store::SharedObjectStore;
+
+// Moves the data of a CloudEvent too large for the sink into the object store, leaving a
+// dataref pointing at it; smaller events pass through untouched
+pub struct ClaimCheckPublisher {
+    store: SharedObjectStore,
+    inner: SharedEventPublisher,
+    // largest event, counted the way EventBridge counts it, sent with its data
+    max_event_bytes: usize,
+    // source and detail-type prefix the sink sends each event with, counted in its size
+    config: EventPublisherConfig,
+}
+
+impl ClaimCheckPublisher {
+    pub fn new(
+        store: SharedObjectStore,
+        inner: SharedEventPublisher,
+        config: &EventPublisherConfig,
+    ) -> Self {
+        Self {
+            store,
+            inner,
+            max_event_bytes: PUT_EVENTS_MAX_BYTES,
+            config: config.clone(),
+        }
+    }
+
+    pub fn with_max_event_bytes(mut self, max_event_bytes: usize) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    async fn claim_check(
+        &self,
+        detail_type: &str,
+        detail: String,
+    ) -> Result<String, EventPublisherError> {
+        let event_bytes = entry_size(
+            &self.config.source,
+            &self.config.detail_type(detail_type),
+            &detail,
+        );
+        if event_bytes <= self.max_event_bytes {
+            return Ok(detail);
+        }
+        let mut event: Value = from_str(&detail)?;
+        let (Some(id), Some(data)) = (
+            event["id"].as_str().map(str::to_string),
+            event.as_object_mut().and_then(|event| event.remove("data")),
+        ) else {
+            warn!(
+                "{} event of {} bytes has no CloudEvents id and data to claim check",
+                detail_type, event_bytes
+            );
+            return Ok(detail);
+        };
+        // keyed by event id, so a retried publish overwrites the same object
+        let key = format!("{}/{}.json", detail_type, id);
+        let dataref = self.store.put(&key, to_vec(&data)?).await?;
+        info!(
+            "{} event {} of {} bytes claim checked at {}",
+            detail_type, id, event_bytes, dataref
+        );
+        event["dataref"] = Value::String(dataref);
+        Ok(to_string(&event)?)
+    }
+}
+
+#[async_trait]
+impl EventPublisher for ClaimCheckPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        let detail = self.claim_check(detail_type, detail).await?;
+        self.inner.publish(detail_type, detail).await
+    }
+
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        let mut checked_details = Vec::with_capacity(details.len());
+        for detail in details {
+            checked_details.push(self.claim_check(detail_type, detail).await?);
+        }
+        self.inner.publish_batch(detail_type, checked_details).await
+    }
+
+    async fn check(&self) -> Result<(), EventPublisherError> {
+        self.inner.check().await
+    }
+
+    fn outbox_metrics(&self) -> Option<OutboxMetricsSnapshot> {
+        self.inner.outbox_metrics()
+    }
+}
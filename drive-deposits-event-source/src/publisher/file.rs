@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+use super::{event_envelope, EventPublisher, EventPublisherError};
+
+// Appends one JSON event per line (newline-delimited JSON)
+#[derive(Debug)]
+pub struct FilePublisher {
+    path: PathBuf,
+    // one writer at a time so concurrent bank events never interleave within a line
+    file: Mutex<File>,
+    // source and detail-type prefix of each line
+    config: EventPublisherConfig,
+}
+
+impl FilePublisher {
+    pub async fn open(
+        path: PathBuf,
+        config: EventPublisherConfig,
+    ) -> Result<Self, EventPublisherError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|err| std::io::Error::new(err.kind(), format!("{:?}: {}", path, err)))?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for FilePublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        let mut line = serde_json::to_string(&event_envelope(&self.config, detail_type, &detail)?)?;
+        line.push('\n');
+        let mut file = self.file.lock().await;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        debug!("{} event appended to {:?}", detail_type, self.path);
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+use super::{EventPublisher, EventPublisherError, SharedEventPublisher};
+
+const EVENT_EXTENSION: &str = "json";
+const PARTIAL_EXTENSION: &str = "partial";
+const DEAD_LETTER_DIR: &str = "dead";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxConfig {
+    pub dir: PathBuf,
+    // delivery attempts before an event failing with a retryable error is moved to the dead
+    // letter directory; permanent errors are dead lettered on the first attempt
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // moves the dead lettered events back in to be delivered again, after the pending ones
+    pub replay_dead_letters: bool,
+}
+
+impl OutboxConfig {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            // about 12 minutes with the default backoff
+            max_attempts: 20,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(60),
+            replay_dead_letters: false,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Default)]
+struct OutboxMetrics {
+    backlog: AtomicUsize,
+    delivered: AtomicU64,
+    retries: AtomicU64,
+    dead_lettered: AtomicU64,
+}
+
+// counts of records, each holding the events of one publish or publish_batch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutboxMetricsSnapshot {
+    // stored and not yet delivered or dead lettered
+    pub backlog: usize,
+    pub delivered: u64,
+    pub retries: u64,
+    pub dead_lettered: u64,
+}
+
+// Stores each event, or each batch of events, as a file in dir and returns right away; a background
+// dispatcher delivers them in order through the inner publisher, batches with a single
+// publish_batch. Events left over from a previous run are delivered first, so a dir must belong
+// to a single process.
+pub struct OutboxPublisher {
+    dir: PathBuf,
+    // held from numbering a record until it is renamed into place, so the dispatcher never sees
+    // a record before the ones numbered ahead of it
+    next_sequence: Mutex<u64>,
+    metrics: Arc<OutboxMetrics>,
+    stored: Arc<Notify>,
+    dispatcher: JoinHandle<()>,
+}
+
+impl OutboxPublisher {
+    pub async fn start(
+        config: OutboxConfig,
+        inner: SharedEventPublisher,
+    ) -> Result<Self, EventPublisherError> {
+        fs::create_dir_all(config.dir.join(DEAD_LETTER_DIR))
+            .await
+            .map_err(|err| with_path(err, &config.dir))?;
+        remove_partial_events(&config.dir).await?;
+        let mut pending = pending_events(&config.dir).await?;
+        let mut next_sequence = pending
+            .last()
+            .and_then(|path| sequence_of(path))
+            .map_or(0, |sequence| sequence + 1);
+        if config.replay_dead_letters {
+            let replayed = replay_dead_letters(&config.dir, next_sequence).await?;
+            next_sequence += replayed.len() as u64;
+            pending.extend(replayed);
+        }
+        info!(
+            "event outbox {:?} starting with {} pending events",
+            config.dir,
+            pending.len()
+        );
+
+        let metrics = Arc::new(OutboxMetrics::default());
+        metrics.backlog.store(pending.len(), Ordering::SeqCst);
+        let stored = Arc::new(Notify::new());
+        let dispatcher = tokio::spawn(dispatch(
+            config.clone(),
+            inner,
+            metrics.clone(),
+            stored.clone(),
+        ));
+        Ok(Self {
+            dir: config.dir,
+            next_sequence: Mutex::new(next_sequence),
+            metrics,
+            stored,
+            dispatcher,
+        })
+    }
+}
+
+impl Drop for OutboxPublisher {
+    fn drop(&mut self) {
+        // whatever is still stored is delivered by the next run
+        self.dispatcher.abort();
+    }
+}
+
+impl OutboxPublisher {
+    async fn store(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        let mut next_sequence = self.next_sequence.lock().await;
+        let path = self
+            .dir
+            .join(format!("{:020}.{}", *next_sequence, EVENT_EXTENSION));
+        let events = details.len();
+        // counted before the dispatcher can see it, so the backlog never goes below zero
+        let backlog = self.metrics.backlog.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Err(err) = write_record(&path, detail_type, &details).await {
+            self.metrics.backlog.fetch_sub(1, Ordering::SeqCst);
+            return Err(err);
+        }
+        *next_sequence += 1;
+        drop(next_sequence);
+        debug!(
+            backlog,
+            "{} {} events stored in outbox as {:?}", events, detail_type, path
+        );
+        self.stored.notify_one();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventPublisher for OutboxPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.store(detail_type, vec![detail]).await
+    }
+
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        if details.is_empty() {
+            return Ok(());
+        }
+        self.store(detail_type, details).await
+    }
+
+    fn outbox_metrics(&self) -> Option<OutboxMetricsSnapshot> {
+        Some(snapshot(&self.metrics))
+    }
+}
+
+async fn dispatch(
+    config: OutboxConfig,
+    inner: SharedEventPublisher,
+    metrics: Arc<OutboxMetrics>,
+    stored: Arc<Notify>,
+) {
+    // events that could not be dead lettered; they stay stored for the next run
+    let mut stuck = HashSet::new();
+    loop {
+        let pending = match pending_events(&config.dir).await {
+            Ok(pending) => pending,
+            Err(err) => {
+                error!("event outbox {:?} could not be listed: {}", config.dir, err);
+                sleep(config.max_backoff).await;
+                continue;
+            }
+        };
+        let pending: Vec<PathBuf> = pending
+            .into_iter()
+            .filter(|path| !stuck.contains(path))
+            .collect();
+        if pending.is_empty() {
+            stored.notified().await;
+            continue;
+        }
+        for path in pending {
+            if !deliver(&config, inner.as_ref(), &metrics, &path).await {
+                stuck.insert(path);
+            }
+        }
+        let snapshot = snapshot(&metrics);
+        info!(
+            backlog = snapshot.backlog,
+            delivered = snapshot.delivered,
+            retries = snapshot.retries,
+            dead_lettered = snapshot.dead_lettered,
+            "event outbox caught up"
+        );
+    }
+}
+
+fn snapshot(metrics: &OutboxMetrics) -> OutboxMetricsSnapshot {
+    OutboxMetricsSnapshot {
+        backlog: metrics.backlog.load(Ordering::SeqCst),
+        delivered: metrics.delivered.load(Ordering::SeqCst),
+        retries: metrics.retries.load(Ordering::SeqCst),
+        dead_lettered: metrics.dead_lettered.load(Ordering::SeqCst),
+    }
+}
+
+// false when the event is still stored where it was, so the dispatcher skips it from now on
+async fn deliver(
+    config: &OutboxConfig,
+    inner: &dyn EventPublisher,
+    metrics: &OutboxMetrics,
+    path: &Path,
+) -> bool {
+    let (detail_type, mut details) = match read_record(path).await {
+        Ok(record) => record,
+        Err(err) => {
+            // retrying cannot help
+            error!("event outbox {:?} is unreadable: {}", path, err);
+            return dead_letter(config, metrics, path).await;
+        }
+    };
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = inner.publish_batch(&detail_type, details.clone()).await;
+        if let Some(undelivered) = result.as_ref().err().and_then(undelivered_entries) {
+            // only the events the sink did not take are sent again, or dead lettered
+            details = undelivered
+                .into_iter()
+                .filter_map(|index| details.get(index).cloned())
+                .collect();
+            if let Err(err) = write_record(path, &detail_type, &details).await {
+                // the whole record is delivered again on the next run rather than lost
+                error!(
+                    "event {:?} could not be narrowed to its undelivered events: {}",
+                    path, err
+                );
+            }
+        }
+        match result {
+            Ok(()) => {
+                if let Err(err) = fs::remove_file(path).await {
+                    // delivered again on the next run rather than lost
+                    error!("delivered event {:?} could not be removed: {}", path, err);
+                }
+                metrics.delivered.fetch_add(1, Ordering::SeqCst);
+                let backlog = metrics.backlog.fetch_sub(1, Ordering::SeqCst) - 1;
+                debug!(backlog, "{} event {:?} delivered", detail_type, path);
+                return true;
+            }
+            Err(err) if err.is_permanent() => {
+                error!(
+                    "{} event {:?} failed permanently on attempt {}, dead lettering: {}",
+                    detail_type, path, attempt, err
+                );
+                return dead_letter(config, metrics, path).await;
+            }
+            Err(err) if attempt >= config.max_attempts => {
+                error!(
+                    "{} event {:?} failed after {} attempts, dead lettering: {}",
+                    detail_type, path, attempt, err
+                );
+                return dead_letter(config, metrics, path).await;
+            }
+            Err(err) => {
+                let backoff = config.backoff(attempt);
+                metrics.retries.fetch_add(1, Ordering::SeqCst);
+                warn!(
+                    backlog = metrics.backlog.load(Ordering::SeqCst),
+                    "{} event {:?} attempt {} failed, retrying after {:?}: {}",
+                    detail_type,
+                    path,
+                    attempt,
+                    backoff,
+                    err
+                );
+                sleep(backoff).await;
+            }
+        }
+    }
+}
+
+async fn dead_letter(config: &OutboxConfig, metrics: &OutboxMetrics, path: &Path) -> bool {
+    let dead_path = config
+        .dir
+        .join(DEAD_LETTER_DIR)
+        .join(path.file_name().unwrap_or_default());
+    if let Err(err) = fs::rename(path, &dead_path).await {
+        // kept rather than lost; the next run tries it again
+        error!(
+            "event {:?} could not be dead lettered, leaving it for the next run: {}",
+            path, err
+        );
+        return false;
+    }
+    metrics.dead_lettered.fetch_add(1, Ordering::SeqCst);
+    metrics.backlog.fetch_sub(1, Ordering::SeqCst);
+    true
+}
+
+// dead lettered events in their original order, renamed to follow the pending ones
+async fn replay_dead_letters(
+    dir: &Path,
+    first_sequence: u64,
+) -> Result<Vec<PathBuf>, EventPublisherError> {
+    let dead_letters = pending_events(&dir.join(DEAD_LETTER_DIR)).await?;
+    let mut replayed = Vec::with_capacity(dead_letters.len());
+    for (sequence, dead_path) in (first_sequence..).zip(dead_letters) {
+        let path = dir.join(format!("{:020}.{}", sequence, EVENT_EXTENSION));
+        fs::rename(&dead_path, &path)
+            .await
+            .map_err(|err| with_path(err, &dead_path))?;
+        replayed.push(path);
+    }
+    if !replayed.is_empty() {
+        info!(
+            "event outbox {:?} replaying {} dead lettered events",
+            dir,
+            replayed.len()
+        );
+    }
+    Ok(replayed)
+}
+
+// left by a publish that never returned, so nobody was told the event was stored
+async fn remove_partial_events(dir: &Path) -> Result<(), EventPublisherError> {
+    let mut entries = fs::read_dir(dir).await.map_err(|err| with_path(err, dir))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == PARTIAL_EXTENSION)
+        {
+            warn!("event outbox removing partially written {:?}", path);
+            fs::remove_file(&path)
+                .await
+                .map_err(|err| with_path(err, &path))?;
+        }
+    }
+    Ok(())
+}
+
+// of a batch whose other events were delivered, so the record is never empty
+fn undelivered_entries(err: &EventPublisherError) -> Option<Vec<usize>> {
+    err.undelivered_entries()
+        .filter(|undelivered| !undelivered.is_empty())
+}
+
+// written under another name and renamed, so the dispatcher never reads a partial record
+async fn write_record(
+    path: &Path,
+    detail_type: &str,
+    details: &[String],
+) -> Result<(), EventPublisherError> {
+    let record = json!({ "detail_type": detail_type, "details": details });
+    let partial_path = path.with_extension(PARTIAL_EXTENSION);
+    let mut file = File::create(&partial_path)
+        .await
+        .map_err(|err| with_path(err, &partial_path))?;
+    file.write_all(&serde_json::to_vec(&record)?).await?;
+    file.sync_all().await?;
+    fs::rename(&partial_path, path).await?;
+    Ok(())
+}
+
+// the detail type and details of a record; records of a single event stored by earlier versions
+// have a detail instead of details
+async fn read_record(path: &Path) -> Result<(String, Vec<String>), EventPublisherError> {
+    let record: Value = serde_json::from_slice(&fs::read(path).await?)?;
+    let details = match (record["details"].as_array(), record["detail"].as_str()) {
+        (Some(details), _) => details
+            .iter()
+            .map(|detail| detail.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>(),
+        (None, Some(detail)) => Some(vec![detail.to_string()]),
+        (None, None) => None,
+    };
+    match (record["detail_type"].as_str(), details) {
+        (Some(detail_type), Some(details)) => Ok((detail_type.to_string(), details)),
+        _ => Err(EventPublisherError::File(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing detail_type or details",
+        ))),
+    }
+}
+
+// stored events in the order they were published
+async fn pending_events(dir: &Path) -> Result<Vec<PathBuf>, EventPublisherError> {
+    let mut entries = fs::read_dir(dir).await.map_err(|err| with_path(err, dir))?;
+    let mut pending = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == EVENT_EXTENSION)
+        {
+            pending.push(path);
+        }
+    }
+    pending.sort();
+    Ok(pending)
+}
+
+fn sequence_of(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+fn with_path(err: io::Error, path: &Path) -> io::Error {
+    io::Error::new(err.kind(), format!("{:?}: {}", path, err))
+}
@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+use serde_json::{from_str, Value};
+use tracing::error;
+
+use super::{EventPublisher, EventPublisherError, OutboxMetricsSnapshot, SharedEventPublisher};
+use crate::schema::validate_payload;
+
+// Checks the payload of each event against the JSON Schema of its detail type before the inner
+// publisher sees it, so a payload the writer cannot read is never sent
+pub struct ValidatingPublisher {
+    inner: SharedEventPublisher,
+}
+
+impl ValidatingPublisher {
+    pub fn new(inner: SharedEventPublisher) -> Self {
+        Self { inner }
+    }
+
+    fn validate(&self, detail_type: &str, detail: &str) -> Result<(), EventPublisherError> {
+        let mut detail: Value = from_str(detail)?;
+        // the data of a CloudEvent, or the bare payload of an unversioned event
+        let data = match detail.get("specversion") {
+            Some(_) => detail["data"].take(),
+            None => detail,
+        };
+        validate_payload(detail_type, &data).inspect_err(|err| error!("{}", err))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventPublisher for ValidatingPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.validate(detail_type, &detail)?;
+        self.inner.publish(detail_type, detail).await
+    }
+
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        for detail in &details {
+            self.validate(detail_type, detail)?;
+        }
+        self.inner.publish_batch(detail_type, details).await
+    }
+
+    async fn check(&self) -> Result<(), EventPublisherError> {
+        self.inner.check().await
+    }
+
+    fn outbox_metrics(&self) -> Option<OutboxMetricsSnapshot> {
+        self.inner.outbox_metrics()
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use tracing::{debug, error};
+
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+use super::{event_envelope, EventPublisher, EventPublisherError};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// POSTs each event as JSON; anything but a 2xx response is a failed publish
+#[derive(Debug, Clone)]
+pub struct WebhookPublisher {
+    client: Client,
+    url: String,
+    // source and detail-type prefix of each event
+    config: EventPublisherConfig,
+}
+
+impl WebhookPublisher {
+    pub fn new(url: String, config: EventPublisherConfig) -> Result<Self, EventPublisherError> {
+        let client = Client::builder().timeout(WEBHOOK_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            url,
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for WebhookPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        let body = serde_json::to_string(&event_envelope(&self.config, detail_type, &detail)?)?;
+        let response = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .inspect_err(|err| error!("webhook {} send err is {}", self.url, err))?;
+        let status = response.status();
+        debug!(
+            "webhook {} responded {} to {} event",
+            self.url, status, detail_type
+        );
+        if !status.is_success() {
+            return Err(EventPublisherError::WebhookStatus {
+                url: self.url.clone(),
+                status: status.as_u16(),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+use jsonschema::Validator;
+use schemars::schema_for;
+use serde_json::{to_value, Value};
+use thiserror::Error;
+
+use drive_deposits_rest_types::cloud_event::{data_schema, EVENT_SOURCE};
+
+use crate::payload_types::{Bank, CalculatePortfolioResponse, DepositLevel};
+use crate::publisher::{BANK_LEVEL, DEPOSIT_LEVEL, PORTFOLIO_LEVEL};
+
+// detail types whose payloads have a schema
+pub const PAYLOAD_DETAIL_TYPES: [&str; 3] = [BANK_LEVEL, PORTFOLIO_LEVEL, DEPOSIT_LEVEL];
+
+static VALIDATORS: LazyLock<HashMap<&'static str, Validator>> = LazyLock::new(|| {
+    PAYLOAD_DETAIL_TYPES
+        .into_iter()
+        .filter_map(|detail_type| {
+            let schema = payload_schema(detail_type)?;
+            let validator = jsonschema::validator_for(&schema)
+                .unwrap_or_else(|err| panic!("{} payload schema is invalid: {}", detail_type, err));
+            Some((detail_type, validator))
+        })
+        .collect()
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    // JSON pointer into the payload, for example /banks/0/deposits/1/apy
+    pub instance_path: String,
+    // JSON pointer to the schema keyword that failed
+    pub schema_path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instance_path = match self.instance_path.as_str() {
+            "" => "/",
+            instance_path => instance_path,
+        };
+        write!(
+            f,
+            "{}: {} (schema {})",
+            instance_path, self.message, self.schema_path
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{detail_type} payload does not match {schema}: {}", violations_summary(.violations))]
+pub struct SchemaValidationError {
+    pub detail_type: String,
+    pub schema: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+fn violations_summary(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(SchemaViolation::to_string)
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+// JSON Schema (draft-07) of the data of an event, generated from the payload types; $id is the
+// dataschema of its CloudEvents envelope with the default source, the payload is the same for any
+pub fn payload_schema(detail_type: &str) -> Option<Value> {
+    let schema = match detail_type {
+        BANK_LEVEL => schema_for!(Bank),
+        PORTFOLIO_LEVEL => schema_for!(CalculatePortfolioResponse),
+        DEPOSIT_LEVEL => schema_for!(DepositLevel),
+        _ => return None,
+    };
+    let mut schema = to_value(schema).ok()?;
+    schema["$id"] = Value::String(data_schema(EVENT_SOURCE, detail_type));
+    Some(schema)
+}
+
+// every violation, not just the first; detail types without a schema always pass
+pub fn validate_payload(detail_type: &str, data: &Value) -> Result<(), SchemaValidationError> {
+    let Some(validator) = VALIDATORS.get(detail_type) else {
+        return Ok(());
+    };
+    let violations = validator
+        .iter_errors(data)
+        .map(|err| SchemaViolation {
+            instance_path: err.instance_path.to_string(),
+            schema_path: err.schema_path.to_string(),
+            message: err.to_string(),
+        })
+        .collect::<Vec<SchemaViolation>>();
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(SchemaValidationError {
+        detail_type: detail_type.to_string(),
+        schema: data_schema(EVENT_SOURCE, detail_type),
+        violations,
+    })
+}
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+use drive_deposits_event_source::object_store::{FileObjectStore, ObjectStore, ObjectStoreError};
+use drive_deposits_event_source::publisher::{
+    ChannelPublisher, ClaimCheckPublisher, EventPublisher, PublishedEvent, PORTFOLIO_LEVEL,
+};
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+fn store_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("drive-deposits-claim-check-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn cloud_event(id: &str, banks: usize) -> String {
+    json!({
+        "specversion": "1.0",
+        "id": id,
+        "type": "drive-deposits.portfolio-level.v1",
+        "subject": "portfolio-1",
+        "data": { "banks": vec!["VISION-BANK"; banks] },
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_oversized_event_data_is_stored_and_referenced() {
+    let store = Arc::new(FileObjectStore::new(store_dir("oversized")));
+    let (channel, mut events) = ChannelPublisher::new();
+    let publisher = ClaimCheckPublisher::new(
+        store.clone(),
+        Arc::new(channel),
+        &EventPublisherConfig::default(),
+    )
+    .with_max_event_bytes(1024);
+
+    publisher
+        .publish_batch(
+            PORTFOLIO_LEVEL,
+            vec![cloud_event("small", 1), cloud_event("large", 100)],
+        )
+        .await
+        .unwrap();
+
+    let small = events.recv().await.unwrap();
+    assert_eq!(
+        small,
+        PublishedEvent {
+            detail_type: PORTFOLIO_LEVEL.to_string(),
+            detail: cloud_event("small", 1),
+        }
+    );
+
+    let large: Value = serde_json::from_str(&events.recv().await.unwrap().detail).unwrap();
+    assert!(large.get("data").is_none());
+    assert_eq!(large["subject"], "portfolio-1");
+    let dataref = large["dataref"].as_str().unwrap();
+    assert!(dataref.ends_with("portfolio-level/large.json"));
+    let data: Value = serde_json::from_slice(&store.get(dataref).await.unwrap()).unwrap();
+    assert_eq!(data["banks"].as_array().unwrap().len(), 100);
+}
+
+#[tokio::test]
+async fn test_configured_source_and_prefix_count_toward_event_size() {
+    let store = Arc::new(FileObjectStore::new(store_dir("prefixed")));
+    let detail = cloud_event("prefixed", 1);
+    // fits with the default source and no prefix
+    let max_event_bytes = "drive-deposits".len() + PORTFOLIO_LEVEL.len() + detail.len();
+    let config = EventPublisherConfig {
+        source: "drive-deposits-staging".to_string(),
+        detail_type_prefix: "staging-".to_string(),
+        ..EventPublisherConfig::default()
+    };
+    let (channel, mut events) = ChannelPublisher::new();
+    let publisher = ClaimCheckPublisher::new(store, Arc::new(channel), &config)
+        .with_max_event_bytes(max_event_bytes);
+
+    publisher.publish(PORTFOLIO_LEVEL, detail).await.unwrap();
+
+    let event: Value = serde_json::from_str(&events.recv().await.unwrap().detail).unwrap();
+    assert!(event.get("data").is_none());
+    assert!(event["dataref"]
+        .as_str()
+        .unwrap()
+        .ends_with("portfolio-level/prefixed.json"));
+}
+
+#[tokio::test]
+async fn test_file_object_store_rejects_references_outside_its_dir() {
+    let dir = store_dir("foreign");
+    let store = FileObjectStore::new(dir.clone());
+    store
+        .put("bank-level/1.json", b"{}".to_vec())
+        .await
+        .unwrap();
+
+    for reference in [
+        "file:///etc/passwd".to_string(),
+        format!("file://{}/../outside.json", dir.display()),
+        "s3://bucket/bank-level/1.json".to_string(),
+    ] {
+        assert!(matches!(
+            store.get(&reference).await,
+            Err(ObjectStoreError::ForeignReference(_))
+        ));
+    }
+}
@@ -0,0 +1,599 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use pretty_assertions::assert_eq;
+use tokio::time::{sleep, timeout};
+
+use drive_deposits_event_source::eb::{DriveDepositsEventBridgeError, EntryOutcome};
+use drive_deposits_event_source::publisher::{
+    ChannelPublisher, EventPublisher, EventPublisherError, OutboxConfig, OutboxMetricsSnapshot,
+    OutboxPublisher, PublishedEvent, BANK_LEVEL, PORTFOLIO_LEVEL,
+};
+
+// fails its first `failures` publishes, like a sink that is down for a while
+struct FlakyPublisher {
+    failures: AtomicU32,
+    inner: ChannelPublisher,
+}
+
+#[async_trait]
+impl EventPublisher for FlakyPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+        if failing {
+            return Err(EventPublisherError::ChannelClosed);
+        }
+        self.inner.publish(detail_type, detail).await
+    }
+}
+
+// the detail type and details of each publish_batch; the first one fails for the entries of
+// failed_first, like a PutEvents request where some entries were throttled
+#[derive(Default)]
+struct BatchRecordingPublisher {
+    batches: Mutex<Vec<(String, Vec<String>)>>,
+    failed_first: Vec<usize>,
+}
+
+#[async_trait]
+impl EventPublisher for BatchRecordingPublisher {
+    async fn publish(&self, detail_type: &str, detail: String) -> Result<(), EventPublisherError> {
+        self.publish_batch(detail_type, vec![detail]).await
+    }
+
+    async fn publish_batch(
+        &self,
+        detail_type: &str,
+        details: Vec<String>,
+    ) -> Result<(), EventPublisherError> {
+        let mut batches = self.batches.lock().unwrap();
+        let first = batches.is_empty();
+        let outcomes = (0..details.len())
+            .map(|index| {
+                if first && self.failed_first.contains(&index) {
+                    EntryOutcome::Failed {
+                        error_code: "ThrottlingException".to_string(),
+                        error_message: None,
+                        attempts: 4,
+                    }
+                } else {
+                    EntryOutcome::Delivered {
+                        event_id: None,
+                        attempts: 1,
+                    }
+                }
+            })
+            .collect();
+        batches.push((detail_type.to_string(), details));
+        if first && !self.failed_first.is_empty() {
+            return Err(DriveDepositsEventBridgeError::FailedEntries {
+                summary: "throttled".to_string(),
+                outcomes,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+// an EventBridge rejecting every entry as malformed
+struct MalformedPublisher;
+
+#[async_trait]
+impl EventPublisher for MalformedPublisher {
+    async fn publish(
+        &self,
+        _detail_type: &str,
+        _detail: String,
+    ) -> Result<(), EventPublisherError> {
+        Err(DriveDepositsEventBridgeError::FailedEntries {
+            summary: "malformed".to_string(),
+            outcomes: vec![EntryOutcome::Failed {
+                error_code: "MalformedDetail".to_string(),
+                error_message: None,
+                attempts: 1,
+            }],
+        }
+        .into())
+    }
+}
+
+// a webhook answering every event with a client error
+struct RejectingPublisher;
+
+#[async_trait]
+impl EventPublisher for RejectingPublisher {
+    async fn publish(
+        &self,
+        _detail_type: &str,
+        _detail: String,
+    ) -> Result<(), EventPublisherError> {
+        Err(EventPublisherError::WebhookStatus {
+            url: "http://localhost/events".to_string(),
+            status: 400,
+        })
+    }
+}
+
+fn banks(names: &[&str]) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| format!(r#"{{"name":"{}"}}"#, name))
+        .collect()
+}
+
+fn outbox_config(name: &str) -> OutboxConfig {
+    let dir = std::env::temp_dir().join(format!("drive-deposits-outbox-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    OutboxConfig {
+        initial_backoff: Duration::from_millis(1),
+        ..OutboxConfig::new(dir)
+    }
+}
+
+fn stored_events(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .count()
+}
+
+async fn wait_for_metrics(outbox: &OutboxPublisher, expected: OutboxMetricsSnapshot) {
+    timeout(Duration::from_secs(5), async {
+        while outbox.outbox_metrics() != Some(expected) {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("outbox metrics {:?}", outbox.outbox_metrics()));
+}
+
+#[tokio::test]
+async fn test_outbox_retries_until_delivered_in_order() {
+    let config = outbox_config("retries");
+    let (channel, mut events) = ChannelPublisher::new();
+    let flaky = FlakyPublisher {
+        failures: AtomicU32::new(3),
+        inner: channel,
+    };
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(flaky))
+        .await
+        .unwrap();
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+    outbox
+        .publish(PORTFOLIO_LEVEL, r#"{"banks":[]}"#.to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        events.recv().await.unwrap(),
+        PublishedEvent {
+            detail_type: BANK_LEVEL.to_string(),
+            detail: r#"{"name":"VISION-BANK"}"#.to_string(),
+        }
+    );
+    assert_eq!(
+        events.recv().await.unwrap().detail_type,
+        PORTFOLIO_LEVEL.to_string()
+    );
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 2,
+            retries: 3,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    assert_eq!(stored_events(&config.dir), 0);
+}
+
+#[tokio::test]
+async fn test_outbox_delivers_events_stored_by_a_previous_run() {
+    let config = outbox_config("previous-run");
+    let down = FlakyPublisher {
+        failures: AtomicU32::new(u32::MAX),
+        inner: ChannelPublisher::new().0,
+    };
+    let outbox = OutboxPublisher::start(
+        OutboxConfig {
+            initial_backoff: Duration::from_secs(60),
+            ..config.clone()
+        },
+        Arc::new(down),
+    )
+    .await
+    .unwrap();
+    for bank in ["VISION-BANK", "PENSFED"] {
+        outbox
+            .publish(BANK_LEVEL, format!(r#"{{"name":"{}"}}"#, bank))
+            .await
+            .unwrap();
+    }
+    drop(outbox);
+    assert_eq!(stored_events(&config.dir), 2);
+
+    let (channel, mut events) = ChannelPublisher::new();
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(channel))
+        .await
+        .unwrap();
+    assert_eq!(
+        events.recv().await.unwrap().detail,
+        r#"{"name":"VISION-BANK"}"#
+    );
+    assert_eq!(events.recv().await.unwrap().detail, r#"{"name":"PENSFED"}"#);
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 2,
+            retries: 0,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_outbox_dead_letters_after_max_attempts() {
+    let config = OutboxConfig {
+        max_attempts: 2,
+        ..outbox_config("dead-letter")
+    };
+    let down = FlakyPublisher {
+        failures: AtomicU32::new(u32::MAX),
+        inner: ChannelPublisher::new().0,
+    };
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(down))
+        .await
+        .unwrap();
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 0,
+            retries: 1,
+            dead_lettered: 1,
+        },
+    )
+    .await;
+    assert_eq!(stored_events(&config.dir), 0);
+    assert_eq!(stored_events(&config.dir.join("dead")), 1);
+}
+
+#[tokio::test]
+async fn test_outbox_retries_until_the_sink_is_back_within_max_attempts() {
+    let config = OutboxConfig {
+        max_attempts: 51,
+        max_backoff: Duration::from_millis(1),
+        ..outbox_config("until-back")
+    };
+    let (channel, mut events) = ChannelPublisher::new();
+    let flaky = FlakyPublisher {
+        failures: AtomicU32::new(50),
+        inner: channel,
+    };
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(flaky))
+        .await
+        .unwrap();
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        events.recv().await.unwrap().detail,
+        r#"{"name":"VISION-BANK"}"#
+    );
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 1,
+            retries: 50,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    assert_eq!(stored_events(&config.dir.join("dead")), 0);
+}
+
+#[tokio::test]
+async fn test_outbox_replays_dead_letters_after_pending_events() {
+    let config = OutboxConfig {
+        max_attempts: 1,
+        ..outbox_config("replay")
+    };
+    let down = FlakyPublisher {
+        failures: AtomicU32::new(1),
+        inner: ChannelPublisher::new().0,
+    };
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(down))
+        .await
+        .unwrap();
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 0,
+            retries: 0,
+            dead_lettered: 1,
+        },
+    )
+    .await;
+    drop(outbox);
+    // stored by a run whose sink was down, so it is still pending
+    std::fs::write(
+        config.dir.join("00000000000000000001.json"),
+        r#"{"detail_type":"portfolio-level","detail":"{\"banks\":[]}"}"#,
+    )
+    .unwrap();
+
+    let (channel, mut events) = ChannelPublisher::new();
+    let outbox = OutboxPublisher::start(
+        OutboxConfig {
+            replay_dead_letters: true,
+            ..config.clone()
+        },
+        Arc::new(channel),
+    )
+    .await
+    .unwrap();
+    assert_eq!(events.recv().await.unwrap().detail_type, PORTFOLIO_LEVEL);
+    assert_eq!(
+        events.recv().await.unwrap().detail,
+        r#"{"name":"VISION-BANK"}"#
+    );
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 2,
+            retries: 0,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    assert_eq!(stored_events(&config.dir.join("dead")), 0);
+
+    // numbered after the replayed events
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"PENSFED"}"#.to_string())
+        .await
+        .unwrap();
+    assert_eq!(events.recv().await.unwrap().detail, r#"{"name":"PENSFED"}"#);
+}
+
+#[tokio::test]
+async fn test_outbox_removes_partial_events_at_start() {
+    let config = outbox_config("partial");
+    std::fs::create_dir_all(&config.dir).unwrap();
+    let partial = config.dir.join("00000000000000000000.partial");
+    std::fs::write(&partial, r#"{"detail_type":"bank-le"#).unwrap();
+
+    let (channel, mut events) = ChannelPublisher::new();
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(channel))
+        .await
+        .unwrap();
+    assert!(!partial.exists());
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        events.recv().await.unwrap().detail,
+        r#"{"name":"VISION-BANK"}"#
+    );
+}
+
+#[tokio::test]
+async fn test_outbox_keeps_event_that_cannot_be_dead_lettered() {
+    let config = OutboxConfig {
+        max_attempts: 2,
+        ..outbox_config("dead-letter-fails")
+    };
+    let down = FlakyPublisher {
+        failures: AtomicU32::new(u32::MAX),
+        inner: ChannelPublisher::new().0,
+    };
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(down))
+        .await
+        .unwrap();
+    // a file where the dead letter directory should be, so moving an event there fails
+    let dead = config.dir.join("dead");
+    std::fs::remove_dir(&dead).unwrap();
+    std::fs::write(&dead, "").unwrap();
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 1,
+            delivered: 0,
+            retries: 1,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(
+        outbox.outbox_metrics().unwrap().retries,
+        1,
+        "not retried again in this run"
+    );
+    assert_eq!(stored_events(&config.dir), 1);
+}
+
+#[tokio::test]
+async fn test_outbox_stores_and_delivers_a_batch_as_one_record() {
+    let config = outbox_config("batch");
+    let inner = Arc::new(BatchRecordingPublisher::default());
+    let outbox = OutboxPublisher::start(config.clone(), inner.clone())
+        .await
+        .unwrap();
+
+    outbox
+        .publish_batch(
+            BANK_LEVEL,
+            banks(&["VISION-BANK", "PENSFED", "MORGAN-STANLEY"]),
+        )
+        .await
+        .unwrap();
+    outbox
+        .publish(PORTFOLIO_LEVEL, r#"{"banks":[]}"#.to_string())
+        .await
+        .unwrap();
+
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 2,
+            retries: 0,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    assert_eq!(
+        *inner.batches.lock().unwrap(),
+        vec![
+            (
+                BANK_LEVEL.to_string(),
+                banks(&["VISION-BANK", "PENSFED", "MORGAN-STANLEY"])
+            ),
+            (
+                PORTFOLIO_LEVEL.to_string(),
+                vec![r#"{"banks":[]}"#.to_string()]
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_outbox_resends_only_the_undelivered_events_of_a_batch() {
+    let config = outbox_config("batch-undelivered");
+    let inner = Arc::new(BatchRecordingPublisher {
+        failed_first: vec![1],
+        ..BatchRecordingPublisher::default()
+    });
+    let outbox = OutboxPublisher::start(config.clone(), inner.clone())
+        .await
+        .unwrap();
+
+    outbox
+        .publish_batch(
+            BANK_LEVEL,
+            banks(&["VISION-BANK", "PENSFED", "MORGAN-STANLEY"]),
+        )
+        .await
+        .unwrap();
+
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 1,
+            retries: 1,
+            dead_lettered: 0,
+        },
+    )
+    .await;
+    let batches = inner.batches.lock().unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].1, banks(&["PENSFED"]));
+}
+
+#[tokio::test]
+async fn test_outbox_dead_letters_permanent_failures_without_retrying() {
+    let config = outbox_config("permanent");
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(RejectingPublisher))
+        .await
+        .unwrap();
+
+    for bank in ["VISION-BANK", "PENSFED"] {
+        outbox
+            .publish(BANK_LEVEL, format!(r#"{{"name":"{}"}}"#, bank))
+            .await
+            .unwrap();
+    }
+
+    // the first rejected event does not hold up the second
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 0,
+            retries: 0,
+            dead_lettered: 2,
+        },
+    )
+    .await;
+    assert_eq!(stored_events(&config.dir.join("dead")), 2);
+}
+
+#[tokio::test]
+async fn test_outbox_dead_letters_entries_eventbridge_rejects_as_malformed() {
+    let config = outbox_config("malformed");
+    let outbox = OutboxPublisher::start(config.clone(), Arc::new(MalformedPublisher))
+        .await
+        .unwrap();
+
+    outbox
+        .publish(BANK_LEVEL, r#"{"name":"VISION-BANK"}"#.to_string())
+        .await
+        .unwrap();
+
+    wait_for_metrics(
+        &outbox,
+        OutboxMetricsSnapshot {
+            backlog: 0,
+            delivered: 0,
+            retries: 0,
+            dead_lettered: 1,
+        },
+    )
+    .await;
+}
+
+#[test]
+fn test_outbox_retries_a_limited_number_of_times_by_default() {
+    let config = OutboxConfig::new(std::env::temp_dir());
+
+    assert_eq!(config.max_attempts, 20);
+}
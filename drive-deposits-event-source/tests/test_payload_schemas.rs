@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use pretty_assertions::assert_eq;
+use serde_json::{json, to_value, Value};
+
+use drive_deposits_event_source::payload_types::{Bank, CalculatePortfolioResponse, Deposit};
+use drive_deposits_event_source::publisher::{
+    ChannelPublisher, EventPublisher, EventPublisherError, ValidatingPublisher, BANK_LEVEL,
+    PORTFOLIO_LEVEL,
+};
+use drive_deposits_event_source::schema::{payload_schema, validate_payload, PAYLOAD_DETAIL_TYPES};
+
+fn schema_path(detail_type: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("schemas")
+        .join(format!("{}.v1.schema.json", detail_type))
+}
+
+fn portfolio() -> CalculatePortfolioResponse {
+    CalculatePortfolioResponse {
+        uuid: "portfolio-1".to_string(),
+        banks: vec![Bank {
+            uuid: "bank-1".to_string(),
+            name: "VISION-BANK".to_string(),
+            bank_tz: "America/New_York".to_string(),
+            deposits: vec![Deposit {
+                uuid: "deposit-1".to_string(),
+                apy: "2.4".to_string(),
+                ..Deposit::default()
+            }],
+            ..Bank::default()
+        }],
+        created_at: "2024-03-05T09:00:00.000000Z".to_string(),
+        tenant_id: Some("tenant-a".to_string()),
+        ..CalculatePortfolioResponse::default()
+    }
+}
+
+// the published schemas are the contract with the writer; regenerate them with
+// UPDATE_PAYLOAD_SCHEMAS=1 cargo test -p drive-deposits-event-source --test test_payload_schemas
+#[test]
+fn test_published_schemas_match_payload_types() {
+    for detail_type in PAYLOAD_DETAIL_TYPES {
+        let generated = payload_schema(detail_type).unwrap();
+        let path = schema_path(detail_type);
+        if std::env::var("UPDATE_PAYLOAD_SCHEMAS").is_ok() {
+            let mut contents = serde_json::to_string_pretty(&generated).unwrap();
+            contents.push('\n');
+            std::fs::write(&path, contents).unwrap();
+        }
+        let published: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(published, generated, "{:?} is out of date", path);
+    }
+}
+
+#[test]
+fn test_serialized_payload_matches_its_schema() {
+    let portfolio = portfolio();
+    assert!(validate_payload(PORTFOLIO_LEVEL, &to_value(&portfolio).unwrap()).is_ok());
+    assert!(validate_payload(BANK_LEVEL, &to_value(&portfolio.banks[0]).unwrap()).is_ok());
+}
+
+#[test]
+fn test_violations_name_every_offending_field() {
+    let mut payload = to_value(portfolio()).unwrap();
+    payload["banks"][0]["deposits"][0]["apy"] = json!(2.4);
+    payload.as_object_mut().unwrap().remove("created_at");
+
+    let err = validate_payload(PORTFOLIO_LEVEL, &payload).unwrap_err();
+    let mut instance_paths = err
+        .violations
+        .iter()
+        .map(|violation| violation.instance_path.as_str())
+        .collect::<Vec<&str>>();
+    instance_paths.sort();
+    assert_eq!(instance_paths, vec!["", "/banks/0/deposits/0/apy"]);
+    let message = err.to_string();
+    assert!(message.contains("urn:drive-deposits:schema:portfolio-level:v1"));
+    assert!(message.contains("\"created_at\" is a required property"));
+    assert!(message.contains("/banks/0/deposits/0/apy: 2.4 is not of type \"string\""));
+}
+
+#[tokio::test]
+async fn test_validating_publisher_only_forwards_valid_payloads() {
+    let (channel, mut events) = ChannelPublisher::new();
+    let publisher = ValidatingPublisher::new(Arc::new(channel));
+    let event = |data: Value| {
+        json!({
+            "specversion": "1.0",
+            "id": "event-1",
+            "type": "drive-deposits.portfolio-level.v1",
+            "data": data,
+        })
+        .to_string()
+    };
+
+    let result = publisher
+        .publish(PORTFOLIO_LEVEL, event(json!({ "uuid": "portfolio-1" })))
+        .await;
+    assert!(matches!(
+        result,
+        Err(EventPublisherError::SchemaValidation(_))
+    ));
+    assert!(events.try_recv().is_err());
+
+    publisher
+        .publish(PORTFOLIO_LEVEL, event(to_value(portfolio()).unwrap()))
+        .await
+        .unwrap();
+    assert_eq!(events.recv().await.unwrap().detail_type, PORTFOLIO_LEVEL);
+}
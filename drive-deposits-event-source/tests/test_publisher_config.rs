@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use pretty_assertions::assert_eq;
+
+use drive_deposits_event_source::eb::missing_rules;
+use drive_deposits_event_source::publisher::BANK_LEVEL;
+use drive_deposits_rest_types::server_config::{
+    EventPublisherConfig, ServerConfig, LOCALSTACK_ENDPOINT,
+};
+
+fn with_env(vars: &[(&'static str, &str)]) -> EventPublisherConfig {
+    let vars: HashMap<&'static str, String> = vars
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    ServerConfig::default()
+        .with_env_overrides(|name| vars.get(name).cloned())
+        .unwrap()
+        .event_publisher
+}
+
+#[test]
+fn test_event_publisher_env_overrides() {
+    let config = with_env(&[
+        (
+            "EVENT_PUBLISHER_EVENT_BUS",
+            "arn:aws:events:us-west-2:123456789012:event-bus/drive-deposits-staging",
+        ),
+        ("EVENT_PUBLISHER_SOURCE", "drive-deposits-staging"),
+        ("EVENT_PUBLISHER_DETAIL_TYPE_PREFIX", "staging-"),
+        ("EVENT_PUBLISHER_REGION", "us-west-2"),
+        (
+            "EVENT_PUBLISHER_REQUIRED_RULES",
+            "staging-bank-level, staging-portfolio-level",
+        ),
+        ("EVENT_PUBLISHER_DEPOSIT_LEVEL_EVENTS", "true"),
+    ]);
+
+    assert_eq!(config.source, "drive-deposits-staging");
+    assert_eq!(config.detail_type(BANK_LEVEL), "staging-bank-level");
+    assert_eq!(config.region.as_deref(), Some("us-west-2"));
+    assert_eq!(
+        config.required_rules,
+        vec!["staging-bank-level", "staging-portfolio-level"]
+    );
+    assert_eq!(config.endpoint_url, None);
+    assert!(config.deposit_level_events);
+    assert!(!EventPublisherConfig::default().deposit_level_events);
+}
+
+#[test]
+fn test_use_localstack_only_fills_a_missing_endpoint() {
+    let config = with_env(&[("USE_LOCALSTACK", "true")]);
+    assert_eq!(config.endpoint_url.as_deref(), Some(LOCALSTACK_ENDPOINT));
+
+    let config = with_env(&[
+        ("USE_LOCALSTACK", "true"),
+        ("EVENT_PUBLISHER_ENDPOINT_URL", "http://localhost:4566/"),
+    ]);
+    assert_eq!(
+        config.endpoint_url.as_deref(),
+        Some("http://localhost:4566/")
+    );
+}
+
+#[test]
+fn test_missing_rules_are_reported_by_name() {
+    let required = EventPublisherConfig::default().required_rules;
+    let rule_names = vec![
+        "drive-deposits-bank-level".to_string(),
+        "some-other-rule".to_string(),
+    ];
+
+    assert_eq!(
+        missing_rules(&required, &rule_names),
+        vec![
+            "drive-deposits-banks-level",
+            "drive-deposits-banks-level-for-lambda"
+        ]
+    );
+    assert!(missing_rules(&required, &required).is_empty());
+}
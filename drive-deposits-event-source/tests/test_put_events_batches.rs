@@ -0,0 +1,50 @@
+use pretty_assertions::assert_eq;
+
+use drive_deposits_event_source::eb::{
+    entry_size, is_retryable_entry_error, put_events_batches, PUT_EVENTS_MAX_BYTES,
+    PUT_EVENTS_MAX_ENTRIES,
+};
+use drive_deposits_event_source::publisher::BANK_LEVEL;
+
+#[test]
+fn test_put_events_batches_split_on_entry_count() {
+    let entry_sizes = vec![100; 2 * PUT_EVENTS_MAX_ENTRIES + 1];
+    assert_eq!(
+        put_events_batches(&entry_sizes),
+        vec![0..10, 10..20, 20..21]
+    );
+}
+
+#[test]
+fn test_put_events_batches_split_on_total_size() {
+    let third = PUT_EVENTS_MAX_BYTES / 3;
+    let entry_sizes = vec![third, third, third, third, 1];
+    assert_eq!(put_events_batches(&entry_sizes), vec![0..3, 3..5]);
+}
+
+#[test]
+fn test_put_events_batches_oversized_entry_goes_alone() {
+    let entry_sizes = vec![10, PUT_EVENTS_MAX_BYTES + 1, 10];
+    assert_eq!(put_events_batches(&entry_sizes), vec![0..1, 1..2, 2..3]);
+}
+
+#[test]
+fn test_put_events_batches_no_entries() {
+    assert!(put_events_batches(&[]).is_empty());
+}
+
+#[test]
+fn test_entry_size_counts_source_detail_type_and_detail_bytes() {
+    assert_eq!(
+        entry_size("drive-deposits", BANK_LEVEL, r#"{"name":"é"}"#),
+        "drive-deposits".len() + "bank-level".len() + 13
+    );
+}
+
+#[test]
+fn test_only_transient_entry_errors_are_retried() {
+    assert!(is_retryable_entry_error("ThrottlingException"));
+    assert!(is_retryable_entry_error("InternalFailure"));
+    assert!(!is_retryable_entry_error("MalformedDetail"));
+    assert!(!is_retryable_entry_error("AccessDeniedException"));
+}
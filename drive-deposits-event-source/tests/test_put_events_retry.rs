@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use aws_sdk_eventbridge::config::{BehaviorVersion, Credentials, Region, SharedHttpClient};
+use aws_sdk_eventbridge::{Client, Config};
+use aws_smithy_runtime::client::http::test_util::infallible_client_fn;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+use drive_deposits_event_source::eb::{
+    DriveDepositsEventBridge, DriveDepositsEventBridgeError, EntryOutcome,
+    PUT_EVENTS_REQUEST_FAILED,
+};
+use drive_deposits_event_source::publisher::EventPublisherError;
+use drive_deposits_event_source::publisher::{EventPublisher, BANK_LEVEL};
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+// the details of each PutEvents request, in the order they were sent
+type Requests = Arc<Mutex<Vec<Vec<String>>>>;
+
+fn event_bridge_with(http_client: SharedHttpClient) -> DriveDepositsEventBridge {
+    let config = Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+        .http_client(http_client)
+        .build();
+    DriveDepositsEventBridge::from_client(
+        Client::from_conf(config),
+        EventPublisherConfig::default(),
+    )
+}
+
+// An EventBridge answering every PutEvents itself: an entry whose detail is in failures fails
+// with that error code as long as its count lasts, every other entry is delivered.
+fn event_bridge(
+    failures: HashMap<String, (&'static str, u32)>,
+) -> (DriveDepositsEventBridge, Requests) {
+    let requests = Requests::default();
+    let sent = requests.clone();
+    let failures = Mutex::new(failures);
+    let http_client = infallible_client_fn(move |request| {
+        let body: Value = serde_json::from_slice(request.body().bytes().unwrap()).unwrap();
+        let details: Vec<String> = body["Entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["Detail"].as_str().unwrap().to_string())
+            .collect();
+        let mut failures = failures.lock().unwrap();
+        let entries: Vec<Value> = details
+            .iter()
+            .enumerate()
+            .map(|(index, detail)| match failures.get_mut(detail) {
+                Some((error_code, remaining)) if *remaining > 0 => {
+                    *remaining -= 1;
+                    json!({"ErrorCode": error_code, "ErrorMessage": "failed"})
+                }
+                _ => json!({"EventId": format!("event-{}-{}", sent.lock().unwrap().len(), index)}),
+            })
+            .collect();
+        let failed_entry_count = entries
+            .iter()
+            .filter(|entry| entry.get("ErrorCode").is_some())
+            .count();
+        sent.lock().unwrap().push(details);
+        http_02x::Response::builder()
+            .status(200)
+            .body(json!({"FailedEntryCount": failed_entry_count, "Entries": entries}).to_string())
+            .unwrap()
+    });
+    (event_bridge_with(http_client), requests)
+}
+
+// An EventBridge accepting the first PutEvents request and denying every later one as a whole
+fn event_bridge_denying_after_first_request() -> (DriveDepositsEventBridge, Requests) {
+    let requests = Requests::default();
+    let sent = requests.clone();
+    let http_client = infallible_client_fn(move |request| {
+        let body: Value = serde_json::from_slice(request.body().bytes().unwrap()).unwrap();
+        let entries = body["Entries"].as_array().unwrap();
+        let mut sent = sent.lock().unwrap();
+        sent.push(
+            entries
+                .iter()
+                .map(|entry| entry["Detail"].as_str().unwrap().to_string())
+                .collect(),
+        );
+        if sent.len() > 1 {
+            return http_02x::Response::builder()
+                .status(400)
+                .body(json!({"__type": "AccessDeniedException", "message": "denied"}).to_string())
+                .unwrap();
+        }
+        let entries: Vec<Value> = (0..entries.len())
+            .map(|index| json!({"EventId": format!("event-{}", index)}))
+            .collect();
+        http_02x::Response::builder()
+            .status(200)
+            .body(json!({"FailedEntryCount": 0, "Entries": entries}).to_string())
+            .unwrap()
+    });
+    (event_bridge_with(http_client), requests)
+}
+
+fn details(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn attempts(outcome: &EntryOutcome) -> u32 {
+    match outcome {
+        EntryOutcome::Delivered { attempts, .. } | EntryOutcome::Failed { attempts, .. } => {
+            *attempts
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_put_events_resends_only_failed_entries() {
+    let (bridge, requests) = event_bridge(HashMap::from([(
+        "b".to_string(),
+        ("ThrottlingException", 2),
+    )]));
+
+    let outcomes = bridge
+        .put_events(BANK_LEVEL, &details(&["a", "b", "c"]))
+        .await;
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![details(&["a", "b", "c"]), details(&["b"]), details(&["b"])]
+    );
+    assert!(outcomes
+        .iter()
+        .all(|outcome| matches!(outcome, EntryOutcome::Delivered { .. })));
+    assert_eq!(
+        outcomes.iter().map(attempts).collect::<Vec<_>>(),
+        vec![1, 3, 1]
+    );
+}
+
+#[tokio::test]
+async fn test_put_events_gives_up_after_retry_limit() {
+    let (bridge, requests) = event_bridge(HashMap::from([
+        ("b".to_string(), ("ThrottlingException", u32::MAX)),
+        ("c".to_string(), ("MalformedDetail", u32::MAX)),
+    ]));
+
+    let outcomes = bridge
+        .put_events(BANK_LEVEL, &details(&["a", "b", "c"]))
+        .await;
+
+    // the malformed entry is not sent again, the throttled one until the 4 attempts are used
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            details(&["a", "b", "c"]),
+            details(&["b"]),
+            details(&["b"]),
+            details(&["b"]),
+        ]
+    );
+    assert!(matches!(
+        outcomes[0],
+        EntryOutcome::Delivered { attempts: 1, .. }
+    ));
+    assert_eq!(
+        outcomes[1],
+        EntryOutcome::Failed {
+            error_code: "ThrottlingException".to_string(),
+            error_message: Some("failed".to_string()),
+            attempts: 4,
+        }
+    );
+    assert_eq!(
+        outcomes[2],
+        EntryOutcome::Failed {
+            error_code: "MalformedDetail".to_string(),
+            error_message: Some("failed".to_string()),
+            attempts: 1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_publish_batch_fails_with_the_failed_entries() {
+    let (bridge, _requests) = event_bridge(HashMap::from([(
+        "b".to_string(),
+        ("MalformedDetail", u32::MAX),
+    )]));
+
+    let err = bridge
+        .publish_batch(BANK_LEVEL, details(&["a", "b"]))
+        .await
+        .unwrap_err();
+
+    assert!(err
+        .to_string()
+        .contains("1 of 2 bank-level entries failed: entry 1 MalformedDetail"));
+}
+
+#[tokio::test]
+async fn test_put_events_keeps_outcomes_of_batches_sent_before_a_request_fails() {
+    let (bridge, requests) = event_bridge_denying_after_first_request();
+    let names: Vec<String> = (0..25).map(|index| format!("bank-{}", index)).collect();
+
+    let outcomes = bridge.put_events(BANK_LEVEL, &names).await;
+
+    // the third batch is not sent once the second one is denied
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    assert_eq!(outcomes.len(), 25);
+    assert!(outcomes[..10]
+        .iter()
+        .all(|outcome| matches!(outcome, EntryOutcome::Delivered { attempts: 1, .. })));
+    for (index, outcome) in outcomes.iter().enumerate().skip(10) {
+        let EntryOutcome::Failed {
+            error_code,
+            attempts,
+            ..
+        } = outcome
+        else {
+            panic!("entry {} was delivered", index);
+        };
+        assert_eq!(error_code, PUT_EVENTS_REQUEST_FAILED);
+        assert_eq!(*attempts, if index < 20 { 1 } else { 0 });
+    }
+}
+
+#[tokio::test]
+async fn test_publish_batch_reports_the_undelivered_entries() {
+    let (bridge, _requests) = event_bridge_denying_after_first_request();
+    let names: Vec<String> = (0..12).map(|index| format!("bank-{}", index)).collect();
+
+    let err = bridge.publish_batch(BANK_LEVEL, names).await.unwrap_err();
+
+    assert_eq!(err.undelivered_entries(), Some(vec![10, 11]));
+    let EventPublisherError::EventBridge(err) = err else {
+        panic!("expected an EventBridge error, got {:?}", err);
+    };
+    assert!(matches!(
+        *err,
+        DriveDepositsEventBridgeError::FailedEntries { .. }
+    ));
+}
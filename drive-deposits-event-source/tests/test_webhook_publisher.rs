@@ -0,0 +1,140 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+use drive_deposits_event_source::publisher::{
+    EventPublisher, EventPublisherError, WebhookPublisher, BANK_LEVEL, EVENT_SOURCE,
+};
+use drive_deposits_rest_types::server_config::EventPublisherConfig;
+
+// what the webhook received, as content type and body
+type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+// a webhook on a local port answering every POST to /events with status
+async fn webhook(status: StatusCode) -> (String, Received) {
+    let received = Received::default();
+    let app = Router::new()
+        .route(
+            "/events",
+            post(
+                move |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                    let content_type = headers
+                        .get("content-type")
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    received.lock().await.push((content_type, body));
+                    status
+                },
+            ),
+        )
+        .with_state(received.clone());
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, received)
+}
+
+#[tokio::test]
+async fn test_webhook_posts_event_envelope() {
+    let (url, received) = webhook(StatusCode::ACCEPTED).await;
+    let publisher = WebhookPublisher::new(url, EventPublisherConfig::default()).unwrap();
+
+    publisher
+        .publish(BANK_LEVEL, json!({"name": "VISION-BANK"}).to_string())
+        .await
+        .unwrap();
+
+    let received = received.lock().await;
+    assert_eq!(received.len(), 1);
+    let (content_type, body) = &received[0];
+    assert_eq!(content_type, "application/json");
+    assert_eq!(
+        serde_json::from_str::<Value>(body).unwrap(),
+        json!({
+            "source": EVENT_SOURCE,
+            "detail-type": BANK_LEVEL,
+            "detail": {"name": "VISION-BANK"},
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_webhook_envelope_uses_configured_source_and_prefix() {
+    let (url, received) = webhook(StatusCode::OK).await;
+    let config = EventPublisherConfig {
+        source: "drive-deposits-staging".to_string(),
+        detail_type_prefix: "staging-".to_string(),
+        ..EventPublisherConfig::default()
+    };
+    let publisher = WebhookPublisher::new(url, config).unwrap();
+
+    publisher
+        .publish(BANK_LEVEL, json!({"name": "VISION-BANK"}).to_string())
+        .await
+        .unwrap();
+
+    let received = received.lock().await;
+    let body: Value = serde_json::from_str(&received[0].1).unwrap();
+    assert_eq!(body["source"], "drive-deposits-staging");
+    assert_eq!(body["detail-type"], "staging-bank-level");
+}
+
+#[tokio::test]
+async fn test_webhook_error_status_fails_publish() {
+    let (url, received) = webhook(StatusCode::SERVICE_UNAVAILABLE).await;
+    let publisher = WebhookPublisher::new(url.clone(), EventPublisherConfig::default()).unwrap();
+
+    let err = publisher
+        .publish(BANK_LEVEL, json!({"name": "VISION-BANK"}).to_string())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        EventPublisherError::WebhookStatus { url: failed_url, status: 503 } if failed_url == url
+    ));
+    assert_eq!(received.lock().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_webhook_does_not_send_detail_that_is_not_json() {
+    let (url, received) = webhook(StatusCode::OK).await;
+    let publisher = WebhookPublisher::new(url, EventPublisherConfig::default()).unwrap();
+
+    let err = publisher
+        .publish(BANK_LEVEL, "not json".to_string())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, EventPublisherError::Json(_)));
+    assert!(received.lock().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_webhook_unreachable_fails_publish() {
+    // bound and dropped, so nothing listens on the port anymore
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let url = format!("http://{}/events", listener.local_addr().unwrap());
+    drop(listener);
+    let publisher = WebhookPublisher::new(url, EventPublisherConfig::default()).unwrap();
+
+    let err = publisher
+        .publish(BANK_LEVEL, json!({}).to_string())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, EventPublisherError::Webhook(_)));
+}
@@ -0,0 +1,53 @@
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+use tracing::{debug, info};
+
+use drive_deposits_rest_types::auth::{
+    Authenticator, TenantId, API_KEY_HEADER, AUTHORIZATION_HEADER,
+};
+
+// Authenticates every DriveDepositsService call from its metadata and puts the caller's TenantId
+// in the request extensions for the service methods
+#[derive(Debug, Clone)]
+pub struct AuthInterceptor {
+    authenticator: Authenticator,
+}
+
+impl AuthInterceptor {
+    pub fn new(authenticator: Authenticator) -> Self {
+        if authenticator.is_disabled() {
+            info!("authentication disabled, every caller is the default tenant");
+        }
+        Self { authenticator }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let metadata = request.metadata();
+        let authorization = metadata
+            .get(AUTHORIZATION_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let api_key = metadata
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let tenant_id = self
+            .authenticator
+            .authenticate(authorization, api_key)
+            .map_err(|err| Status::unauthenticated(err.to_string()))?;
+        debug!("grpc call authenticated for tenant {}", tenant_id);
+        request.extensions_mut().insert(tenant_id);
+        Ok(request)
+    }
+}
+
+// set by AuthInterceptor, or by the rest gateway when it calls the service in process
+// tonic::Status is large, but it is what the service methods return through `?`
+#[allow(clippy::result_large_err)]
+pub fn tenant_id<T>(request: &Request<T>) -> Result<TenantId, Status> {
+    request
+        .extensions()
+        .get::<TenantId>()
+        .cloned()
+        .ok_or_else(|| Status::unauthenticated("No authenticated tenant for the request"))
+}
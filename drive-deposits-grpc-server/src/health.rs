@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use tonic::server::NamedService;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::server::{health_reporter, HealthReporter};
+use tonic_health::ServingStatus;
+use tracing::{debug, info, warn};
+
+use drive_deposits_event_source::publisher::SharedEventPublisher;
+use drive_deposits_proto_grpc_types::generated::drive_deposits_service_server::DriveDepositsServiceServer;
+
+use crate::portfolio::DriveDepositsCalculator;
+
+const EVENT_PUBLISHER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+const DRIVE_DEPOSITS_SERVICE: &str =
+    <DriveDepositsServiceServer<DriveDepositsCalculator> as NamedService>::NAME;
+
+// grpc.health.v1 for the server as a whole ("") and for DriveDepositsService. Without events
+// there is nothing downstream so both are always SERVING; with events both follow the event
+// publisher's check, for EventBridge whether its rules can still be listed.
+pub async fn health_service(
+    event_publisher: Option<SharedEventPublisher>,
+) -> HealthServer<impl Health> {
+    health_service_checking_every(event_publisher, EVENT_PUBLISHER_CHECK_INTERVAL).await
+}
+
+async fn health_service_checking_every(
+    event_publisher: Option<SharedEventPublisher>,
+    check_interval: Duration,
+) -> HealthServer<impl Health> {
+    let (reporter, service) = health_reporter();
+    report(&reporter, ServingStatus::Serving).await;
+    if let Some(event_publisher) = event_publisher {
+        tokio::spawn(watch_event_publisher(
+            reporter,
+            event_publisher,
+            check_interval,
+        ));
+    }
+    service
+}
+
+async fn watch_event_publisher(
+    reporter: HealthReporter,
+    event_publisher: SharedEventPublisher,
+    check_interval: Duration,
+) {
+    let mut interval = tokio::time::interval(check_interval);
+    // create_publisher already checked at startup
+    interval.tick().await;
+    let mut last_status = ServingStatus::Serving;
+    loop {
+        interval.tick().await;
+        let status = match event_publisher.check().await {
+            Ok(()) => ServingStatus::Serving,
+            Err(err) => {
+                warn!("event publisher check for health failed: {}", err);
+                ServingStatus::NotServing
+            }
+        };
+        debug!("event publisher health status is {:?}", status);
+        // with an outbox the server stays SERVING while the sink is down, the backlog grows instead
+        if let Some(metrics) = event_publisher.outbox_metrics() {
+            info!(
+                backlog = metrics.backlog,
+                delivered = metrics.delivered,
+                retries = metrics.retries,
+                dead_lettered = metrics.dead_lettered,
+                "event outbox metrics"
+            );
+        }
+        if status != last_status {
+            info!(
+                "health status changed from {:?} to {:?}",
+                last_status, status
+            );
+            report(&reporter, status).await;
+            last_status = status;
+        }
+    }
+}
+
+async fn report(reporter: &HealthReporter, status: ServingStatus) {
+    let mut reporter = reporter.clone();
+    reporter.set_service_status("", status).await;
+    reporter
+        .set_service_status(DRIVE_DEPOSITS_SERVICE, status)
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use pretty_assertions::assert_eq;
+    use tokio::net::TcpListener;
+    use tokio::time::timeout;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tokio_stream::StreamExt;
+    use tonic::transport::{Channel, Endpoint, Server};
+    use tonic::Streaming;
+    use tonic_health::pb::health_check_response::ServingStatus as PbServingStatus;
+    use tonic_health::pb::health_client::HealthClient;
+    use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse};
+
+    use drive_deposits_event_source::publisher::{EventPublisher, EventPublisherError};
+
+    use super::*;
+
+    const CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+    // the sink is up while healthy is true
+    struct StubPublisher {
+        healthy: AtomicBool,
+    }
+
+    #[async_trait]
+    impl EventPublisher for StubPublisher {
+        async fn publish(
+            &self,
+            _detail_type: &str,
+            _detail: String,
+        ) -> Result<(), EventPublisherError> {
+            Ok(())
+        }
+
+        async fn check(&self) -> Result<(), EventPublisherError> {
+            if self.healthy.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(EventPublisherError::ChannelClosed)
+            }
+        }
+    }
+
+    async fn serve(health: HealthServer<impl Health>) -> HealthClient<Channel> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(health)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let channel = Endpoint::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        HealthClient::new(channel)
+    }
+
+    async fn check(client: &mut HealthClient<Channel>, service: &str) -> PbServingStatus {
+        let response = client
+            .check(HealthCheckRequest {
+                service: service.to_string(),
+            })
+            .await
+            .unwrap();
+        response.into_inner().status()
+    }
+
+    async fn next_status(statuses: &mut Streaming<HealthCheckResponse>) -> PbServingStatus {
+        timeout(Duration::from_secs(5), statuses.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_health_service_without_publisher_is_serving() {
+        let mut client = serve(health_service(None).await).await;
+
+        assert_eq!(check(&mut client, "").await, PbServingStatus::Serving);
+        assert_eq!(
+            check(&mut client, DRIVE_DEPOSITS_SERVICE).await,
+            PbServingStatus::Serving
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_service_follows_event_publisher_check() {
+        let publisher = Arc::new(StubPublisher {
+            healthy: AtomicBool::new(true),
+        });
+        let health = health_service_checking_every(Some(publisher.clone()), CHECK_INTERVAL).await;
+        let mut client = serve(health).await;
+        let mut statuses = client
+            .watch(HealthCheckRequest {
+                service: DRIVE_DEPOSITS_SERVICE.to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(next_status(&mut statuses).await, PbServingStatus::Serving);
+
+        publisher.healthy.store(false, Ordering::SeqCst);
+        assert_eq!(
+            next_status(&mut statuses).await,
+            PbServingStatus::NotServing
+        );
+        assert_eq!(check(&mut client, "").await, PbServingStatus::NotServing);
+
+        publisher.healthy.store(true, Ordering::SeqCst);
+        assert_eq!(next_status(&mut statuses).await, PbServingStatus::Serving);
+        assert_eq!(check(&mut client, "").await, PbServingStatus::Serving);
+    }
+}
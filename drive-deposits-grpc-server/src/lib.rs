@@ -1,2 +1,5 @@
+pub mod auth;
+pub mod health;
 pub mod portfolio;
 pub mod service_router;
+pub mod shutdown;
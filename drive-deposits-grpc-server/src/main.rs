@@ -1,9 +1,12 @@
 use std::error::Error;
 
+use tokio_util::task::TaskTracker;
 use tracing::{error, info, info_span, Instrument};
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
 
 use drive_deposits_grpc_server::service_router::app;
+use drive_deposits_grpc_server::shutdown::{run_until_shutdown, shutdown_on_signal};
+use drive_deposits_rest_types::server_config::ServerConfig;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -20,11 +23,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("RUST_LOG is {}", rust_log);
 
     let span = info_span!("main");
-    let app = app().instrument(span.clone()).await?;
-    let addr = "[::]:50052".parse().unwrap();
+    let config = ServerConfig::load()?.grpc_server;
+    let shutdown = shutdown_on_signal();
+    let calculations = TaskTracker::new();
+    let app = app(&config, calculations.clone())
+        .instrument(span.clone())
+        .await?;
+    let addr = config.bind_address;
 
     span.in_scope(|| info!("gRPC server running! on {}", addr));
-    let serve_result = app.serve(addr).instrument(info_span!("server")).await;
+    let serve = app.serve_with_shutdown(addr, shutdown.clone().cancelled_owned());
+    let serve_result =
+        run_until_shutdown(serve, &shutdown, &calculations, config.shutdown_timeout())
+            .instrument(info_span!("server"))
+            .await;
 
     serve_result.inspect_err(|e| {
         span.in_scope(|| error!("gRPC server error is {:?}", e));
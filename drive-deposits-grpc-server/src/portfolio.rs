@@ -1,17 +1,30 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::task::TaskTracker;
 use tonic::{async_trait, Request, Response, Status};
 use tracing::{debug, error, info_span};
 
-use drive_deposits_event_source::eb::DriveDepositsEventBridge;
+use drive_deposits_cal_types::math::engine::{Cancellation, EventOptions};
+use drive_deposits_event_source::publisher::SharedEventPublisher;
 use drive_deposits_proto_grpc_types::generated::{
     drive_deposits_service_server::DriveDepositsService, CalculatePortfolioRequest,
-    CalculatePortfolioResponse,
+    CalculatePortfolioResponse, CalculatePortfolioStreamResponse, CalculatePortfoliosRequest,
+    CalculatePortfoliosResponse,
 };
+use drive_deposits_rest_types::auth::CORRELATION_ID_HEADER;
+
+use crate::auth::tenant_id;
 
 mod calculate;
 mod grpc_status_handler;
 
 pub struct DriveDepositsCalculator {
-    pub drive_deposits_eb: Option<DriveDepositsEventBridge>,
+    pub event_publisher: Option<SharedEventPublisher>,
+    pub event_options: EventOptions,
+    // calculations and their event sends, drained on graceful shutdown
+    pub calculations: TaskTracker,
 }
 
 #[async_trait]
@@ -22,10 +35,175 @@ impl DriveDepositsService for DriveDepositsCalculator {
     ) -> Result<Response<CalculatePortfolioResponse>, Status> {
         info_span!("grpc_calculate_portfolio");
         debug!("calculate_portfolio request incoming is : {:#?}", request);
+        let tenant_id = tenant_id(&request)?;
+        let correlation_id = correlation_id(&request);
+        let cancellation = request_cancellation(&request);
+        // tonic drops this future when the client goes away or its deadline expires
+        let _cancel_on_drop = cancellation.token.clone().drop_guard();
         let delta_request = request.into_inner();
-        let response = calculate::by_period(delta_request, self.drive_deposits_eb.clone())
-            .await
-            .inspect_err(|err| error!("building response errors : {:?}", err))?;
+        let response = calculate::by_period(
+            delta_request,
+            tenant_id,
+            correlation_id,
+            self.event_publisher.clone(),
+            self.event_options.clone(),
+            &self.calculations,
+            cancellation,
+        )
+        .await
+        .inspect_err(|err| error!("building response errors : {:?}", err))?;
         Ok(Response::new(response))
     }
+
+    type CalculatePortfolioStreamStream =
+        ReceiverStream<Result<CalculatePortfolioStreamResponse, Status>>;
+
+    async fn calculate_portfolio_stream(
+        &self,
+        request: Request<CalculatePortfolioRequest>,
+    ) -> Result<Response<Self::CalculatePortfolioStreamStream>, Status> {
+        info_span!("grpc_calculate_portfolio_stream");
+        debug!(
+            "calculate_portfolio_stream request incoming is : {:#?}",
+            request
+        );
+        let tenant_id = tenant_id(&request)?;
+        let correlation_id = correlation_id(&request);
+        let cancellation = request_cancellation(&request);
+        let delta_request = request.into_inner();
+        let receiver = calculate::by_period_stream(
+            delta_request,
+            tenant_id,
+            correlation_id,
+            self.event_publisher.clone(),
+            self.event_options.clone(),
+            &self.calculations,
+            cancellation,
+        )
+        .await
+        .inspect_err(|err| error!("building stream response errors : {:?}", err))?;
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+    async fn calculate_portfolios(
+        &self,
+        request: Request<CalculatePortfoliosRequest>,
+    ) -> Result<Response<CalculatePortfoliosResponse>, Status> {
+        info_span!("grpc_calculate_portfolios");
+        debug!("calculate_portfolios request incoming is : {:#?}", request);
+        let tenant_id = tenant_id(&request)?;
+        let correlation_id = correlation_id(&request);
+        let cancellation = request_cancellation(&request);
+        let _cancel_on_drop = cancellation.token.clone().drop_guard();
+        let batch_request = request.into_inner();
+        let response = calculate::by_period_batch(
+            batch_request,
+            tenant_id,
+            correlation_id,
+            self.event_publisher.clone(),
+            self.event_options.clone(),
+            &self.calculations,
+            cancellation,
+        )
+        .await
+        .inspect_err(|err| error!("building batch response errors : {:?}", err))?;
+        Ok(Response::new(response))
+    }
+}
+
+// the caller's correlation id, if any, goes into the events of the calculation
+fn correlation_id<T>(request: &Request<T>) -> Option<String> {
+    request
+        .metadata()
+        .get(CORRELATION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|correlation_id| !correlation_id.is_empty())
+        .map(str::to_string)
+}
+
+// the client's grpc-timeout header, if any, becomes the calculation deadline
+fn request_cancellation<T>(request: &Request<T>) -> Cancellation {
+    let deadline = request
+        .metadata()
+        .get("grpc-timeout")
+        .and_then(|value| value.to_str().ok())
+        .and_then(grpc_timeout)
+        .map(|timeout| Instant::now() + timeout);
+    Cancellation::new(deadline)
+}
+
+// an integer of at most 8 digits followed by a unit, per the gRPC over HTTP/2 spec
+fn grpc_timeout(value: &str) -> Option<Duration> {
+    let (amount, unit) = value.split_at(value.len().checked_sub(1)?);
+    if amount.is_empty() || amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tonic::metadata::MetadataValue;
+
+    use super::*;
+
+    #[test]
+    fn test_grpc_timeout_units() {
+        assert_eq!(grpc_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(grpc_timeout("2M"), Some(Duration::from_secs(120)));
+        assert_eq!(grpc_timeout("3S"), Some(Duration::from_secs(3)));
+        assert_eq!(grpc_timeout("2999m"), Some(Duration::from_millis(2999)));
+        assert_eq!(grpc_timeout("10u"), Some(Duration::from_micros(10)));
+        assert_eq!(grpc_timeout("5n"), Some(Duration::from_nanos(5)));
+        assert_eq!(
+            grpc_timeout("99999999S"),
+            Some(Duration::from_secs(99_999_999))
+        );
+    }
+
+    #[test]
+    fn test_grpc_timeout_rejects_invalid_values() {
+        for value in [
+            "",
+            "S",
+            "3",
+            "3s",
+            "3 S",
+            "-3S",
+            "+3S",
+            "1.5S",
+            "123456789S",
+        ] {
+            assert_eq!(grpc_timeout(value), None, "{:?}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_cancellation_deadline_from_grpc_timeout() {
+        let mut request = Request::new(());
+        assert_eq!(request_cancellation(&request).deadline, None);
+
+        request
+            .metadata_mut()
+            .insert("grpc-timeout", MetadataValue::from_static("3S"));
+        let before = Instant::now();
+        let deadline = request_cancellation(&request).deadline.unwrap();
+        assert!(deadline >= before + Duration::from_secs(3));
+        assert!(deadline <= Instant::now() + Duration::from_secs(3));
+
+        request
+            .metadata_mut()
+            .insert("grpc-timeout", MetadataValue::from_static("soon"));
+        assert_eq!(request_cancellation(&request).deadline, None);
+    }
 }
@@ -1,33 +1,62 @@
+use std::sync::Arc;
+
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::task::TaskTracker;
 use tonic::Status;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, Instrument};
 
 use drive_deposits_cal_types::cal_types::PortfolioRequest as CalBankRequest;
-use drive_deposits_event_source::eb::DriveDepositsEventBridge;
+use drive_deposits_event_source::publisher::SharedEventPublisher;
 use drive_deposits_proto_grpc_types::generated::{
+    calculate_portfolio_stream_response::Result as StreamResult,
+    correlated_portfolio_response::Result as CorrelatedResult,
     CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
     CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
+    CalculatePortfolioStreamResponse as GrpcCalculatePortfolioStreamResponse,
+    CalculatePortfoliosRequest as GrpcCalculatePortfoliosRequest,
+    CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+    CorrelatedPortfolioResponse as GrpcCorrelatedPortfolioResponse,
 };
+use drive_deposits_rest_types::auth::TenantId;
 
-use crate::portfolio::grpc_status_handler::CalculationHaltErrorWrapper;
-use drive_deposits_cal_types::math::engine::calculate_portfolio;
+use crate::portfolio::grpc_status_handler::{
+    CalculationHaltErrorWrapper, RequestConversionErrorWrapper,
+};
+use drive_deposits_cal_types::math::engine::{
+    calculate_portfolio, calculate_portfolio_streaming, CalculationHaltError, Cancellation,
+    EventOptions,
+};
 
 use super::grpc_status_handler;
 
+const STREAM_CHANNEL_CAPACITY: usize = 16;
+// same upper limit as the REST gateway batch request
+const MAX_BATCH_PORTFOLIOS: usize = 100;
+// portfolios of a batch calculated at the same time; each one already calculates its banks
+// concurrently, so this stays small
+const BATCH_CONCURRENCY: usize = 4;
+
 pub async fn by_period(
     delta_request: GrpcCalculatePortfolioRequest,
-    eb: Option<DriveDepositsEventBridge>,
+    tenant_id: TenantId,
+    correlation_id: Option<String>,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    calculations: &TaskTracker,
+    cancellation: Cancellation,
 ) -> Result<GrpcCalculatePortfolioResponse, Status> {
-    info!("new_banks incoming is : {:?}", delta_request.new_banks);
-    grpc_status_handler::bad_request_errors(&delta_request.new_banks)
-        .inspect_err(|err| error!("new_banks checking at the grpc level errors : {:?}", err))?;
-
-    // convert grpc CalculatePortfolioRequest to calculator CalculatePortfolioRequest
-    let cal_req: CalBankRequest = delta_request.into();
-    debug!("Converted from grpc to cal: {:?}", cal_req);
+    let cal_req = to_cal_request(delta_request, tenant_id, correlation_id)?;
 
     // process calculation for calculator CalculatePortfolioRequest
-    let cal_resp = calculate_portfolio(cal_req, eb)
+    // tracked task so shutdown waits for its events; a dropped request cancels it instead
+    let cal_resp = calculations
+        .spawn(
+            calculate_portfolio(cal_req, publisher, event_options, cancellation).in_current_span(),
+        )
         .await
+        .map_err(|err| CalculationHaltErrorWrapper(CalculationHaltError::Join(err)))?
         .map_err(CalculationHaltErrorWrapper)?;
     debug!("calculated response: {:?}", cal_resp);
 
@@ -38,6 +67,208 @@ pub async fn by_period(
     Ok(grpc_resp)
 }
 
+// Request errors are returned before the stream starts; once it has started each calculated bank
+// is sent as it completes, followed by the portfolio summary or a final error status. The client
+// dropping the stream cancels the calculation.
+pub async fn by_period_stream(
+    delta_request: GrpcCalculatePortfolioRequest,
+    tenant_id: TenantId,
+    correlation_id: Option<String>,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    calculations: &TaskTracker,
+    cancellation: Cancellation,
+) -> Result<Receiver<Result<GrpcCalculatePortfolioStreamResponse, Status>>, Status> {
+    let cal_req = to_cal_request(delta_request, tenant_id, correlation_id)?;
+
+    let (stream_sender, stream_receiver) = channel(STREAM_CHANNEL_CAPACITY);
+    calculations.spawn(
+        async move {
+            let (bank_sender, mut bank_receiver) = channel(STREAM_CHANNEL_CAPACITY);
+            let calculation = tokio::spawn(
+                calculate_portfolio_streaming(
+                    cal_req,
+                    publisher,
+                    event_options,
+                    bank_sender,
+                    cancellation.clone(),
+                )
+                .in_current_span(),
+            );
+
+            loop {
+                let cal_bank = tokio::select! {
+                    cal_bank = bank_receiver.recv() => cal_bank,
+                    _ = stream_sender.closed() => None,
+                };
+                let Some(cal_bank) = cal_bank else { break };
+                let bank_message = GrpcCalculatePortfolioStreamResponse {
+                    result: Some(StreamResult::Bank(cal_bank.into())),
+                };
+                if stream_sender.send(Ok(bank_message)).await.is_err() {
+                    break;
+                }
+            }
+            if stream_sender.is_closed() {
+                info!("client dropped the stream; cancelling the calculation");
+                cancellation.token.cancel();
+            }
+            drop(bank_receiver);
+
+            let last_message = match calculation.await {
+                Ok(Ok(cal_resp)) => {
+                    debug!("calculated streaming response: {:?}", cal_resp);
+                    Ok(GrpcCalculatePortfolioStreamResponse {
+                        result: Some(StreamResult::PortfolioSummary(cal_resp.into())),
+                    })
+                }
+                Ok(Err(err)) => {
+                    error!("streaming calculation halted: {:?}", err);
+                    Err(Status::from(CalculationHaltErrorWrapper(err)))
+                }
+                Err(err) => {
+                    error!("streaming calculation task failed: {:?}", err);
+                    Err(Status::from(CalculationHaltErrorWrapper(
+                        CalculationHaltError::Join(err),
+                    )))
+                }
+            };
+            if stream_sender.send(last_message).await.is_err() {
+                info!("client dropped the stream before the portfolio summary");
+            }
+        }
+        .in_current_span(),
+    );
+
+    Ok(stream_receiver)
+}
+
+// Each portfolio goes through by_period on its own, so its result is exactly what
+// CalculatePortfolio would have returned; a failed portfolio becomes the error of its result.
+pub async fn by_period_batch(
+    batch_request: GrpcCalculatePortfoliosRequest,
+    tenant_id: TenantId,
+    correlation_id: Option<String>,
+    publisher: Option<SharedEventPublisher>,
+    event_options: EventOptions,
+    calculations: &TaskTracker,
+    cancellation: Cancellation,
+) -> Result<GrpcCalculatePortfoliosResponse, Status> {
+    grpc_status_handler::batch_bad_request_errors(
+        &batch_request.portfolio_requests,
+        MAX_BATCH_PORTFOLIOS,
+    )
+    .inspect_err(|err| {
+        error!(
+            "portfolio_requests checking at the grpc level errors : {:?}",
+            err
+        )
+    })?;
+
+    let semaphore = Arc::new(Semaphore::new(BATCH_CONCURRENCY));
+    let mut portfolio_calculations = JoinSet::new();
+    let mut results = Vec::with_capacity(batch_request.portfolio_requests.len());
+    for (index, correlated_request) in batch_request.portfolio_requests.into_iter().enumerate() {
+        // replaced below once the calculation task reports back
+        results.push((
+            correlated_request.correlation_id.clone(),
+            Err(Status::internal(
+                "Portfolio calculation task could not proceed",
+            )),
+        ));
+        let semaphore = semaphore.clone();
+        let publisher = publisher.clone();
+        let event_options = event_options.clone();
+        let tenant_id = tenant_id.clone();
+        // the portfolio's own correlation id, so its events match the client's batch entry; the
+        // request's is only a fallback, since every portfolio of the batch would share it
+        let correlation_id = Some(correlated_request.correlation_id.clone())
+            .filter(|correlation_id| !correlation_id.is_empty())
+            .or_else(|| correlation_id.clone());
+        let calculations = calculations.clone();
+        let cancellation = cancellation.clone();
+        portfolio_calculations.spawn(
+            async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .map_err(|_| Status::internal("Batch calculation was stopped"))?;
+                // a missing portfolio_request fails the same way as one without banks
+                let delta_request = correlated_request.portfolio_request.unwrap_or_default();
+                Ok::<_, Status>((
+                    index,
+                    by_period(
+                        delta_request,
+                        tenant_id,
+                        correlation_id,
+                        publisher,
+                        event_options,
+                        &calculations,
+                        cancellation,
+                    )
+                    .await,
+                ))
+            }
+            .in_current_span(),
+        );
+    }
+
+    while let Some(calculation) = portfolio_calculations.join_next().await {
+        match calculation {
+            Ok(Ok((index, result))) => results[index].1 = result,
+            Ok(Err(err)) => error!("batch portfolio calculation stopped: {:?}", err),
+            Err(err) => error!("batch portfolio calculation task failed: {:?}", err),
+        }
+    }
+
+    let mut batch_response = GrpcCalculatePortfoliosResponse::default();
+    for (correlation_id, result) in results {
+        let result = match result {
+            Ok(grpc_resp) => {
+                batch_response.succeeded += 1;
+                CorrelatedResult::PortfolioResponse(grpc_resp)
+            }
+            Err(status) => {
+                info!("portfolio {} failed in batch: {:?}", correlation_id, status);
+                batch_response.failed += 1;
+                CorrelatedResult::Error(grpc_status_handler::portfolio_error(&status))
+            }
+        };
+        batch_response
+            .portfolio_responses
+            .push(GrpcCorrelatedPortfolioResponse {
+                correlation_id,
+                result: Some(result),
+            });
+    }
+    debug!("grpc batch response: {:?}", batch_response);
+
+    Ok(batch_response)
+}
+
+// tonic::Status is large, but it is what the service methods return through `?`
+#[allow(clippy::result_large_err)]
+fn to_cal_request(
+    delta_request: GrpcCalculatePortfolioRequest,
+    tenant_id: TenantId,
+    correlation_id: Option<String>,
+) -> Result<CalBankRequest, Status> {
+    info!("new_banks incoming is : {:?}", delta_request.new_banks);
+    grpc_status_handler::bad_request_errors(&delta_request.new_banks)
+        .inspect_err(|err| error!("new_banks checking at the grpc level errors : {:?}", err))?;
+
+    // convert grpc CalculatePortfolioRequest to calculator CalculatePortfolioRequest
+    // collecting every field violation so the client gets all of them back in BadRequest details
+    let mut cal_req: CalBankRequest = delta_request
+        .try_into()
+        .inspect_err(|err| error!("grpc to cal conversion errors : {:?}", err))
+        .map_err(RequestConversionErrorWrapper)?;
+    cal_req.tenant_id = tenant_id;
+    cal_req.correlation_id = correlation_id;
+    debug!("Converted from grpc to cal: {:?}", cal_req);
+    Ok(cal_req)
+}
+
 // use drive_deposits_proto_grpc_types::generated::{
 //     Bank, CalculatePortfolioRequest, CalculatePortfolioResponse, Delta, Deposit,
 //     Maturity, NewBank, Outcome,
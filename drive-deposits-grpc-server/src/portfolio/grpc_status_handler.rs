@@ -1,9 +1,20 @@
+use std::collections::{HashMap, HashSet};
+
 use tonic::{Code, Status};
-use tonic_types::{BadRequest, Help, LocalizedMessage, StatusExt};
+use tonic_types::{BadRequest, ErrorInfo, Help, LocalizedMessage, StatusExt};
+
+use drive_deposits_cal_types::convert::from_grpc_cal_request::RequestConversionError;
+use drive_deposits_cal_types::math::engine::{CalculationHaltError, CancelReason};
+use drive_deposits_proto_grpc_types::generated::{
+    CorrelatedPortfolioRequest, NewBank, PortfolioError, PortfolioViolation,
+};
 
-use drive_deposits_cal_types::math::engine::CalculationHaltError;
-use drive_deposits_proto_grpc_types::generated::NewBank;
+// BadRequest field violations have no code, so the code of each field goes in ErrorInfo metadata
+const VIOLATION_CODES_REASON: &str = "FIELD_VIOLATION_CODES";
+const VIOLATION_CODES_DOMAIN: &str = "drinnovations.us";
 
+// tonic::Status is large, but it is what the service methods return through `?`
+#[allow(clippy::result_large_err)]
 pub fn bad_request_errors(new_banks: &[NewBank]) -> Result<(), Status> {
     let mut bad_request = BadRequest::new(vec![]);
     if new_banks.is_empty() {
@@ -16,18 +27,123 @@ pub fn bad_request_errors(new_banks: &[NewBank]) -> Result<(), Status> {
     }
 
     if !bad_request.is_empty() {
-        let help = Help::with_link("check your banks list", "https://drinnovations.us");
-        let localized_message = LocalizedMessage::new("en-US", "overall validate your banks list");
-        let status = Status::with_error_details_vec(
-            Code::InvalidArgument,
-            "request contains invalid arguments",
-            vec![bad_request.into(), help.into(), localized_message.into()],
+        let field_codes = HashMap::from([("new_banks".to_string(), "length".to_string())]);
+        return Err(invalid_argument_status(bad_request, field_codes));
+    }
+    Ok(())
+}
+
+// tonic::Status is large, but it is what the service methods return through `?`
+#[allow(clippy::result_large_err)]
+pub fn batch_bad_request_errors(
+    portfolio_requests: &[CorrelatedPortfolioRequest],
+    max_portfolios: usize,
+) -> Result<(), Status> {
+    let mut bad_request = BadRequest::new(vec![]);
+    let mut field_codes = HashMap::new();
+    if portfolio_requests.is_empty() {
+        bad_request.add_violation("portfolio_requests", "portfolio_requests cannot be empty");
+        field_codes.insert("portfolio_requests".to_string(), "length".to_string());
+    } else if portfolio_requests.len() > max_portfolios {
+        bad_request.add_violation(
+            "portfolio_requests",
+            format!(
+                "too many portfolios provided; must be at most upper limit of {}",
+                max_portfolios
+            ),
         );
-        return Err(status);
+        field_codes.insert("portfolio_requests".to_string(), "length".to_string());
+    }
+
+    let mut correlation_ids = HashSet::new();
+    for (index, portfolio_request) in portfolio_requests.iter().enumerate() {
+        let field = format!("portfolio_requests[{}].correlation_id", index);
+        if portfolio_request.correlation_id.is_empty() {
+            bad_request.add_violation(field.clone(), "correlation_id cannot be empty");
+            field_codes.insert(field, "length".to_string());
+        } else if !correlation_ids.insert(portfolio_request.correlation_id.as_str()) {
+            bad_request.add_violation(
+                field.clone(),
+                format!(
+                    "duplicate correlation_id {}",
+                    portfolio_request.correlation_id
+                ),
+            );
+            field_codes.insert(field, "duplicate_correlation_id".to_string());
+        }
+    }
+
+    if !bad_request.is_empty() {
+        return Err(invalid_argument_status(bad_request, field_codes));
     }
     Ok(())
 }
 
+// the status one portfolio of a batch failed with, kept as its result so the batch carries on
+pub fn portfolio_error(status: &Status) -> PortfolioError {
+    let field_codes = status
+        .get_details_error_info()
+        .map(|error_info| error_info.metadata)
+        .unwrap_or_default();
+    let violations = status
+        .get_details_bad_request()
+        .map(|bad_request| {
+            bad_request
+                .field_violations
+                .into_iter()
+                .map(|violation| PortfolioViolation {
+                    code: field_codes
+                        .get(&violation.field)
+                        .cloned()
+                        .unwrap_or_else(|| "invalid_argument".to_string()),
+                    field: violation.field,
+                    description: violation.description,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    PortfolioError {
+        code: status.code() as i32,
+        message: status.message().to_string(),
+        violations,
+    }
+}
+
+fn invalid_argument_status(
+    bad_request: BadRequest,
+    field_codes: HashMap<String, String>,
+) -> Status {
+    let error_info = ErrorInfo::new(VIOLATION_CODES_REASON, VIOLATION_CODES_DOMAIN, field_codes);
+    let help = Help::with_link("check your banks list", "https://drinnovations.us");
+    let localized_message = LocalizedMessage::new("en-US", "overall validate your banks list");
+    Status::with_error_details_vec(
+        Code::InvalidArgument,
+        "request contains invalid arguments",
+        vec![
+            bad_request.into(),
+            error_info.into(),
+            help.into(),
+            localized_message.into(),
+        ],
+    )
+}
+
+pub struct RequestConversionErrorWrapper(pub RequestConversionError);
+impl From<RequestConversionErrorWrapper> for Status {
+    fn from(wrapper: RequestConversionErrorWrapper) -> Self {
+        let mut bad_request = BadRequest::new(vec![]);
+        let mut field_codes = HashMap::new();
+        for violation in wrapper.0.violations {
+            // a field with several violations keeps the code of the first one
+            field_codes
+                .entry(violation.field.clone())
+                .or_insert(violation.code);
+            bad_request.add_violation(violation.field, violation.description);
+        }
+        invalid_argument_status(bad_request, field_codes)
+    }
+}
+
 pub struct CalculationHaltErrorWrapper(pub CalculationHaltError);
 impl From<CalculationHaltErrorWrapper> for Status {
     fn from(wrapper: CalculationHaltErrorWrapper) -> Self {
@@ -39,18 +155,24 @@ impl From<CalculationHaltErrorWrapper> for Status {
                 "Join error all calculations could not proceed: {}",
                 e
             )),
-            CalculationHaltError::DriveDepositsEventBridgeError(e) => {
+            CalculationHaltError::EventPublisherError(e) => {
                 Status::internal(format!(
-                    "Drive Deposits SEND_CAL_EVENTS is true but could not send events for processing as desired: {}",
+                    "Drive Deposits EVENT_PUBLISHER is set but could not publish events for processing as desired: {}",
                     e
                 ))
             }
             CalculationHaltError::EventSourceJsonSerializationError(e) => {
                 Status::internal(format!(
-                    "Drive Deposits SEND_CAL_EVENTS is true but could not serialize events for sending as desired: {}",
+                    "Drive Deposits EVENT_PUBLISHER is set but could not serialize events for sending as desired: {}",
                     e
                 ))
             }
+            CalculationHaltError::Cancelled(CancelReason::DeadlineExceeded) => {
+                Status::deadline_exceeded("Deadline exceeded before the calculation completed")
+            }
+            CalculationHaltError::Cancelled(CancelReason::Cancelled) => {
+                Status::cancelled("Calculation cancelled by the caller")
+            }
         }
     }
 }
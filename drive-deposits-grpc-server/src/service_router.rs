@@ -1,29 +1,201 @@
-use tonic::{transport::server::Router, transport::Server};
+use tokio_util::task::TaskTracker;
+use tonic::transport::{server::Router, Certificate, Identity, Server, ServerTlsConfig};
 use tonic_reflection::server::Builder;
-use tracing::{info, info_span, instrument};
+use tracing::{info, info_span, instrument, warn};
 
-use drive_deposits_event_source::eb::create_eb;
+use drive_deposits_cal_types::math::engine::EventOptions;
+use drive_deposits_event_source::publisher::create_publisher;
 use drive_deposits_proto_grpc_types::generated::{
     drive_deposits_service_server::DriveDepositsServiceServer, FILE_DESCRIPTOR_SET,
 };
+use drive_deposits_rest_types::auth::Authenticator;
+use drive_deposits_rest_types::server_config::{read_pem, EventPublisherConfig, GrpcServerConfig};
 
+use crate::auth::AuthInterceptor;
+use crate::health::health_service;
 use crate::portfolio::DriveDepositsCalculator;
 
-#[instrument]
-pub async fn app() -> Result<Router, Box<dyn std::error::Error>> {
+#[instrument(skip(calculations))]
+pub async fn app(
+    config: &GrpcServerConfig,
+    calculations: TaskTracker,
+) -> Result<Router, Box<dyn std::error::Error>> {
     let server_reflection = Builder::configure()
         .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
         .build_v1alpha()?;
     info!("reflection built using v1alpha since Postman gRPC still uses it as of tonic 0.12.2+");
 
-    let eb = create_eb().await?;
+    // reflection and health stay open; every DriveDepositsService call needs credentials
+    let auth_interceptor = AuthInterceptor::new(Authenticator::from_env()?);
+    let event_publisher = create_publisher().await?;
+    let health = health_service(event_publisher.clone()).await;
     let delta = DriveDepositsCalculator {
-        drive_deposits_eb: eb,
+        event_publisher,
+        event_options: EventOptions::from(&EventPublisherConfig::load()?),
+        calculations,
     };
 
-    let builder = Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls_config) = server_tls_config(config)? {
+        server = server.tls_config(tls_config)?;
+    }
+    let builder = server
         .trace_fn(|_| info_span!("drivedeposits_server"))
         .add_service(server_reflection)
-        .add_service(DriveDepositsServiceServer::new(delta));
+        .add_service(health)
+        .add_service(DriveDepositsServiceServer::with_interceptor(
+            delta,
+            auth_interceptor,
+        ));
     Ok(builder)
 }
+
+fn server_tls_config(
+    config: &GrpcServerConfig,
+) -> Result<Option<ServerTlsConfig>, Box<dyn std::error::Error>> {
+    let Some(tls) = config.tls()? else {
+        if config.client_ca_path.is_some() {
+            warn!("client_ca_path is ignored without tls_cert_path and tls_key_path");
+        }
+        info!("grpc server is plaintext");
+        return Ok(None);
+    };
+    let identity = Identity::from_pem(read_pem(&tls.cert_path)?, read_pem(&tls.key_path)?);
+    let mut tls_config = ServerTlsConfig::new().identity(identity);
+    match &config.client_ca_path {
+        Some(client_ca_path) => {
+            info!(
+                "grpc server is TLS, requiring client certificates signed by {:?}",
+                client_ca_path
+            );
+            tls_config =
+                tls_config.client_ca_root(Certificate::from_pem(read_pem(client_ca_path)?));
+        }
+        None => info!("grpc server is TLS"),
+    }
+    Ok(Some(tls_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::net::SocketAddr;
+    use std::path::{Path, PathBuf};
+
+    use pretty_assertions::assert_eq;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{ClientTlsConfig, Endpoint};
+    use tonic_health::pb::health_check_response::ServingStatus;
+    use tonic_health::pb::health_client::HealthClient;
+    use tonic_health::pb::HealthCheckRequest;
+
+    use crate::health::health_service;
+
+    use super::*;
+
+    // a CA, and a server and a client certificate signed by it, as PEM files in dir
+    fn write_certificates(dir: &Path) {
+        create_dir_all(dir).unwrap();
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        write(dir.join("ca.pem"), ca.pem()).unwrap();
+        for name in ["server", "client"] {
+            let key = KeyPair::generate().unwrap();
+            let certificate = CertificateParams::new(vec!["localhost".to_string()])
+                .unwrap()
+                .signed_by(&key, &ca, &ca_key)
+                .unwrap();
+            write(dir.join(format!("{}.pem", name)), certificate.pem()).unwrap();
+            write(dir.join(format!("{}.key", name)), key.serialize_pem()).unwrap();
+        }
+    }
+
+    fn tls_config(name: &str, client_ca: bool) -> (GrpcServerConfig, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("drive-deposits-grpc-tls-{}", name));
+        write_certificates(&dir);
+        let config = GrpcServerConfig {
+            tls_cert_path: Some(dir.join("server.pem")),
+            tls_key_path: Some(dir.join("server.key")),
+            client_ca_path: client_ca.then(|| dir.join("ca.pem")),
+            ..Default::default()
+        };
+        (config, dir)
+    }
+
+    async fn serve(config: &GrpcServerConfig) -> SocketAddr {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        let tls_config = server_tls_config(config).unwrap().unwrap();
+        let router = Server::builder()
+            .tls_config(tls_config)
+            .unwrap()
+            .add_service(health_service(None).await);
+        tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+        address
+    }
+
+    // with TLS 1.3 a missing client certificate only fails the first call, not the connect
+    async fn check(
+        address: SocketAddr,
+        dir: &Path,
+        client_identity: bool,
+    ) -> Result<ServingStatus, Box<dyn std::error::Error>> {
+        let mut client_tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(read_pem(&dir.join("ca.pem"))?))
+            .domain_name("localhost");
+        if client_identity {
+            client_tls = client_tls.identity(Identity::from_pem(
+                read_pem(&dir.join("client.pem"))?,
+                read_pem(&dir.join("client.key"))?,
+            ));
+        }
+        let channel = Endpoint::from_shared(format!("https://{}", address))?
+            .tls_config(client_tls)?
+            .connect()
+            .await?;
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await?;
+        Ok(response.into_inner().status())
+    }
+
+    #[test]
+    fn test_server_tls_config_is_plaintext_without_certificate() {
+        let config = GrpcServerConfig {
+            client_ca_path: Some(PathBuf::from("certs/ca.pem")),
+            ..Default::default()
+        };
+        assert!(server_tls_config(&config).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_server_serves_tls() {
+        let (config, dir) = tls_config("tls", false);
+        let address = serve(&config).await;
+
+        assert_eq!(
+            check(address, &dir, false).await.unwrap(),
+            ServingStatus::Serving
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_serves_mtls_only_to_clients_with_certificates() {
+        let (config, dir) = tls_config("mtls", true);
+        let address = serve(&config).await;
+
+        assert_eq!(
+            check(address, &dir, true).await.unwrap(),
+            ServingStatus::Serving
+        );
+        assert!(check(address, &dir, false).await.is_err());
+    }
+}
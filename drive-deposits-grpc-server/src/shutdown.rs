@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::signal;
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
+
+// ctrl-c locally, SIGTERM from docker and kubernetes
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            error!("failed to listen for ctrl-c: {:?}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                error!("failed to listen for SIGTERM: {:?}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("ctrl-c received, shutting down"),
+        _ = terminate => info!("SIGTERM received, shutting down"),
+    }
+}
+
+pub fn shutdown_on_signal() -> CancellationToken {
+    let shutdown = CancellationToken::new();
+    let cancel = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        cancel.cancel();
+    });
+    shutdown
+}
+
+// serve must stop accepting once shutdown is cancelled; from then on in-flight requests and the
+// tracked calculations share one deadline, and whatever is still running at it is abandoned
+pub async fn run_until_shutdown<E>(
+    serve: impl Future<Output = Result<(), E>>,
+    shutdown: &CancellationToken,
+    calculations: &TaskTracker,
+    timeout: Duration,
+) -> Result<(), E> {
+    tokio::pin!(serve);
+    let served = tokio::select! {
+        served = &mut serve => served,
+        _ = shutdown.cancelled() => {
+            info!("draining in-flight requests and calculations for up to {:?}", timeout);
+            let deadline = Instant::now() + timeout;
+            let served = match timeout_at(deadline, &mut serve).await {
+                Ok(served) => served,
+                Err(_) => {
+                    warn!("shutdown timeout reached; abandoning in-flight requests");
+                    Ok(())
+                }
+            };
+            drain_calculations(calculations, deadline).await;
+            return served;
+        }
+    };
+    // stopped on its own, calculations still get the full timeout
+    drain_calculations(calculations, Instant::now() + timeout).await;
+    served
+}
+
+async fn drain_calculations(calculations: &TaskTracker, deadline: Instant) {
+    calculations.close();
+    match timeout_at(deadline, calculations.wait()).await {
+        Ok(()) => info!("all in-flight calculations and their events finished"),
+        Err(_) => error!(
+            "shutdown timeout reached; abandoning {} in-flight calculations and their events",
+            calculations.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use tokio::time::sleep;
+    use tokio_util::sync::CancellationToken;
+    use tokio_util::task::TaskTracker;
+
+    use super::run_until_shutdown;
+
+    #[tokio::test]
+    async fn test_run_until_shutdown_drains_tracked_calculations() {
+        let shutdown = CancellationToken::new();
+        let calculations = TaskTracker::new();
+        let events_sent = Arc::new(AtomicBool::new(false));
+        let sent = events_sent.clone();
+        calculations.spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            sent.store(true, Ordering::SeqCst);
+        });
+        let serve = shutdown.clone().cancelled_owned();
+        shutdown.cancel();
+
+        let served = run_until_shutdown(
+            async move {
+                serve.await;
+                Ok::<_, ()>(())
+            },
+            &shutdown,
+            &calculations,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert_eq!(served, Ok(()));
+        assert!(events_sent.load(Ordering::SeqCst));
+        assert!(calculations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_until_shutdown_abandons_calculations_after_timeout() {
+        let shutdown = CancellationToken::new();
+        let calculations = TaskTracker::new();
+        calculations.spawn(sleep(Duration::from_secs(3600)));
+        shutdown.cancel();
+
+        let served = run_until_shutdown(
+            std::future::pending::<Result<(), ()>>(),
+            &shutdown,
+            &calculations,
+            Duration::from_millis(50),
+        )
+        .await;
+
+        assert_eq!(served, Ok(()));
+        assert_eq!(calculations.len(), 1);
+    }
+}
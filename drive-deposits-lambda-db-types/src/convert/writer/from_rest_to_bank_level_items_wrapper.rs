@@ -1,7 +1,9 @@
 use crate::convert::writer::with_level_context::{
     error_with_bank_level, ItemWriterError, LevelSpecificItemWriterError,
 };
-use crate::db_item_types::{BankLevelItem, BankLevelItemsWrapper};
+use crate::db_item_types::{
+    portfolio_partition_key, tenant_of, BankLevelItem, BankLevelItemsWrapper,
+};
 use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
 use rust_decimal::Decimal;
 use serde_json::to_string;
@@ -12,7 +14,7 @@ impl TryFrom<&CalculatePortfolioResponse> for BankLevelItemsWrapper {
     fn try_from(rest: &CalculatePortfolioResponse) -> Result<Self, Self::Error> {
         // for a specific response
         let portfolio_uuid = rest.uuid.clone();
-        let pk_portfolio_uuid = format!("PORTFOLIO#UUID#{}", portfolio_uuid);
+        let pk_portfolio_uuid = portfolio_partition_key(tenant_of(rest), &portfolio_uuid);
         let created_at = rest.created_at.clone();
 
         let items = rest
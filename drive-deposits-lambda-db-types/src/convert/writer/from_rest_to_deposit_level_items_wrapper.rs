@@ -2,7 +2,8 @@ use crate::convert::writer::with_level_context::{
     error_with_deposit_level, ItemWriterError, LevelSpecificItemWriterError,
 };
 use crate::db_item_types::{
-    CalculatePortfolioRestWrapper, DepositLevelItem, DepositLevelItemsWrapper, DepositSortCriteria,
+    portfolio_partition_key, tenant_of, CalculatePortfolioRestWrapper, DepositLevelItem,
+    DepositLevelItemsWrapper, DepositSortCriteria,
 };
 use rust_decimal::Decimal;
 use serde_json::to_string;
@@ -20,7 +21,7 @@ impl TryFrom<&CalculatePortfolioRestWrapper> for DepositLevelItemsWrapper {
         let deposit_sort_criteria = &rest_wrapper.deposit_sort_criteria;
         let rest = &rest_wrapper.calculate_portfolio_response;
         let portfolio_uuid = rest.uuid.clone();
-        let pk_format_portfolio_uuid = format!("PORTFOLIO#UUID#{}", portfolio_uuid);
+        let pk_format_portfolio_uuid = portfolio_partition_key(tenant_of(rest), &portfolio_uuid);
 
         let bank_deposit_level_items_per_bank_iter = rest.banks.iter().map(|bank| {
             let bank_deposit_level_items_per_bank_iter = bank.deposits.iter().map(
@@ -1,7 +1,7 @@
 use crate::convert::writer::with_level_context::{
     error_with_portfolio_level, ItemWriterError, LevelSpecificItemWriterError,
 };
-use crate::db_item_types::PortfolioLevelItem;
+use crate::db_item_types::{portfolios_partition_key, tenant_of, PortfolioLevelItem};
 use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
 use rust_decimal::Decimal;
 use serde_json::to_string;
@@ -14,8 +14,8 @@ impl TryFrom<&CalculatePortfolioResponse> for PortfolioLevelItem {
         let outcome = rest.outcome.as_ref().ok_or_else(|| {
             error_with_portfolio_level(ItemWriterError::MissingDataField("outcome".to_string()))
         })?;
-        // for different responses
-        let pk_portfolios = "PORTFOLIOS".to_string();
+        // for different responses of the same tenant
+        let pk_portfolios = portfolios_partition_key(tenant_of(rest));
 
         let delta = outcome.delta.as_ref().ok_or_else(|| {
             error_with_portfolio_level(ItemWriterError::MissingDataField("delta".to_string()))
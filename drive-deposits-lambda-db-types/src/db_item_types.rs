@@ -3,9 +3,32 @@
 
 // access patterns determine types and the structure of the data
 
+use drive_deposits_rest_types::auth::DEFAULT_TENANT_ID;
 use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
 use serde::Serialize;
 
+// every partition key starts with the tenant so one tenant can never query another's items;
+// the default tenant keeps the unprefixed keys written before tenants existed, so those items
+// stay readable without a migration
+pub fn portfolios_partition_key(tenant_id: &str) -> String {
+    match tenant_id {
+        DEFAULT_TENANT_ID => "PORTFOLIOS".to_string(),
+        _ => format!("TENANT#{}#PORTFOLIOS", tenant_id),
+    }
+}
+
+pub fn portfolio_partition_key(tenant_id: &str, portfolio_uuid: &str) -> String {
+    match tenant_id {
+        DEFAULT_TENANT_ID => format!("PORTFOLIO#UUID#{}", portfolio_uuid),
+        _ => format!("TENANT#{}#PORTFOLIO#UUID#{}", tenant_id, portfolio_uuid),
+    }
+}
+
+// events sent before tenants existed have no tenant_id
+pub fn tenant_of(rest: &CalculatePortfolioResponse) -> &str {
+    rest.tenant_id.as_deref().unwrap_or(DEFAULT_TENANT_ID)
+}
+
 // ResponseLevelItem means deals with responses so RESPONSES as pk has many response items
 #[derive(Debug, Default, Serialize)]
 pub struct PortfolioLevelItem {
@@ -1,14 +1,16 @@
 use drive_deposits_rest_types::rest_types::{Outcome, OutcomeWithDates};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 pub const DEFAULT_TOP_K: usize = 2;
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ItemParamsRequest {
     pub order: Option<Order>,
     pub top_k: Option<usize>,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Order {
     #[default]
@@ -27,7 +29,7 @@ impl From<&Order> for bool {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct Metadata {
     pub order: Order,
     pub top_k: usize,
@@ -36,19 +38,19 @@ pub struct Metadata {
 
 // REST API response: Calculation results sorted by delta period growth at the portfolios level
 // delta period growth is the default order criteria
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct ByLevelForPortfolios {
     pub data: PortfolioData,
     pub metadata: Metadata,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct PortfolioData {
     pub responses: Vec<PortfolioResponse>,
     pub count_responses: usize,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct PortfolioResponse {
     pub portfolio_uuid: String,
     pub outcome: Outcome,
@@ -57,19 +59,19 @@ pub struct PortfolioResponse {
 
 //  REST API response: Calculation results sorted by delta period growth at the banks level for a given portfolio
 // delta period growth is the default order criteria
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct ByLevelForBanks {
     pub data: BankData,
     pub metadata: Metadata,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct BankData {
     pub responses: Vec<BankResponse>,
     pub count_responses: usize,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct BankResponse {
     pub bank_uuid: String,
     pub bank_name: String,
@@ -82,19 +84,19 @@ pub struct BankResponse {
 // handles 2 rest api responses at the deposits level:
 //  REST API response: Calculation results sorted by delta period growth at the deposits level for all banks for a given portfolio
 //  REST API response: Calculation results sorted by maturity date at the deposits level for all banks for a given portfolio
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct ByLevelForDeposits {
     pub data: DepositData,
     pub metadata: Metadata,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct DepositData {
     pub responses: Vec<DepositResponse>,
     pub count_responses: usize,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ToSchema)]
 pub struct DepositResponse {
     pub deposit_uuid: String,
     pub bank_uuid: String,
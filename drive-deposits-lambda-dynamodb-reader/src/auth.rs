@@ -0,0 +1,30 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tracing::debug;
+
+use drive_deposits_rest_types::auth::{Authenticator, API_KEY_HEADER, AUTHORIZATION_HEADER};
+
+use crate::handler_error::Error as HandlerError;
+
+// same credentials as the rest gateway; the caller's TenantId goes in the request extensions so
+// every query is scoped to the tenant's own partition keys
+pub async fn authenticate(
+    State(authenticator): State<Authenticator>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, HandlerError> {
+    let headers = request.headers();
+    let authorization = headers
+        .get(AUTHORIZATION_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let tenant_id = authenticator.authenticate(authorization, api_key)?;
+    debug!("reader request authenticated for tenant {}", tenant_id);
+    request.extensions_mut().insert(tenant_id);
+    Ok(next.run(request).await)
+}
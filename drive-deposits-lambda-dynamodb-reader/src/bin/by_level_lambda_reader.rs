@@ -1,16 +1,22 @@
 use axum::extract::{Path, Query, State};
+use axum::middleware::from_fn_with_state;
+use axum::response::Html;
 use axum::routing::get;
-use axum::{debug_handler, Json, Router};
+use axum::{debug_handler, Extension, Json, Router};
 use drive_deposits_lambda_db_types::db_item_types::DepositSortCriteria;
 use drive_deposits_lambda_db_types::query_response_types::{
     ByLevelForBanks, ByLevelForDeposits, ByLevelForPortfolios, ItemParamsRequest,
 };
+use drive_deposits_lambda_dynamodb_reader::auth::authenticate;
 use drive_deposits_lambda_dynamodb_reader::dynamodb::query::{
     query_banks, query_deposits, query_portfolios,
 };
 use drive_deposits_lambda_dynamodb_reader::dynamodb::DriveDepositsDb;
 use drive_deposits_lambda_dynamodb_reader::handler_error::Error as HandlerError;
+use drive_deposits_lambda_dynamodb_reader::health::{health, Health};
 use drive_deposits_lambda_dynamodb_reader::request_error::Error as RequestError;
+use drive_deposits_rest_types::auth::{Authenticator, TenantId};
+use drive_deposits_rest_types::openapi::{swagger_ui_html, SecurityAddon};
 use lambda_http::{
     http::StatusCode,
     run, service_fn,
@@ -21,31 +27,65 @@ use serde_json::{json, Value};
 use std::env::set_var;
 use std::sync::Arc;
 use tracing::{info, Instrument, Span};
+use utoipa::OpenApi;
 use uuid::Uuid;
 
+const OPENAPI_JSON: &str = "/api-docs/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Drive Deposits By Level Reader"),
+    modifiers(&SecurityAddon),
+    paths(
+        by_level_query_portfolios_delta_growth,
+        by_level_query_banks_delta_growth,
+        by_level_query_deposits_delta_growth,
+        by_level_query_deposits_maturity_date
+    )
+)]
+struct ApiDoc;
+
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn swagger_ui() -> Html<String> {
+    Html(swagger_ui_html(
+        "Drive Deposits By Level Reader",
+        OPENAPI_JSON.trim_start_matches('/'),
+    ))
+}
+
 async fn root() -> Json<Value> {
     Json(
-        json!({ "msg": "Use on of the the routes /by-level-for-portfolios/delta-growth; /by-level-for-banks/delta-growth/:pk_portfolio_uuid; /by-level-for-deposits/delta-growth/:pk_portfolio_uuid; /by-bank-deposits-level/maturity-date/:pk_portfolio_uuid" }),
+        json!({ "msg": "Use on of the the routes /by-level-for-portfolios/delta-growth; /by-level-for-banks/delta-growth/:pk_portfolio_uuid; /by-level-for-deposits/delta-growth/:pk_portfolio_uuid; /by-bank-deposits-level/maturity-date/:pk_portfolio_uuid; API contract at /api-docs/openapi.json and Swagger UI at /swagger-ui" }),
     )
 }
 
-async fn health_check() -> (StatusCode, String) {
-    let health = true;
-    match health {
-        true => (StatusCode::OK, "Healthy!".to_string()),
-        false => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Not healthy!".to_string(),
-        ),
-    }
+async fn health_check(
+    State(db_handler): State<Arc<DriveDepositsDb>>,
+) -> (StatusCode, Json<Health>) {
+    health(&db_handler).await
 }
 
 // #[debug_handler]
 // using just Query<ItemParamsRequest> instead of Option<Query<ItemParamsRequest>> would be sufficient and more straightforward.
 // Since Axum always provides a Some value with an empty ItemParamsRequest when no query parameters are passed, wrapping it in an Option doesn't add any extra functionality.
+#[utoipa::path(
+    get,
+    path = "/by-level-for-portfolios/delta-growth",
+    params(ItemParamsRequest),
+    responses(
+        (status = 200, description = "Portfolios sorted by delta period growth", body = ByLevelForPortfolios),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 500, description = "DynamoDB query error")
+    ),
+    tag = "by-level"
+)]
 async fn by_level_query_portfolios_delta_growth(
     Query(query_item_params): Query<ItemParamsRequest>,
     State(db_handler): State<Arc<DriveDepositsDb>>,
+    Extension(tenant_id): Extension<TenantId>,
 ) -> Result<Json<ByLevelForPortfolios>, HandlerError> {
     let span = info_span!("handler by_level_query_portfolios_delta_growth");
     span.in_scope(|| {
@@ -58,6 +98,7 @@ async fn by_level_query_portfolios_delta_growth(
     let portfolios = query_portfolios(
         &db_handler.dynamodb_client,
         db_handler.table_name.as_str(),
+        &tenant_id,
         query_item_params,
     )
     .instrument(span)
@@ -67,10 +108,23 @@ async fn by_level_query_portfolios_delta_growth(
 }
 
 // #[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/portfolios/{pk_portfolio_uuid}/by-level-for-banks/delta-growth",
+    params(("pk_portfolio_uuid" = String, Path, description = "Portfolio uuid"), ItemParamsRequest),
+    responses(
+        (status = 200, description = "Banks of the portfolio sorted by delta period growth", body = ByLevelForBanks),
+        (status = 400, description = "Invalid portfolio uuid"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 500, description = "DynamoDB query error")
+    ),
+    tag = "by-level"
+)]
 async fn by_level_query_banks_delta_growth(
     Path(pk_portfolio_uuid): Path<String>,
     Query(query_item_params): Query<ItemParamsRequest>,
     State(db_handler): State<Arc<DriveDepositsDb>>,
+    Extension(tenant_id): Extension<TenantId>,
 ) -> Result<Json<ByLevelForBanks>, HandlerError> {
     let span = info_span!("handler by_level_query_banks_delta_growth");
 
@@ -90,6 +144,7 @@ async fn by_level_query_banks_delta_growth(
     let banks = query_banks(
         &db_handler.dynamodb_client,
         db_handler.table_name.as_str(),
+        &tenant_id,
         query_item_params,
         pk_portfolio_uuid,
     )
@@ -100,10 +155,23 @@ async fn by_level_query_banks_delta_growth(
 }
 
 #[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/portfolios/{pk_portfolio_uuid}/by-level-for-deposits/delta-growth",
+    params(("pk_portfolio_uuid" = String, Path, description = "Portfolio uuid"), ItemParamsRequest),
+    responses(
+        (status = 200, description = "Deposits of the portfolio sorted by delta period growth", body = ByLevelForDeposits),
+        (status = 400, description = "Invalid portfolio uuid"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 500, description = "DynamoDB query error")
+    ),
+    tag = "by-level"
+)]
 async fn by_level_query_deposits_delta_growth(
     Path(pk_portfolio_uuid): Path<String>,
     Query(query_item_params): Query<ItemParamsRequest>,
     State(db_handler): State<Arc<DriveDepositsDb>>,
+    Extension(tenant_id): Extension<TenantId>,
 ) -> Result<Json<ByLevelForDeposits>, HandlerError> {
     let span = info_span!("handler by_level_query_deposits_delta_growth");
 
@@ -111,6 +179,7 @@ async fn by_level_query_deposits_delta_growth(
         Path(pk_portfolio_uuid),
         Query(query_item_params),
         State(db_handler),
+        tenant_id,
         DepositSortCriteria::DeltaPeriodGrowth,
     )
     .instrument(span)
@@ -118,10 +187,23 @@ async fn by_level_query_deposits_delta_growth(
 }
 
 #[debug_handler]
+#[utoipa::path(
+    get,
+    path = "/portfolios/{pk_portfolio_uuid}/by-level-for-deposits/maturity-date",
+    params(("pk_portfolio_uuid" = String, Path, description = "Portfolio uuid"), ItemParamsRequest),
+    responses(
+        (status = 200, description = "Deposits of the portfolio sorted by maturity date", body = ByLevelForDeposits),
+        (status = 400, description = "Invalid portfolio uuid"),
+        (status = 401, description = "Missing or invalid credentials"),
+        (status = 500, description = "DynamoDB query error")
+    ),
+    tag = "by-level"
+)]
 async fn by_level_query_deposits_maturity_date(
     Path(pk_portfolio_uuid): Path<String>,
     Query(query_item_params): Query<ItemParamsRequest>,
     State(db_handler): State<Arc<DriveDepositsDb>>,
+    Extension(tenant_id): Extension<TenantId>,
 ) -> Result<Json<ByLevelForDeposits>, HandlerError> {
     let span = info_span!("handler by_level_query_deposits_maturity_date");
 
@@ -129,6 +211,7 @@ async fn by_level_query_deposits_maturity_date(
         Path(pk_portfolio_uuid),
         Query(query_item_params),
         State(db_handler),
+        tenant_id,
         DepositSortCriteria::MaturityDate,
     )
     .instrument(span)
@@ -139,6 +222,7 @@ async fn by_level_query_deposits(
     Path(pk_portfolio_uuid): Path<String>,
     Query(query_item_params): Query<ItemParamsRequest>,
     State(db_handler): State<Arc<DriveDepositsDb>>,
+    tenant_id: TenantId,
     deposit_sort_criteria: DepositSortCriteria,
 ) -> Result<Json<ByLevelForDeposits>, HandlerError> {
     debug!(
@@ -159,6 +243,7 @@ async fn by_level_query_deposits(
     let deposits = query_deposits(
         &db_handler.dynamodb_client,
         db_handler.table_name.as_str(),
+        &tenant_id,
         query_item_params,
         pk_portfolio_uuid,
         deposit_sort_criteria,
@@ -199,9 +284,11 @@ async fn main() -> Result<(), LambdaHttpError> {
             .inspect_err(|err| error!("DriveDepositsDb::handler error is {}", err))?,
     );
 
-    let mut app_router: Router<()> = Router::new()
-        .route("/", get(root))
-        .route("/health", get(health_check))
+    let authenticator = Authenticator::from_env()
+        .inspect_err(|err| error!("Authenticator::from_env error is {}", err))?;
+
+    // only the by-level queries need credentials; root, health and the API docs stay open
+    let by_level_routes = Router::new()
         .route(
             "/by-level-for-portfolios/delta-growth",
             get(by_level_query_portfolios_delta_growth),
@@ -218,6 +305,13 @@ async fn main() -> Result<(), LambdaHttpError> {
             "/portfolios/{pk_portfolio_uuid}/by-level-for-deposits/maturity-date",
             get(by_level_query_deposits_maturity_date),
         )
+        .route_layer(from_fn_with_state(authenticator, authenticate));
+    let mut app_router: Router<()> = Router::new()
+        .route("/", get(root))
+        .route("/health", get(health_check))
+        .route(OPENAPI_JSON, get(openapi_json))
+        .route("/swagger-ui", get(swagger_ui))
+        .merge(by_level_routes)
         .with_state(db_handler);
 
     run(service_fn(|event: Request| {
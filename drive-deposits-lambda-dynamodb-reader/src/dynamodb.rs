@@ -17,13 +17,20 @@ pub enum DbError {
     #[error("DbError DynamoDb client error")]
     DynamoDbClientError,
 
+    // boxed, the sdk error would make every Result carrying this error very large
     #[error("DbError Sdk error is: {0}")]
-    SdkError(#[from] aws_sdk_dynamodb::error::SdkError<DescribeTableError>),
+    SdkError(Box<aws_sdk_dynamodb::error::SdkError<DescribeTableError>>),
 
     #[error("DbError Env var error")]
     VarError(#[from] env::VarError),
 }
 
+impl From<aws_sdk_dynamodb::error::SdkError<DescribeTableError>> for DbError {
+    fn from(err: aws_sdk_dynamodb::error::SdkError<DescribeTableError>) -> Self {
+        Self::SdkError(Box::new(err))
+    }
+}
+
 pub struct DriveDepositsDb {
     pub dynamodb_client: Client,
     pub table_name: String,
@@ -6,7 +6,9 @@ use aws_sdk_dynamodb::Client;
 use drive_deposits_lambda_db_types::convert::reader::with_level_context::LevelSpecificResponseReaderError;
 use drive_deposits_lambda_db_types::{
     convert::reader::with_level_context::LevelSpecificItemReaderError,
-    db_item_types::{BankLevelItem, PortfolioLevelItem},
+    db_item_types::{
+        portfolio_partition_key, portfolios_partition_key, BankLevelItem, PortfolioLevelItem,
+    },
     db_item_types::{DepositLevelItem, DepositSortCriteria},
     query_response_types::{
         BankData, BankResponse, ByLevelForBanks, ByLevelForPortfolios, ItemParamsRequest, Metadata,
@@ -14,13 +16,15 @@ use drive_deposits_lambda_db_types::{
     },
     query_response_types::{ByLevelForDeposits, DepositData, DepositResponse},
 };
+use drive_deposits_rest_types::auth::TenantId;
 use thiserror::Error;
 use tracing::{debug, error, info};
 
 #[derive(Error, Debug)]
 pub enum QueryItemError {
+    // boxed, the sdk error would make every Result carrying this error very large
     #[error("QueryItemError with DynamoDbSdkError error: {0}")]
-    DynamoDbSdkError(#[from] SdkError<QueryError>),
+    DynamoDbSdkError(Box<SdkError<QueryError>>),
 
     #[error("QueryItemError with QueryNoItemFound error: {0}")]
     QueryNoItemFound(String),
@@ -32,9 +36,16 @@ pub enum QueryItemError {
     LevelSpecificResponseReaderError(#[from] LevelSpecificResponseReaderError),
 }
 
+impl From<SdkError<QueryError>> for QueryItemError {
+    fn from(err: SdkError<QueryError>) -> Self {
+        Self::DynamoDbSdkError(Box::new(err))
+    }
+}
+
 pub async fn query_portfolios(
     client: &Client,
     table: &str,
+    tenant_id: &TenantId,
     query_item_params: ItemParamsRequest,
 ) -> Result<ByLevelForPortfolios, QueryItemError> {
     info!("in query_portfolios");
@@ -53,7 +64,7 @@ pub async fn query_portfolios(
         .expression_attribute_names("#partitionKeyName".to_string(), pk)
         .expression_attribute_values(
             ":partitionKeyValue".to_string(),
-            AttributeValue::S("PORTFOLIOS".to_string()),
+            AttributeValue::S(portfolios_partition_key(tenant_id.as_str())),
         )
         .scan_index_forward(forward);
     let query_resp = request.send().await?;
@@ -104,6 +115,7 @@ pub async fn query_portfolios(
 pub async fn query_banks(
     client: &Client,
     table: &str,
+    tenant_id: &TenantId,
     query_item_params: ItemParamsRequest,
     pk_portfolio_uuid: String,
 ) -> Result<ByLevelForBanks, QueryItemError> {
@@ -116,7 +128,8 @@ pub async fn query_banks(
 
     let pk = "PK".to_string();
     let sk = "SK".to_string();
-    let partition_key_value = format!("PORTFOLIO#UUID#{}", pk_portfolio_uuid);
+    // a portfolio of another tenant is in another partition, so it reads as having no banks
+    let partition_key_value = portfolio_partition_key(tenant_id.as_str(), &pk_portfolio_uuid);
     let sort_key_value = "BANK#PERIOD#".to_string();
     let forward = bool::from(&order);
     let query_params = QueryParams {
@@ -183,6 +196,7 @@ pub async fn query_banks(
 pub async fn query_deposits(
     client: &Client,
     table: &str,
+    tenant_id: &TenantId,
     query_item_params: ItemParamsRequest,
     pk_portfolio_uuid: String,
     sort_criteria: DepositSortCriteria,
@@ -196,7 +210,7 @@ pub async fn query_deposits(
 
     let pk = "PK".to_string();
     let sk = "SK".to_string();
-    let partition_key_value = format!("PORTFOLIO#UUID#{}", pk_portfolio_uuid);
+    let partition_key_value = portfolio_partition_key(tenant_id.as_str(), &pk_portfolio_uuid);
     let (sort_key_value, sort_criteria_description) = match sort_criteria {
         DepositSortCriteria::DeltaPeriodGrowth => ("DEPOSIT#PERIOD#".to_string(), format!(
             "Calculation results sorted by delta period growth at the deposits level for portfolio {}",
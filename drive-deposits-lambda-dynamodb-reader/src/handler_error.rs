@@ -1,9 +1,10 @@
 use crate::dynamodb::query::QueryItemError;
 use crate::request_error::Error as RequestError;
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Json, Response},
 };
+use drive_deposits_rest_types::auth::Error as AuthError;
 use serde::Serialize;
 use thiserror::Error;
 use tracing::error;
@@ -19,6 +20,9 @@ pub enum Error {
 
     #[error("QueryItemError querying DynamoDB: {0}")]
     QueryItemError(#[from] QueryItemError),
+
+    #[error("Unauthenticated: {0}")]
+    Unauthenticated(#[from] AuthError),
 }
 
 #[derive(Serialize)]
@@ -39,6 +43,10 @@ impl IntoResponse for Error {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Query item error: {}", err),
             ),
+            Error::Unauthenticated(err) => (
+                StatusCode::UNAUTHORIZED,
+                format!("Unauthenticated: {}", err),
+            ),
             Error::InternalServer => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error".to_string(),
@@ -51,6 +59,12 @@ impl IntoResponse for Error {
             error: error_message,
         };
 
-        (status, Json(error)).into_response()
+        let mut response = (status, Json(error)).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
     }
 }
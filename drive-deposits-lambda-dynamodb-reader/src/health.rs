@@ -0,0 +1,181 @@
+use aws_sdk_dynamodb::types::TableStatus;
+use axum::{http::StatusCode, Json};
+use serde::Serialize;
+use tracing::{debug, error};
+
+use crate::dynamodb::DriveDepositsDb;
+
+// /health needs no credentials, so the AWS error only goes to the log
+pub const DESCRIBE_TABLE_FAILED: &str = "describe table failed";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableHealth {
+    pub status: HealthStatus,
+    pub table_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub status: HealthStatus,
+    pub dynamodb: TableHealth,
+}
+
+// DescribeTable is cheap and needs no items; the table is readable while ACTIVE or UPDATING
+pub async fn probe_table(db_handler: &DriveDepositsDb) -> TableHealth {
+    let table_name = db_handler.table_name.clone();
+    match db_handler
+        .dynamodb_client
+        .describe_table()
+        .table_name(&table_name)
+        .send()
+        .await
+    {
+        Ok(output) => {
+            let table_status = output.table.and_then(|table| table.table_status);
+            debug!("table {} status is {:?}", table_name, table_status);
+            let status = match table_status {
+                Some(TableStatus::Active) | Some(TableStatus::Updating) => HealthStatus::Healthy,
+                _ => HealthStatus::Degraded,
+            };
+            TableHealth {
+                status,
+                table_name,
+                table_status: table_status.map(|table_status| table_status.as_str().to_string()),
+                error: None,
+            }
+        }
+        Err(err) => {
+            error!("describe table {} for health failed: {:?}", table_name, err);
+            TableHealth {
+                status: HealthStatus::Degraded,
+                table_name,
+                table_status: None,
+                error: Some(DESCRIBE_TABLE_FAILED.to_string()),
+            }
+        }
+    }
+}
+
+pub async fn health(db_handler: &DriveDepositsDb) -> (StatusCode, Json<Health>) {
+    let dynamodb = probe_table(db_handler).await;
+    let status = dynamodb.status;
+    let status_code = match status {
+        HealthStatus::Healthy => StatusCode::OK,
+        HealthStatus::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status_code, Json(Health { status, dynamodb }))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+    use aws_sdk_dynamodb::{Client, Config};
+    use aws_smithy_runtime::client::http::test_util::infallible_client_fn;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const TABLE_NAME: &str = "drive-deposits-table";
+
+    // every DescribeTable call gets this response instead of going to DynamoDB
+    fn db_answering(status: u16, body: Value) -> DriveDepositsDb {
+        let body = body.to_string();
+        let http_client = infallible_client_fn(move |_request| {
+            http_02x::Response::builder()
+                .status(status)
+                .body(body.clone())
+                .unwrap()
+        });
+        let config = Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .http_client(http_client)
+            .build();
+        DriveDepositsDb {
+            dynamodb_client: Client::from_conf(config),
+            table_name: TABLE_NAME.to_string(),
+        }
+    }
+
+    fn table_with_status(table_status: &str) -> DriveDepositsDb {
+        db_answering(
+            200,
+            json!({"Table": {"TableName": TABLE_NAME, "TableStatus": table_status}}),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_health_is_ok_while_table_is_readable() {
+        for table_status in ["ACTIVE", "UPDATING"] {
+            let (status_code, Json(health)) = health(&table_with_status(table_status)).await;
+
+            assert_eq!(status_code, StatusCode::OK);
+            assert_eq!(
+                serde_json::to_value(health).unwrap(),
+                json!({
+                    "status": "healthy",
+                    "dynamodb": {
+                        "status": "healthy",
+                        "table_name": TABLE_NAME,
+                        "table_status": table_status,
+                    },
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health_is_unavailable_while_table_is_not_readable() {
+        let (status_code, Json(health)) = health(&table_with_status("CREATING")).await;
+
+        assert_eq!(status_code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert_eq!(health.dynamodb.table_status.as_deref(), Some("CREATING"));
+        assert_eq!(health.dynamodb.error, None);
+    }
+
+    #[tokio::test]
+    async fn test_health_is_unavailable_when_describe_table_fails() {
+        let db_handler = db_answering(
+            400,
+            json!({
+                "__type": "com.amazonaws.dynamodb.v20120810#ResourceNotFoundException",
+                "message": "Requested resource not found",
+            }),
+        );
+
+        let table_health = probe_table(&db_handler).await;
+        assert_eq!(table_health.status, HealthStatus::Degraded);
+        assert_eq!(table_health.table_status, None);
+        assert_eq!(table_health.error.as_deref(), Some(DESCRIBE_TABLE_FAILED));
+
+        let (status_code, Json(health)) = health(&db_handler).await;
+        assert_eq!(status_code, StatusCode::SERVICE_UNAVAILABLE);
+        let body = serde_json::to_value(health).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "status": "degraded",
+                "dynamodb": {
+                    "status": "degraded",
+                    "table_name": TABLE_NAME,
+                    "error": DESCRIBE_TABLE_FAILED,
+                },
+            })
+        );
+        assert!(!body.to_string().contains("ResourceNotFoundException"));
+    }
+}
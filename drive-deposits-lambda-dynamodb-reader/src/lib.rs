@@ -1,4 +1,6 @@
+pub mod auth;
 pub mod dynamodb;
+pub mod health;
 
 pub mod handler_error;
 pub mod request_error;
@@ -1,17 +1,21 @@
 use aws_lambda_events::event::eventbridge::EventBridgeEvent;
+use drive_deposits_event_source::object_store::{create_object_store, SharedObjectStore};
+use drive_deposits_event_source::publisher::PORTFOLIO_LEVEL;
+use drive_deposits_logs_lambda_target::by_level::{acknowledge_deposit_level, ByLevelDetailType};
 use drive_deposits_logs_lambda_target::dynamodb::add::add_item;
 use drive_deposits_logs_lambda_target::dynamodb::DriveDepositsDb;
+use drive_deposits_logs_lambda_target::event_detail::hydrate_validated;
 use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
 use lambda_runtime::{
     run, service_fn,
     tracing::{debug, error, info_span, init_default_subscriber, instrument, Instrument},
     Error, LambdaEvent,
 };
-use serde_json::from_value;
 
-#[instrument(skip(db_handler, event))]
+#[instrument(skip(db_handler, object_store, event))]
 async fn banks_level_handler(
     db_handler: &DriveDepositsDb,
+    object_store: Option<&SharedObjectStore>,
     event: LambdaEvent<EventBridgeEvent>,
 ) -> Result<(), Error> {
     // Extract some useful information from the request
@@ -24,21 +28,46 @@ async fn banks_level_handler(
     debug!("db_handler table name is: {:?}", db_handler.table_name);
     let payload_detail = payload.detail;
     // debug!("payload.detail is  {:#?}", payload_detail);
+    match ByLevelDetailType::from_detail_type(&payload.detail_type) {
+        Some(ByLevelDetailType::PortfolioLevel) => {}
+        Some(ByLevelDetailType::DepositLevel) => {
+            acknowledge_deposit_level(
+                payload_detail,
+                object_store.map(|object_store| object_store.as_ref()),
+            )
+            .await
+            .inspect_err(|err| error!("Failed to read deposit-level detail: {}", err))?;
+            return Ok(());
+        }
+        None => {
+            error!("unsupported detail type {:?}", payload.detail_type);
+            return Err(format!("unsupported detail type {:?}", payload.detail_type).into());
+        }
+    }
     debug!("event_target_response is  ------------ ");
-    let event_target_response: CalculatePortfolioResponse = from_value(payload_detail)
-        .inspect_err(|err| error!("Failed to deserialize payload_detail: {:?}", err))?;
+    let (event_target_response, correlation_id): (CalculatePortfolioResponse, _) =
+        hydrate_validated(
+            PORTFOLIO_LEVEL,
+            payload_detail,
+            object_store.map(|object_store| object_store.as_ref()),
+        )
+        .await
+        .inspect_err(|err| error!("Failed to read payload_detail: {}", err))?;
+    debug!("event correlation id is {:?}", correlation_id);
     debug!("event_target_response is  {:#?}", event_target_response);
 
     add_item(
         &db_handler.dynamodb_client,
         db_handler.table_name.as_str(),
         event_target_response,
+        &db_handler.batch_write,
     )
     .instrument(span)
     .await?;
 
     Ok(())
 }
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     println!("before init tracing subscriber");
@@ -48,12 +77,16 @@ async fn main() -> Result<(), Error> {
     span.in_scope(|| debug!("after init_default_subscriber"));
 
     let db_handler = DriveDepositsDb::handler().await?;
+    // large portfolio events carry a dataref into this store instead of their data
+    let object_store = create_object_store().await?;
 
     // service_fn returns ServiceFn that implements FnMut so cannot pass ownership of db_handler
     // closure is `FnOnce` if it moves the variable `db_handler` out of its environment
-    run(service_fn(|event| banks_level_handler(&db_handler, event)))
-        .instrument(span)
-        .await?;
+    run(service_fn(|event| {
+        banks_level_handler(&db_handler, object_store.as_ref(), event)
+    }))
+    .instrument(span)
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,116 @@
+use serde_json::Value;
+use tracing::info;
+
+use drive_deposits_event_source::object_store::ObjectStore;
+use drive_deposits_event_source::payload_types::DepositLevel;
+use drive_deposits_event_source::publisher::{DEPOSIT_LEVEL, PORTFOLIO_LEVEL};
+
+use crate::event_detail::{hydrate_validated, EventDetailError};
+
+// The detail types the writer lambda handles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByLevelDetailType {
+    PortfolioLevel,
+    DepositLevel,
+}
+
+impl ByLevelDetailType {
+    // suffix match, since publishers can prefix the detail type
+    pub fn from_detail_type(detail_type: &str) -> Option<Self> {
+        if detail_type.ends_with(PORTFOLIO_LEVEL) {
+            Some(Self::PortfolioLevel)
+        } else if detail_type.ends_with(DEPOSIT_LEVEL) {
+            Some(Self::DepositLevel)
+        } else {
+            None
+        }
+    }
+}
+
+// the deposit items are written with the portfolio-level event, which has the portfolio created_at
+// their sort keys need; a deposit-level event is only checked and acknowledged
+pub async fn acknowledge_deposit_level(
+    detail: Value,
+    object_store: Option<&dyn ObjectStore>,
+) -> Result<DepositLevel, EventDetailError> {
+    let (deposit_level, correlation_id): (DepositLevel, _) =
+        hydrate_validated(DEPOSIT_LEVEL, detail, object_store).await?;
+    info!(
+        "deposit-level event for deposit {} of bank {} in portfolio {}, correlation id {:?}",
+        deposit_level.deposit.uuid,
+        deposit_level.bank_name,
+        deposit_level.portfolio_uuid,
+        correlation_id
+    );
+    Ok(deposit_level)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, to_value};
+
+    use drive_deposits_event_source::payload_types::Deposit;
+    use drive_deposits_event_source::publisher::{BANK_LEVEL, EVENT_SOURCE};
+    use drive_deposits_rest_types::cloud_event::CloudEvent;
+
+    use super::*;
+
+    fn deposit_level() -> Value {
+        to_value(DepositLevel {
+            portfolio_uuid: "portfolio-1".to_string(),
+            bank_uuid: "bank-1".to_string(),
+            bank_name: "VISION-BANK".to_string(),
+            bank_tz: "America/New_York".to_string(),
+            tenant_id: Some("tenant-a".to_string()),
+            deposit: Deposit {
+                uuid: "deposit-1".to_string(),
+                apy: "2.4".to_string(),
+                ..Deposit::default()
+            },
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_detail_type() {
+        assert_eq!(
+            ByLevelDetailType::from_detail_type(PORTFOLIO_LEVEL),
+            Some(ByLevelDetailType::PortfolioLevel)
+        );
+        assert_eq!(
+            ByLevelDetailType::from_detail_type("staging.deposit-level"),
+            Some(ByLevelDetailType::DepositLevel)
+        );
+        assert_eq!(ByLevelDetailType::from_detail_type(BANK_LEVEL), None);
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_deposit_level_cloud_event() {
+        let detail = to_value(CloudEvent::new(
+            "event-1".to_string(),
+            EVENT_SOURCE,
+            DEPOSIT_LEVEL,
+            "portfolio-1".to_string(),
+            "correlation-1".to_string(),
+            deposit_level(),
+        ))
+        .unwrap();
+
+        let deposit_level = acknowledge_deposit_level(detail, None).await.unwrap();
+
+        assert_eq!(deposit_level.deposit.uuid, "deposit-1");
+        assert_eq!(deposit_level.bank_name, "VISION-BANK");
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_deposit_level_rejects_deposit_without_bank() {
+        let mut detail = deposit_level();
+        detail.as_object_mut().unwrap().remove("bank_uuid");
+        detail["deposit"]["apy"] = json!(2.4);
+
+        let err = acknowledge_deposit_level(detail, None).await.unwrap_err();
+
+        assert!(matches!(err, EventDetailError::SchemaValidation(_)));
+    }
+}
@@ -2,12 +2,16 @@ pub mod add;
 
 use std::env;
 use std::env::var;
+use std::str::FromStr;
+use std::time::Duration;
 
 use aws_config::BehaviorVersion;
 use aws_sdk_dynamodb::Client;
 use lambda_runtime::tracing::{debug, error, info_span, instrument};
 use thiserror::Error;
 
+use crate::dynamodb::add::BatchWriteConfig;
+
 const LOCALSTACK_ENDPOINT: &str = "http://localhost.localstack.cloud:4566/";
 
 #[derive(Default, Debug, Error)]
@@ -18,18 +22,26 @@ pub enum DbError {
 
     #[error("Env var error")]
     VarError(#[from] env::VarError),
+
+    #[error("Invalid value {value:?} for env variable {setting}")]
+    InvalidSetting {
+        setting: &'static str,
+        value: String,
+    },
 }
 
 pub struct DriveDepositsDb {
     pub dynamodb_client: Client,
     pub table_name: String,
+    pub batch_write: BatchWriteConfig,
 }
 
 impl DriveDepositsDb {
-    fn new(table_name: String, dynamodb_client: Client) -> Self {
+    fn new(table_name: String, dynamodb_client: Client, batch_write: BatchWriteConfig) -> Self {
         Self {
             dynamodb_client,
             table_name,
+            batch_write,
         }
     }
 
@@ -51,8 +63,30 @@ impl DriveDepositsDb {
         let config = config_loader.load().await;
         let dynamodb_client = Client::new(&config);
         span.in_scope(|| debug!("dynamodb client got!"));
-        Ok(Self::new(table_name, dynamodb_client))
+        let batch_write = batch_write_config()?;
+        span.in_scope(|| debug!("dynamodb batch write config: {:?}", batch_write));
+        Ok(Self::new(table_name, dynamodb_client, batch_write))
+    }
+}
+
+// DYNAMODB_BATCH_WRITE_CONCURRENCY and DYNAMODB_BATCH_WRITE_DEADLINE_MS override the defaults
+fn batch_write_config() -> Result<BatchWriteConfig, DbError> {
+    let mut batch_write = BatchWriteConfig::default();
+    if let Ok(value) = var("DYNAMODB_BATCH_WRITE_CONCURRENCY") {
+        batch_write.max_concurrency = parsed_setting("DYNAMODB_BATCH_WRITE_CONCURRENCY", value)?;
+    }
+    if let Ok(value) = var("DYNAMODB_BATCH_WRITE_DEADLINE_MS") {
+        batch_write.deadline =
+            Duration::from_millis(parsed_setting("DYNAMODB_BATCH_WRITE_DEADLINE_MS", value)?);
     }
+    Ok(batch_write)
+}
+
+fn parsed_setting<T: FromStr>(setting: &'static str, value: String) -> Result<T, DbError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| DbError::InvalidSetting { setting, value })
 }
 
 fn env_var_bool(name: &str) -> bool {
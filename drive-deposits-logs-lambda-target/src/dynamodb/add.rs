@@ -1,4 +1,10 @@
-use aws_sdk_dynamodb::{error::SdkError, operation::put_item::PutItemError, Client};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{
+    error::{BuildError, SdkError},
+    operation::{batch_write_item::BatchWriteItemError, put_item::PutItemError},
+    types::{AttributeValue, PutRequest, WriteRequest},
+    Client,
+};
 use drive_deposits_lambda_db_types::{
     convert::writer::with_level_context::LevelSpecificItemWriterError,
     db_item_types::{
@@ -7,43 +13,168 @@ use drive_deposits_lambda_db_types::{
     },
 };
 use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::{AcquireError, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Instant};
 use tokio::try_join;
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
+
+// BatchWriteItem takes at most 25 put requests
+pub const BATCH_WRITE_MAX_ITEMS: usize = 25;
+const BATCH_WRITE_INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+const BATCH_WRITE_MAX_BACKOFF: Duration = Duration::from_secs(2);
 
 #[derive(Error, Debug)]
 pub enum AddItemError {
     #[error("AddItemError with LevelSpecificItemWriterError: {0}")]
     LevelSpecificItemWriterError(#[from] LevelSpecificItemWriterError),
 
+    // the sdk errors are boxed, they would make every Result carrying this error very large;
+    // PutItem only writes the portfolio level item, the other levels go through BatchWriteItem
     #[error("AddItemError with DynamoDbSdkPutItemError error: {0}")]
-    DynamoDbSdkPutItemError(#[from] SdkError<PutItemError>),
+    DynamoDbSdkPutItemError(Box<SdkError<PutItemError>>),
+
+    #[error("AddItemError with DynamoDbSdkBatchWriteItemError error: {0}")]
+    DynamoDbSdkBatchWriteItemError(Box<SdkError<BatchWriteItemError>>),
+
+    #[error("AddItemError with write request BuildError error: {0}")]
+    BuildError(#[from] BuildError),
+
+    #[error("AddItemError with {remaining} items of table {table} still unprocessed after {attempts} BatchWriteItem attempts at the deadline")]
+    UnprocessedItems {
+        table: String,
+        remaining: usize,
+        attempts: u32,
+    },
 
     #[error("AddItemError with tokio::task::JoinError error: {0}")]
     JoinError(#[from] tokio::task::JoinError),
+
+    #[error("AddItemError with tokio::sync::AcquireError error: {0}")]
+    AcquireError(#[from] AcquireError),
+}
+
+impl From<SdkError<PutItemError>> for AddItemError {
+    fn from(err: SdkError<PutItemError>) -> Self {
+        Self::DynamoDbSdkPutItemError(Box::new(err))
+    }
+}
+
+impl From<SdkError<BatchWriteItemError>> for AddItemError {
+    fn from(err: SdkError<BatchWriteItemError>) -> Self {
+        Self::DynamoDbSdkBatchWriteItemError(Box::new(err))
+    }
+}
+
+// How bank and deposit level items are written: chunks of BATCH_WRITE_MAX_ITEMS, at most
+// max_concurrency of them in flight across all levels of a portfolio, and unprocessed items
+// retried until the deadline
+#[derive(Debug, Clone)]
+pub struct BatchWriteConfig {
+    pub max_concurrency: usize,
+    // from the start of each batch write; below the lambda timeout so the error gets logged
+    pub deadline: Duration,
+}
+
+impl Default for BatchWriteConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 4,
+            deadline: Duration::from_secs(20),
+        }
+    }
+}
+
+// The DynamoDB calls add_item makes, behind a trait so the chunking, concurrency and retries of
+// the batch writes can be tested without a table
+#[async_trait]
+pub trait ItemWriter: Clone + Send + Sync + 'static {
+    async fn put_item(
+        &self,
+        table: &str,
+        item: HashMap<String, AttributeValue>,
+    ) -> Result<(), AddItemError>;
+
+    // one BatchWriteItem request; returns the write requests DynamoDB left unprocessed
+    async fn batch_write_item(
+        &self,
+        table: &str,
+        write_requests: Vec<WriteRequest>,
+    ) -> Result<Vec<WriteRequest>, AddItemError>;
+}
+
+#[async_trait]
+impl ItemWriter for Client {
+    async fn put_item(
+        &self,
+        table: &str,
+        item: HashMap<String, AttributeValue>,
+    ) -> Result<(), AddItemError> {
+        let resp = self
+            .put_item()
+            .table_name(table)
+            .set_item(Some(item))
+            .send()
+            .await?;
+        debug!("dynamodb putItem with set_item response is: {:#?}", resp);
+        Ok(())
+    }
+
+    async fn batch_write_item(
+        &self,
+        table: &str,
+        write_requests: Vec<WriteRequest>,
+    ) -> Result<Vec<WriteRequest>, AddItemError> {
+        let resp = self
+            .batch_write_item()
+            .request_items(table, write_requests)
+            .send()
+            .await?;
+        Ok(resp
+            .unprocessed_items
+            .and_then(|mut unprocessed_items| unprocessed_items.remove(table))
+            .unwrap_or_default())
+    }
 }
 
-#[instrument(skip(client, rest))]
-pub async fn add_item(
-    client: &Client,
+#[instrument(skip(writer, rest, batch_write))]
+pub async fn add_item<W: ItemWriter>(
+    writer: &W,
     table: &str,
     rest: CalculatePortfolioResponse,
+    batch_write: &BatchWriteConfig,
 ) -> Result<(), AddItemError> {
     info!("inside add_item");
+    // shared by the bank and deposit level writers, so max_concurrency bounds all their chunks
+    let permits = Arc::new(Semaphore::new(batch_write.max_concurrency.max(1)));
 
     let portfolio_level_item = PortfolioLevelItem::try_from(&rest)?;
-    let (client_clone, table_clone) = clone_db_connection_details(client, table);
+    let (writer_clone, table_clone) = clone_db_connection_details(writer, table);
     info!("spawn add_portfolio_level_item");
     let portfolio_level_item_handle = tokio::spawn(async move {
-        add_portfolio_level_item(&client_clone, &table_clone, portfolio_level_item).await
+        add_portfolio_level_item(&writer_clone, &table_clone, portfolio_level_item).await
     });
 
     info!("spawn add_bank_level_items");
     let banks_level_items_wrapper = BankLevelItemsWrapper::try_from(&rest)?;
-    let (client_clone, table_clone) = clone_db_connection_details(client, table);
+    let (writer_clone, table_clone) = clone_db_connection_details(writer, table);
+    let batch_write_clone = batch_write.clone();
+    let permits_clone = permits.clone();
     let bank_level_items_handle = tokio::spawn(async move {
-        add_bank_level_items(&client_clone, &table_clone, banks_level_items_wrapper).await
+        add_bank_level_items(
+            &writer_clone,
+            &table_clone,
+            banks_level_items_wrapper,
+            &batch_write_clone,
+            permits_clone,
+        )
+        .await
     });
 
     info!("spawn add_deposit_level_items for growth criteria");
@@ -53,12 +184,16 @@ pub async fn add_item(
     };
     let deposit_level_items_growth_criteria =
         DepositLevelItemsWrapper::try_from(&rest_wrapper_growth_criteria)?;
-    let (client_clone, table_clone) = clone_db_connection_details(client, table);
+    let (writer_clone, table_clone) = clone_db_connection_details(writer, table);
+    let batch_write_clone = batch_write.clone();
+    let permits_clone = permits.clone();
     let deposit_level_items_growth_handle = tokio::spawn(async move {
         add_deposit_level_items(
-            &client_clone,
+            &writer_clone,
             &table_clone,
             deposit_level_items_growth_criteria,
+            &batch_write_clone,
+            permits_clone,
         )
         .await
     });
@@ -70,12 +205,15 @@ pub async fn add_item(
     };
     let deposit_level_items_date_criteria =
         DepositLevelItemsWrapper::try_from(&rest_wrapper_date_criteria)?;
-    let (client_clone, table_clone) = clone_db_connection_details(client, table);
+    let (writer_clone, table_clone) = clone_db_connection_details(writer, table);
+    let batch_write_clone = batch_write.clone();
     let deposit_level_items_date_handle = tokio::spawn(async move {
         add_deposit_level_items(
-            &client_clone,
+            &writer_clone,
             &table_clone,
             deposit_level_items_date_criteria,
+            &batch_write_clone,
+            permits,
         )
         .await
     });
@@ -96,73 +234,373 @@ pub async fn add_item(
     Ok(())
 }
 
-fn clone_db_connection_details(client: &Client, table: &str) -> (Client, String) {
-    (client.clone(), table.to_string())
+fn clone_db_connection_details<W: ItemWriter>(writer: &W, table: &str) -> (W, String) {
+    (writer.clone(), table.to_string())
 }
 
-pub async fn add_portfolio_level_item(
-    client: &Client,
+pub async fn add_portfolio_level_item<W: ItemWriter>(
+    writer: &W,
     table: &str,
     item: PortfolioLevelItem,
 ) -> Result<(), AddItemError> {
     debug!("portfolio level item: {:#?}", item);
-    let input_attributes = HashMap::from(item);
-
-    let request = client
-        .put_item()
-        .table_name(table)
-        .set_item(Some(input_attributes));
-
-    let resp = request.send().await?;
-    debug!(
-        "dynamodb add_portfolio_level_item using HashMap from putItem with set_item response is response: {:#?}",
-        resp
-    );
-
-    Ok(())
+    writer.put_item(table, HashMap::from(item)).await
 }
 
-pub async fn add_bank_level_items(
-    client: &Client,
+pub async fn add_bank_level_items<W: ItemWriter>(
+    writer: &W,
     table: &str,
     wrapper: BankLevelItemsWrapper,
+    batch_write: &BatchWriteConfig,
+    permits: Arc<Semaphore>,
 ) -> Result<(), AddItemError> {
-    for item in wrapper.items {
-        let input_attributes = HashMap::from(item);
-        let request = client
-            .put_item()
-            .table_name(table)
-            .set_item(Some(input_attributes));
-
-        let resp = request.send().await?;
-        debug!(
-            "add_banks_level_item using HashMap from PutItemOutput is: {:#?}",
-            resp
-        );
-    }
-
-    Ok(())
+    let items = wrapper.items.into_iter().map(HashMap::from).collect();
+    batch_write_items(writer, table, items, batch_write, permits).await
 }
 
-pub async fn add_deposit_level_items(
-    client: &Client,
+pub async fn add_deposit_level_items<W: ItemWriter>(
+    writer: &W,
     table: &str,
     wrapper: DepositLevelItemsWrapper,
+    batch_write: &BatchWriteConfig,
+    permits: Arc<Semaphore>,
 ) -> Result<(), AddItemError> {
     debug!("banks deposits level wrapper: {:#?}", wrapper);
-    for item in wrapper.deposit_level_items {
-        let input_attributes = HashMap::from(item);
+    let items = wrapper
+        .deposit_level_items
+        .into_iter()
+        .map(HashMap::from)
+        .collect();
+    batch_write_items(writer, table, items, batch_write, permits).await
+}
 
-        let request = client
-            .put_item()
-            .table_name(table)
-            .set_item(Some(input_attributes));
+// each chunk holds one of the permits while in flight
+#[instrument(skip(writer, items, batch_write, permits), fields(items = items.len()))]
+pub async fn batch_write_items<W: ItemWriter>(
+    writer: &W,
+    table: &str,
+    items: Vec<HashMap<String, AttributeValue>>,
+    batch_write: &BatchWriteConfig,
+    permits: Arc<Semaphore>,
+) -> Result<(), AddItemError> {
+    let deadline = Instant::now() + batch_write.deadline;
+    let mut items = items.into_iter();
+    // dropping the join set on an error aborts the chunks still in flight
+    let mut join_set = JoinSet::new();
+    loop {
+        let write_requests = items
+            .by_ref()
+            .take(BATCH_WRITE_MAX_ITEMS)
+            .map(|item| {
+                Ok(WriteRequest::builder()
+                    .put_request(PutRequest::builder().set_item(Some(item)).build()?)
+                    .build())
+            })
+            .collect::<Result<Vec<WriteRequest>, AddItemError>>()?;
+        if write_requests.is_empty() {
+            break;
+        }
+        while let Some(result) = join_set.try_join_next() {
+            result??;
+        }
+        let permit = permits.clone().acquire_owned().await?;
+        let (writer_clone, table_clone) = clone_db_connection_details(writer, table);
+        join_set.spawn(async move {
+            let result = write_chunk(writer_clone, table_clone, write_requests, deadline).await;
+            drop(permit);
+            result
+        });
+    }
+    while let Some(result) = join_set.join_next().await {
+        result??;
+    }
+    Ok(())
+}
 
-        let resp = request.send().await?;
-        debug!(
-            "add_bank_deposits_level_item using HashMap from PutItemOutput is: {:#?}",
-            resp
+// one BatchWriteItem request, then its UnprocessedItems again until none are left
+async fn write_chunk<W: ItemWriter>(
+    writer: W,
+    table: String,
+    mut write_requests: Vec<WriteRequest>,
+    deadline: Instant,
+) -> Result<(), AddItemError> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        write_requests = writer.batch_write_item(&table, write_requests).await?;
+        if write_requests.is_empty() {
+            debug!("batch write chunk done after {} attempts", attempts);
+            return Ok(());
+        }
+        let backoff = jittered_backoff(attempts);
+        if Instant::now() + backoff >= deadline {
+            return Err(AddItemError::UnprocessedItems {
+                table,
+                remaining: write_requests.len(),
+                attempts,
+            });
+        }
+        warn!(
+            "{} items unprocessed after BatchWriteItem attempt {}, retrying in {:?}",
+            write_requests.len(),
+            attempts,
+            backoff
         );
+        sleep(backoff).await;
+    }
+}
+
+// exponential and capped, then somewhere between half and all of it so chunks throttled together
+// do not retry together
+fn jittered_backoff(attempts: u32) -> Duration {
+    let backoff = BATCH_WRITE_INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(BATCH_WRITE_MAX_BACKOFF);
+    backoff / 2 + backoff.mul_f64(random_fraction() / 2.0)
+}
+
+// from std's randomly keyed hasher, which is enough for jitter without a rand dependency
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    const TABLE_NAME: &str = "drive-deposits-test";
+
+    #[derive(Debug, Default)]
+    struct Recorded {
+        put_items: usize,
+        // size of every BatchWriteItem request, retries included
+        batch_sizes: Vec<usize>,
+        in_flight: usize,
+        max_in_flight: usize,
+    }
+
+    // stands in for DynamoDB, each BatchWriteItem taking a while so chunks overlap
+    #[derive(Debug, Clone, Default)]
+    struct RecordingWriter {
+        recorded: Arc<Mutex<Recorded>>,
+        // how many items successive BatchWriteItem requests leave unprocessed, then none
+        unprocessed: Arc<Mutex<VecDeque<usize>>>,
+        never_processes: bool,
+    }
+
+    impl RecordingWriter {
+        fn leaving_unprocessed(unprocessed: Vec<usize>) -> Self {
+            Self {
+                unprocessed: Arc::new(Mutex::new(unprocessed.into())),
+                ..Self::default()
+            }
+        }
+
+        fn recorded(&self) -> std::sync::MutexGuard<'_, Recorded> {
+            self.recorded.lock().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl ItemWriter for RecordingWriter {
+        async fn put_item(
+            &self,
+            _table: &str,
+            _item: HashMap<String, AttributeValue>,
+        ) -> Result<(), AddItemError> {
+            self.recorded().put_items += 1;
+            Ok(())
+        }
+
+        async fn batch_write_item(
+            &self,
+            table: &str,
+            mut write_requests: Vec<WriteRequest>,
+        ) -> Result<Vec<WriteRequest>, AddItemError> {
+            assert_eq!(table, TABLE_NAME);
+            {
+                let mut recorded = self.recorded();
+                recorded.batch_sizes.push(write_requests.len());
+                recorded.in_flight += 1;
+                recorded.max_in_flight = recorded.max_in_flight.max(recorded.in_flight);
+            }
+            sleep(Duration::from_millis(10)).await;
+            self.recorded().in_flight -= 1;
+            if self.never_processes {
+                return Ok(write_requests);
+            }
+            let unprocessed = self.unprocessed.lock().unwrap().pop_front().unwrap_or(0);
+            Ok(write_requests.split_off(write_requests.len() - unprocessed))
+        }
+    }
+
+    fn items(count: usize) -> Vec<HashMap<String, AttributeValue>> {
+        (0..count)
+            .map(|i| {
+                HashMap::from([
+                    (
+                        "PK".to_string(),
+                        AttributeValue::S("PORTFOLIO#1".to_string()),
+                    ),
+                    ("SK".to_string(), AttributeValue::S(format!("ITEM#{i:03}"))),
+                ])
+            })
+            .collect()
+    }
+
+    fn permits(max_concurrency: usize) -> Arc<Semaphore> {
+        Arc::new(Semaphore::new(max_concurrency))
+    }
+
+    // one bank with the given number of deposits
+    fn portfolio(deposits: usize) -> CalculatePortfolioResponse {
+        let outcome = json!({
+            "delta": {"period": "1", "period_unit": "Month", "growth": "2.00"},
+            "maturity": {"amount": "1000", "interest": "24.00", "total": "1024.00"},
+            "errors": []
+        });
+        let deposits = (0..deposits)
+            .map(|i| {
+                json!({
+                    "uuid": format!("31111111-1111-4111-8111-111111111{i:03}"),
+                    "account": "1234",
+                    "account_type": "Savings",
+                    "apy": "2.4",
+                    "years": "1",
+                    "outcome": outcome,
+                    "outcome_with_dates": {
+                        "start_date_in_bank_tz": "2024-01-10",
+                        "maturity_date_in_bank_tz": "2025-01-10",
+                        "errors": []
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(json!({
+            "uuid": "11111111-1111-4111-8111-111111111111",
+            "banks": [{
+                "uuid": "21111111-1111-4111-8111-111111111111",
+                "name": "VISION-BANK",
+                "bank_tz": "America/New_York",
+                "deposits": deposits,
+                "outcome": outcome
+            }],
+            "outcome": outcome,
+            "created_at": "2024-01-10T15:30:00.000000Z",
+            "tenant_id": "tenant-a"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_write_items_in_chunks_of_25() {
+        let writer = RecordingWriter::default();
+
+        batch_write_items(
+            &writer,
+            TABLE_NAME,
+            items(60),
+            &BatchWriteConfig::default(),
+            permits(4),
+        )
+        .await
+        .unwrap();
+
+        let mut batch_sizes = writer.recorded().batch_sizes.clone();
+        batch_sizes.sort();
+        assert_eq!(batch_sizes, vec![10, 25, 25]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_write_items_bounded_by_max_concurrency() {
+        let writer = RecordingWriter::default();
+
+        batch_write_items(
+            &writer,
+            TABLE_NAME,
+            items(200),
+            &BatchWriteConfig::default(),
+            permits(3),
+        )
+        .await
+        .unwrap();
+
+        let recorded = writer.recorded();
+        assert_eq!(recorded.batch_sizes.len(), 8);
+        assert_eq!(recorded.max_in_flight, 3);
+    }
+
+    #[tokio::test]
+    async fn test_add_item_writers_share_max_concurrency() {
+        let writer = RecordingWriter::default();
+        let batch_write = BatchWriteConfig {
+            max_concurrency: 2,
+            ..BatchWriteConfig::default()
+        };
+
+        add_item(&writer, TABLE_NAME, portfolio(30), &batch_write)
+            .await
+            .unwrap();
+
+        let recorded = writer.recorded();
+        assert_eq!(recorded.put_items, 1);
+        // the bank level item, then 30 deposit level items for each sort criteria
+        let mut batch_sizes = recorded.batch_sizes.clone();
+        batch_sizes.sort();
+        assert_eq!(batch_sizes, vec![1, 5, 5, 25, 25]);
+        assert_eq!(recorded.max_in_flight, 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_write_items_retries_unprocessed_items() {
+        let writer = RecordingWriter::leaving_unprocessed(vec![7, 2]);
+
+        batch_write_items(
+            &writer,
+            TABLE_NAME,
+            items(20),
+            &BatchWriteConfig::default(),
+            permits(4),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(writer.recorded().batch_sizes, vec![20, 7, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_write_items_unprocessed_at_deadline() {
+        let writer = RecordingWriter {
+            never_processes: true,
+            ..RecordingWriter::default()
+        };
+        let batch_write = BatchWriteConfig {
+            max_concurrency: 4,
+            deadline: Duration::from_millis(300),
+        };
+
+        let err = batch_write_items(&writer, TABLE_NAME, items(20), &batch_write, permits(4))
+            .await
+            .unwrap_err();
+
+        let AddItemError::UnprocessedItems {
+            table,
+            remaining,
+            attempts,
+        } = err
+        else {
+            panic!("expected unprocessed items at the deadline, got {:?}", err);
+        };
+        assert_eq!(table, TABLE_NAME);
+        assert_eq!(remaining, 20);
+        assert_eq!(attempts as usize, writer.recorded().batch_sizes.len());
+        assert!(attempts > 1);
     }
-    Ok(())
 }
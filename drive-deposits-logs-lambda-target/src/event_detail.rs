@@ -0,0 +1,363 @@
+use serde::de::DeserializeOwned;
+use serde_json::{from_slice, from_value, Value};
+use thiserror::Error;
+use tracing::debug;
+
+use drive_deposits_event_source::object_store::{ObjectStore, ObjectStoreError};
+use drive_deposits_event_source::schema::{validate_payload, SchemaValidationError};
+use drive_deposits_rest_types::cloud_event::{
+    data_version, CloudEvent, DATA_VERSION, SPEC_VERSION,
+};
+
+#[derive(Debug, Error)]
+pub enum EventDetailError {
+    #[error("Unsupported CloudEvents specversion {0}; expected {SPEC_VERSION}")]
+    UnsupportedSpecVersion(Value),
+
+    #[error("Unsupported event type {0:?}; expected data version v{DATA_VERSION}")]
+    UnsupportedEventType(String),
+
+    #[error("Event detail does not match the payload type: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Event {0} has neither data nor dataref")]
+    MissingData(String),
+
+    #[error("Event data is stored at {0} but EVENT_CLAIM_CHECK_STORE is not set")]
+    MissingObjectStore(String),
+
+    #[error("Event data could not be fetched: {0}")]
+    ObjectStore(#[from] ObjectStoreError),
+
+    #[error(transparent)]
+    SchemaValidation(#[from] SchemaValidationError),
+}
+
+// The EventBridge detail is a CloudEvents envelope around the payload, or the bare payload from
+// publishers that predate the envelope; both are accepted while publishers migrate
+#[derive(Debug)]
+pub enum EventDetail<T> {
+    CloudEvent(Box<CloudEvent<T>>),
+    Unversioned(T),
+}
+
+impl<T: DeserializeOwned> EventDetail<T> {
+    pub fn from_detail(detail: Value) -> Result<Self, EventDetailError> {
+        match detail.get("specversion") {
+            None => Ok(Self::Unversioned(from_value(detail)?)),
+            Some(spec_version) if spec_version == SPEC_VERSION => {
+                let cloud_event: CloudEvent<T> = from_value(detail)?;
+                // a newer data version may not fit this writer's payload types; the detail-type
+                // is not compared, since publishers can prefix it
+                if data_version(&cloud_event.event_type) != Some(DATA_VERSION) {
+                    return Err(EventDetailError::UnsupportedEventType(
+                        cloud_event.event_type,
+                    ));
+                }
+                Ok(Self::CloudEvent(Box::new(cloud_event)))
+            }
+            Some(spec_version) => Err(EventDetailError::UnsupportedSpecVersion(
+                spec_version.clone(),
+            )),
+        }
+    }
+
+    // the payload, fetched from the object store when the event only carries a dataref to it
+    pub async fn hydrate(
+        self,
+        object_store: Option<&dyn ObjectStore>,
+    ) -> Result<T, EventDetailError> {
+        let cloud_event = match self {
+            Self::CloudEvent(cloud_event) => *cloud_event,
+            Self::Unversioned(data) => return Ok(data),
+        };
+        match (cloud_event.data, cloud_event.dataref) {
+            (Some(data), _) => Ok(data),
+            (None, Some(dataref)) => {
+                let object_store = object_store
+                    .ok_or_else(|| EventDetailError::MissingObjectStore(dataref.clone()))?;
+                debug!("event {} data fetched from {}", cloud_event.id, dataref);
+                Ok(from_slice(&object_store.get(&dataref).await?)?)
+            }
+            (None, None) => Err(EventDetailError::MissingData(cloud_event.id)),
+        }
+    }
+}
+
+impl<T> EventDetail<T> {
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            Self::CloudEvent(cloud_event) => Some(cloud_event.correlationid.as_str()),
+            Self::Unversioned(_) => None,
+        }
+    }
+}
+
+// the payload of a detail_type event, checked against its JSON Schema before it is read into T, so
+// a mismatch names every offending field instead of the first serde error
+pub async fn hydrate_validated<T: DeserializeOwned>(
+    detail_type: &str,
+    detail: Value,
+    object_store: Option<&dyn ObjectStore>,
+) -> Result<(T, Option<String>), EventDetailError> {
+    let event_detail = EventDetail::<Value>::from_detail(detail)?;
+    let correlation_id = event_detail.correlation_id().map(str::to_string);
+    let data = event_detail.hydrate(object_store).await?;
+    validate_payload(detail_type, &data)?;
+    Ok((from_value(data)?, correlation_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+    use serde_json::{json, to_value};
+
+    use drive_deposits_event_source::object_store::FileObjectStore;
+    use drive_deposits_event_source::payload_types::{Bank, Deposit};
+    use drive_deposits_event_source::publisher::{BANK_LEVEL, EVENT_SOURCE};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Payload {
+        name: String,
+    }
+
+    fn cloud_event(data: Value) -> Value {
+        to_value(CloudEvent::new(
+            "event-1".to_string(),
+            EVENT_SOURCE,
+            BANK_LEVEL,
+            "portfolio-1".to_string(),
+            "correlation-1".to_string(),
+            data,
+        ))
+        .unwrap()
+    }
+
+    // the envelope of an event whose data was claim checked at dataref
+    fn claim_checked(dataref: Option<&str>) -> Value {
+        let mut detail = cloud_event(json!(null));
+        detail.as_object_mut().unwrap().remove("data");
+        if let Some(dataref) = dataref {
+            detail["dataref"] = json!(dataref);
+        }
+        detail
+    }
+
+    fn store(name: &str) -> FileObjectStore {
+        let dir = std::env::temp_dir().join(format!("drive-deposits-writer-hydrate-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        FileObjectStore::new(dir)
+    }
+
+    #[test]
+    fn test_from_detail_bare_payload() {
+        let event_detail =
+            EventDetail::<Payload>::from_detail(json!({"name": "VISION-BANK"})).unwrap();
+
+        assert_eq!(event_detail.correlation_id(), None);
+        assert!(matches!(
+            event_detail,
+            EventDetail::Unversioned(Payload { name }) if name == "VISION-BANK"
+        ));
+    }
+
+    #[test]
+    fn test_from_detail_v1_envelope() {
+        let event_detail =
+            EventDetail::<Payload>::from_detail(cloud_event(json!({"name": "VISION-BANK"})))
+                .unwrap();
+
+        assert_eq!(event_detail.correlation_id(), Some("correlation-1"));
+        let EventDetail::CloudEvent(cloud_event) = event_detail else {
+            panic!("expected a CloudEvent");
+        };
+        assert_eq!(cloud_event.event_type, "drive-deposits.bank-level.v1");
+        assert_eq!(
+            cloud_event.data,
+            Some(Payload {
+                name: "VISION-BANK".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_detail_rejects_unsupported_spec_version() {
+        let mut detail = cloud_event(json!({"name": "VISION-BANK"}));
+        detail["specversion"] = json!("0.3");
+
+        let err = EventDetail::<Payload>::from_detail(detail).unwrap_err();
+
+        assert!(matches!(
+            err,
+            EventDetailError::UnsupportedSpecVersion(spec_version) if spec_version == "0.3"
+        ));
+    }
+
+    #[test]
+    fn test_from_detail_rejects_unsupported_data_version() {
+        let mut detail = cloud_event(json!({"name": "VISION-BANK"}));
+        detail["type"] = json!("drive-deposits.bank-level.v2");
+
+        let err = EventDetail::<Payload>::from_detail(detail).unwrap_err();
+
+        assert!(matches!(
+            err,
+            EventDetailError::UnsupportedEventType(event_type)
+                if event_type == "drive-deposits.bank-level.v2"
+        ));
+    }
+
+    #[test]
+    fn test_from_detail_bare_payload_of_another_type() {
+        let err = EventDetail::<Payload>::from_detail(json!({"banks": []})).unwrap_err();
+
+        assert!(matches!(err, EventDetailError::Json(_)));
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_inline_data_without_object_store() {
+        let event_detail =
+            EventDetail::<Payload>::from_detail(cloud_event(json!({"name": "VISION-BANK"})))
+                .unwrap();
+
+        assert_eq!(
+            event_detail.hydrate(None).await.unwrap(),
+            Payload {
+                name: "VISION-BANK".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_fetches_data_through_dataref() {
+        let store = store("dataref");
+        let dataref = store
+            .put(
+                "bank-level/event-1.json",
+                json!({"name": "VISION-BANK"}).to_string().into_bytes(),
+            )
+            .await
+            .unwrap();
+        let event_detail =
+            EventDetail::<Payload>::from_detail(claim_checked(Some(&dataref))).unwrap();
+
+        assert_eq!(
+            event_detail.hydrate(Some(&store)).await.unwrap(),
+            Payload {
+                name: "VISION-BANK".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_dataref_without_object_store() {
+        let event_detail = EventDetail::<Payload>::from_detail(claim_checked(Some(
+            "s3://drive-deposits-claim-check/bank-level/event-1.json",
+        )))
+        .unwrap();
+
+        let err = event_detail.hydrate(None).await.unwrap_err();
+
+        assert!(matches!(
+            err,
+            EventDetailError::MissingObjectStore(dataref)
+                if dataref == "s3://drive-deposits-claim-check/bank-level/event-1.json"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_without_data_or_dataref() {
+        let store = store("missing-data");
+        let event_detail = EventDetail::<Payload>::from_detail(claim_checked(None)).unwrap();
+
+        let err = event_detail.hydrate(Some(&store)).await.unwrap_err();
+
+        assert!(matches!(err, EventDetailError::MissingData(id) if id == "event-1"));
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_expired_dataref() {
+        let store = store("expired");
+        let dataref = store
+            .put("bank-level/event-1.json", b"{}".to_vec())
+            .await
+            .unwrap();
+        std::fs::remove_file(dataref.trim_start_matches("file://")).unwrap();
+        let event_detail =
+            EventDetail::<Payload>::from_detail(claim_checked(Some(&dataref))).unwrap();
+
+        let err = event_detail.hydrate(Some(&store)).await.unwrap_err();
+
+        assert!(matches!(err, EventDetailError::ObjectStore(_)));
+    }
+
+    // a bank-level payload with a missing field, a mistyped field and a mistyped nested field
+    fn non_matching_bank() -> Value {
+        let mut bank = to_value(Bank {
+            uuid: "bank-1".to_string(),
+            name: "VISION-BANK".to_string(),
+            bank_tz: "America/New_York".to_string(),
+            deposits: vec![Deposit {
+                uuid: "deposit-1".to_string(),
+                apy: "2.4".to_string(),
+                ..Deposit::default()
+            }],
+            ..Bank::default()
+        })
+        .unwrap();
+        bank.as_object_mut().unwrap().remove("uuid");
+        bank["name"] = json!(7);
+        bank["deposits"][0]["apy"] = json!(2.4);
+        bank
+    }
+
+    fn violated_paths(err: EventDetailError) -> Vec<String> {
+        let EventDetailError::SchemaValidation(err) = err else {
+            panic!("expected a schema validation error, got {:?}", err);
+        };
+        assert_eq!(err.detail_type, BANK_LEVEL);
+        let mut instance_paths = err
+            .violations
+            .into_iter()
+            .map(|violation| violation.instance_path)
+            .collect::<Vec<String>>();
+        instance_paths.sort();
+        instance_paths
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_validated_lists_every_violation_of_enveloped_payload() {
+        let err = hydrate_validated::<Bank>(BANK_LEVEL, cloud_event(non_matching_bank()), None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(violated_paths(err), vec!["", "/deposits/0/apy", "/name"]);
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_validated_lists_every_violation_of_bare_payload() {
+        let err = hydrate_validated::<Bank>(BANK_LEVEL, non_matching_bank(), None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(violated_paths(err), vec!["", "/deposits/0/apy", "/name"]);
+    }
+
+    #[tokio::test]
+    async fn test_hydrate_validated_reads_matching_payload() {
+        let mut bank = non_matching_bank();
+        bank["uuid"] = json!("bank-1");
+        bank["name"] = json!("VISION-BANK");
+        bank["deposits"][0]["apy"] = json!("2.4");
+
+        let (bank, correlation_id) = hydrate_validated::<Bank>(BANK_LEVEL, cloud_event(bank), None)
+            .await
+            .unwrap();
+
+        assert_eq!(bank.name, "VISION-BANK");
+        assert_eq!(correlation_id.as_deref(), Some("correlation-1"));
+    }
+}
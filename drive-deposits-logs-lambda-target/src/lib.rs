@@ -1 +1,3 @@
+pub mod by_level;
 pub mod dynamodb;
+pub mod event_detail;
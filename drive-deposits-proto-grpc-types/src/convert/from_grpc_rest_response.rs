@@ -2,17 +2,25 @@ use heck::ToUpperCamelCase;
 use tracing::{info, info_span};
 
 use drive_deposits_rest_types::rest_types::{
-    Bank as RestBank, CalculatePortfolioResponse as RestCalculatePortfolioResponse,
-    Delta as RestDelta, Deposit as RestDeposit, Maturity as RestMaturity, Outcome as RestOutcome,
-    OutcomeWithDates as RestOutcomeWithDates, ProcessingError as RestProcessingError,
+    field_path_to_pointer, Bank as RestBank,
+    CalculatePortfolioResponse as RestCalculatePortfolioResponse,
+    CalculatePortfoliosResponse as RestCalculatePortfoliosResponse,
+    CorrelatedPortfolioResponse as RestCorrelatedPortfolioResponse, Delta as RestDelta,
+    Deposit as RestDeposit, Maturity as RestMaturity, Outcome as RestOutcome,
+    OutcomeWithDates as RestOutcomeWithDates, PortfolioError as RestPortfolioError,
+    PortfolioSummary as RestPortfolioSummary, PortfolioViolation as RestPortfolioViolation,
+    ProcessingError as RestProcessingError,
 };
 
 use crate::generated::{
-    AccountType as GrpcAccountType, Bank as GrpcBank,
-    CalculatePortfolioResponse as GrpcCalculatePortfolioResponse, Delta as GrpcDelta,
+    correlated_portfolio_response::Result as CorrelatedResult, AccountType as GrpcAccountType,
+    Bank as GrpcBank, CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
+    CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+    CorrelatedPortfolioResponse as GrpcCorrelatedPortfolioResponse, Delta as GrpcDelta,
     Deposit as GrpcDeposit, Maturity as GrpcMaturity, Outcome as GrpcOutcome,
     OutcomeWithDates as GrpcOutcomeWithDates, PeriodUnit as GrpcPeriodUnit,
-    ProcessingError as GrpcProcessingError,
+    PortfolioError as GrpcPortfolioError, PortfolioSummary as GrpcPortfolioSummary,
+    PortfolioViolation as GrpcPortfolioViolation, ProcessingError as GrpcProcessingError,
 };
 
 impl From<GrpcProcessingError> for RestProcessingError {
@@ -89,6 +97,7 @@ impl From<GrpcBank> for RestBank {
             bank_tz: grpc.bank_tz.to_string(),
             deposits: grpc.deposits.into_iter().map(|x| x.into()).collect(),
             outcome: grpc.outcome.map(|x| x.into()),
+            tenant_id: None,
         }
     }
 }
@@ -100,6 +109,7 @@ impl From<GrpcCalculatePortfolioResponse> for RestCalculatePortfolioResponse {
             banks: grpc.banks.into_iter().map(|x| x.into()).collect(),
             created_at: grpc.created_at,
             outcome: grpc.outcome.map(|x| x.into()),
+            tenant_id: None,
         };
         info_span!("grpc_rest_response::From::grpc").in_scope(|| {
             info!(
@@ -110,3 +120,65 @@ impl From<GrpcCalculatePortfolioResponse> for RestCalculatePortfolioResponse {
         rest
     }
 }
+
+impl From<GrpcPortfolioSummary> for RestPortfolioSummary {
+    fn from(grpc: GrpcPortfolioSummary) -> Self {
+        Self {
+            uuid: grpc.uuid,
+            outcome: grpc.outcome.map(|x| x.into()),
+            created_at: grpc.created_at,
+            bank_count: grpc.bank_count,
+        }
+    }
+}
+
+impl From<GrpcPortfolioViolation> for RestPortfolioViolation {
+    fn from(grpc: GrpcPortfolioViolation) -> Self {
+        Self {
+            pointer: field_path_to_pointer(&grpc.field),
+            code: grpc.code,
+            message: grpc.description,
+        }
+    }
+}
+
+impl From<GrpcPortfolioError> for RestPortfolioError {
+    fn from(grpc: GrpcPortfolioError) -> Self {
+        Self {
+            code: format!("{:?}", tonic::Code::from_i32(grpc.code)),
+            message: grpc.message,
+            violations: grpc.violations.into_iter().map(|x| x.into()).collect(),
+        }
+    }
+}
+
+impl From<GrpcCorrelatedPortfolioResponse> for RestCorrelatedPortfolioResponse {
+    fn from(grpc: GrpcCorrelatedPortfolioResponse) -> Self {
+        let (portfolio_response, error) = match grpc.result {
+            Some(CorrelatedResult::PortfolioResponse(grpc_response)) => {
+                (Some(grpc_response.into()), None)
+            }
+            Some(CorrelatedResult::Error(grpc_error)) => (None, Some(grpc_error.into())),
+            None => (None, None),
+        };
+        Self {
+            correlation_id: grpc.correlation_id,
+            portfolio_response,
+            error,
+        }
+    }
+}
+
+impl From<GrpcCalculatePortfoliosResponse> for RestCalculatePortfoliosResponse {
+    fn from(grpc: GrpcCalculatePortfoliosResponse) -> Self {
+        Self {
+            portfolio_responses: grpc
+                .portfolio_responses
+                .into_iter()
+                .map(|x| x.into())
+                .collect(),
+            succeeded: grpc.succeeded,
+            failed: grpc.failed,
+        }
+    }
+}
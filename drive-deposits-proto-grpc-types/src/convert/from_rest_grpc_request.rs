@@ -1,64 +1,141 @@
 use heck::ToShoutySnakeCase;
+use thiserror::Error;
 use tracing::{debug, info, info_span};
 
 use drive_deposits_rest_types::rest_types::{
-    CalculatePortfolioRequest as RestCalculatePortfolioRequest, NewBank as RestNewBank,
+    CalculatePortfolioRequest as RestCalculatePortfolioRequest,
+    CorrelatedPortfolioRequest as RestCorrelatedPortfolioRequest, NewBank as RestNewBank,
     NewDelta as RestNewDelta, NewDeposit as RestNewDeposit,
 };
 
 use crate::generated::{
     AccountType as GrpcAccountType, CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
-    NewBank as GrpcNewBank, NewDelta as GrpcNewDelta, NewDeposit as GrpcNewDeposit,
-    PeriodUnit as GrpcPeriodUnit,
+    CorrelatedPortfolioRequest as GrpcCorrelatedPortfolioRequest, NewBank as GrpcNewBank,
+    NewDelta as GrpcNewDelta, NewDeposit as GrpcNewDeposit, PeriodUnit as GrpcPeriodUnit,
 };
 
-impl From<RestNewDeposit> for GrpcNewDeposit {
-    fn from(rest: RestNewDeposit) -> Self {
+// An account_type or period_unit the grpc enums have no specified variant for; the rest validators
+// catch these first, so only requests that skipped them get here
+#[derive(Debug, Error)]
+#[error("{field}: {message}")]
+pub struct Error {
+    // full path to the field, for example new_banks[0].new_deposits[1].account_type
+    pub field: String,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Error {
+    fn prefixed(self, prefix: &str) -> Self {
+        Self {
+            field: format!("{}.{}", prefix, self.field),
+            ..self
+        }
+    }
+}
+
+impl TryFrom<RestNewDeposit> for GrpcNewDeposit {
+    type Error = Error;
+
+    fn try_from(rest: RestNewDeposit) -> Result<Self, Self::Error> {
         debug!(
             "rest.account_type.to_shouty_snake_case() is {}",
             rest.account_type.to_shouty_snake_case()
         );
-        GrpcNewDeposit {
+        let account_type =
+            GrpcAccountType::from_str_name(&rest.account_type.to_shouty_snake_case())
+                .filter(|account_type| *account_type != GrpcAccountType::Unspecified)
+                .ok_or_else(|| Error {
+                    field: "account_type".to_string(),
+                    code: "invalid_account_type",
+                    message: format!(
+                        "Incorrect account_type: {}. Must be Checking, Savings, CertificateOfDeposit, or BrokerageCertificateOfDeposit.",
+                        rest.account_type
+                    ),
+                })?;
+        Ok(GrpcNewDeposit {
             account: rest.account,
-            account_type: GrpcAccountType::from_str_name(&rest.account_type.to_shouty_snake_case())
-                .unwrap_or_default() as i32,
+            account_type: account_type as i32,
             apy: rest.apy,
             years: rest.years,
             amount: rest.amount,
             start_date_in_bank_tz: rest.start_date_in_bank_tz.to_string(),
-        }
+        })
     }
 }
 
-impl From<RestNewBank> for GrpcNewBank {
-    fn from(rest: RestNewBank) -> Self {
-        Self {
+impl TryFrom<RestNewBank> for GrpcNewBank {
+    type Error = Error;
+
+    fn try_from(rest: RestNewBank) -> Result<Self, Self::Error> {
+        Ok(Self {
             name: rest.name,
             bank_tz: rest.bank_tz.to_string(),
-            new_deposits: rest.new_deposits.into_iter().map(|x| x.into()).collect(),
-        }
+            new_deposits: rest
+                .new_deposits
+                .into_iter()
+                .enumerate()
+                .map(|(index, new_deposit)| {
+                    GrpcNewDeposit::try_from(new_deposit)
+                        .map_err(|err| err.prefixed(&format!("new_deposits[{}]", index)))
+                })
+                .collect::<Result<Vec<GrpcNewDeposit>, Error>>()?,
+        })
     }
 }
 
-impl From<RestNewDelta> for GrpcNewDelta {
-    fn from(rest: RestNewDelta) -> Self {
-        Self {
+impl TryFrom<RestNewDelta> for GrpcNewDelta {
+    type Error = Error;
+
+    fn try_from(rest: RestNewDelta) -> Result<Self, Self::Error> {
+        let period_unit = GrpcPeriodUnit::from_str_name(&rest.period_unit.to_shouty_snake_case())
+            .filter(|period_unit| *period_unit != GrpcPeriodUnit::Unspecified)
+            .ok_or_else(|| Error {
+                field: "period_unit".to_string(),
+                code: "invalid_period_unit",
+                message: format!(
+                    "Incorrect period_unit: {}. Must be Day, Week, Month, or Year.",
+                    rest.period_unit
+                ),
+            })?;
+        Ok(Self {
             period: rest.period,
-            period_unit: GrpcPeriodUnit::from_str_name(&rest.period_unit.to_shouty_snake_case())
-                .unwrap_or_default() as i32,
-        }
+            period_unit: period_unit as i32,
+        })
     }
 }
-impl From<RestCalculatePortfolioRequest> for GrpcCalculatePortfolioRequest {
-    fn from(rest: RestCalculatePortfolioRequest) -> Self {
-        // 44 |         let grpc_new_delta = rest.new_delta.into();
-        //    |                                             ^^^^ the trait `From<drive_deposits_io_types::io_types::NewDelta>` is not implemented for `generated::NewDelta`, which is required by `drive_deposits_io_types::io_types::NewDelta: Into<_>`
+
+impl TryFrom<RestCalculatePortfolioRequest> for GrpcCalculatePortfolioRequest {
+    type Error = Error;
+
+    fn try_from(rest: RestCalculatePortfolioRequest) -> Result<Self, Self::Error> {
         let grpc = Self {
-            new_banks: rest.new_banks.into_iter().map(|x| x.into()).collect(),
-            new_delta: Some(rest.new_delta.into()),
+            new_banks: rest
+                .new_banks
+                .into_iter()
+                .enumerate()
+                .map(|(index, new_bank)| {
+                    GrpcNewBank::try_from(new_bank)
+                        .map_err(|err| err.prefixed(&format!("new_banks[{}]", index)))
+                })
+                .collect::<Result<Vec<GrpcNewBank>, Error>>()?,
+            new_delta: Some(
+                GrpcNewDelta::try_from(rest.new_delta).map_err(|err| err.prefixed("new_delta"))?,
+            ),
         };
-        info_span!("rest_grpc_request::From::rest")
+        info_span!("rest_grpc_request::TryFrom::rest")
             .in_scope(|| info!("rest request converted to grpc request: {:?}", grpc));
-        grpc
+        Ok(grpc)
+    }
+}
+
+impl TryFrom<RestCorrelatedPortfolioRequest> for GrpcCorrelatedPortfolioRequest {
+    type Error = Error;
+
+    fn try_from(rest: RestCorrelatedPortfolioRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            correlation_id: rest.correlation_id,
+            portfolio_request: Some(rest.portfolio_request.try_into()?),
+        })
     }
 }
@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tonic::metadata::MetadataMap;
+use tracing::{debug, info};
+
+use drive_deposits_rest_types::auth::{
+    Authenticator, Error as AuthError, TenantId, API_KEY_HEADER, AUTHORIZATION_HEADER,
+    CORRELATION_ID_HEADER,
+};
+
+use crate::drive_deposits_client::ProblemDetails;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Unauthenticated(#[from] AuthError),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        info!("authentication failed: {}", self);
+        let mut response =
+            ProblemDetails::new(StatusCode::UNAUTHORIZED, self.to_string()).into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        response
+    }
+}
+
+// Who a calculate request is for; put in the request extensions by authenticate
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+    pub tenant_id: TenantId,
+    // the caller's own credentials, forwarded so the grpc server authenticates the call again,
+    // along with the correlation id for its events
+    credentials: MetadataMap,
+}
+
+impl Caller {
+    // credentials as metadata for the grpc server, and the tenant as an extension for the in
+    // process calculator, which has no interceptor in front of it
+    pub fn grpc_request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        *request.metadata_mut() = self.credentials.clone();
+        request.extensions_mut().insert(self.tenant_id.clone());
+        request
+    }
+}
+
+pub async fn authenticate(
+    State(authenticator): State<Authenticator>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let caller = caller(&authenticator, request.headers())?;
+    debug!("request authenticated for tenant {}", caller.tenant_id);
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
+
+fn caller(authenticator: &Authenticator, headers: &HeaderMap) -> Result<Caller, AuthError> {
+    let authorization = header_str(headers, AUTHORIZATION_HEADER);
+    let api_key = header_str(headers, API_KEY_HEADER);
+    let tenant_id = authenticator.authenticate(authorization, api_key)?;
+
+    let mut credentials = MetadataMap::new();
+    for name in [AUTHORIZATION_HEADER, API_KEY_HEADER, CORRELATION_ID_HEADER] {
+        if let Some(value) = header_str(headers, name).and_then(|value| value.parse().ok()) {
+            credentials.insert(name, value);
+        }
+    }
+    Ok(Caller {
+        tenant_id,
+        credentials,
+    })
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        middleware::from_fn_with_state,
+        routing::get,
+        Extension, Router,
+    };
+    use pretty_assertions::assert_eq;
+    use tower::ServiceExt;
+
+    use drive_deposits_rest_types::auth::{
+        encode_token, Authenticator, Claims, TenantId, API_KEY_HEADER, AUTHORIZATION_HEADER,
+    };
+
+    use super::{authenticate, Caller};
+
+    const JWT_SECRET: &str = "test-jwt-secret";
+
+    fn app() -> Router {
+        let api_keys = HashMap::from([(
+            "tenant-b-key".to_string(),
+            "tenant-b".parse::<TenantId>().unwrap(),
+        )]);
+        let authenticator = Authenticator::new(Some(JWT_SECRET), api_keys);
+        // echoes what a handler would forward to the grpc server
+        let echo_caller = |Extension(caller): Extension<Caller>| async move {
+            let grpc_request = caller.grpc_request(());
+            let forwarded = grpc_request.metadata().get(AUTHORIZATION_HEADER).is_some()
+                || grpc_request.metadata().get(API_KEY_HEADER).is_some();
+            let tenant_id = grpc_request.extensions().get::<TenantId>().unwrap();
+            format!("{} {}", tenant_id, forwarded)
+        };
+        Router::new()
+            .route("/calculate", get(echo_caller))
+            .route_layer(from_fn_with_state(authenticator, authenticate))
+    }
+
+    async fn call(request: Request<Body>) -> (StatusCode, String) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_missing_credentials_is_unauthorized_problem() {
+        let response = app()
+            .oneshot(Request::get("/calculate").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_tenant_is_forwarded() {
+        let claims = Claims {
+            sub: "client-1".to_string(),
+            tenant_id: "tenant-a".to_string(),
+            exp: 4_102_444_800,
+        };
+        let token = encode_token(JWT_SECRET, &claims).unwrap();
+        let request = Request::get("/calculate")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(
+            call(request).await,
+            (StatusCode::OK, "tenant-a true".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_key_tenant_and_rejected_credentials() {
+        let request = Request::get("/calculate")
+            .header(API_KEY_HEADER, "tenant-b-key")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            call(request).await,
+            (StatusCode::OK, "tenant-b true".to_string())
+        );
+
+        let request = Request::get("/calculate")
+            .header(API_KEY_HEADER, "unknown-key")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(request).await.0, StatusCode::UNAUTHORIZED);
+
+        let expired = Claims {
+            sub: "client-1".to_string(),
+            tenant_id: "tenant-a".to_string(),
+            exp: 1,
+        };
+        let request = Request::get("/calculate")
+            .header(
+                header::AUTHORIZATION,
+                format!("Bearer {}", encode_token(JWT_SECRET, &expired).unwrap()),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = call(request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("ExpiredSignature"));
+    }
+}
@@ -0,0 +1,440 @@
+use std::env::var;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio_util::task::TaskTracker;
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use drive_deposits_cal_types::math::engine::EventOptions;
+use drive_deposits_event_source::publisher::{create_publisher, SharedEventPublisher};
+use drive_deposits_grpc_server::portfolio::DriveDepositsCalculator;
+use drive_deposits_proto_grpc_types::generated::{
+    drive_deposits_service_server::DriveDepositsService,
+    CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
+    CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
+    CalculatePortfoliosRequest as GrpcCalculatePortfoliosRequest,
+    CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+};
+use drive_deposits_rest_types::server_config::{Error as ServerConfigError, EventPublisherConfig};
+
+use crate::drive_deposits_client::{
+    CalculatePortfolioClient, CalculatePortfolioStreamClient, CalculatePortfoliosClient,
+    GrpcStreamResponses,
+};
+use crate::grpc_channel::{DriveDepositsGrpcClient, Error as GrpcChannelError};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid CALCULATION_MODE {0:?}; must be grpc or in_process")]
+    InvalidMode(String),
+    #[error("Grpc channel error: {0}")]
+    GrpcChannel(#[from] GrpcChannelError),
+    #[error("Drive Deposits event publisher error: {0}")]
+    EventPublisherError(#[from] drive_deposits_event_source::publisher::EventPublisherError),
+    #[error("Drive Deposits event publisher config error: {0}")]
+    EventPublisherConfig(#[from] ServerConfigError),
+}
+
+// Calls the same DriveDepositsService implementation the grpc server exposes, without the
+// network hop, so request conversion, error statuses and the JSON output are identical to grpc
+// mode. Events go to the EVENT_PUBLISHER as usual.
+#[derive(Clone)]
+pub struct InProcessCalculator {
+    calculator: Arc<DriveDepositsCalculator>,
+}
+
+impl InProcessCalculator {
+    pub fn new(
+        event_publisher: Option<SharedEventPublisher>,
+        event_options: EventOptions,
+        calculations: TaskTracker,
+    ) -> Self {
+        Self {
+            calculator: Arc::new(DriveDepositsCalculator {
+                event_publisher,
+                event_options,
+                calculations,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl CalculatePortfolioClient for InProcessCalculator {
+    async fn calculate_portfolio_request(
+        mut self,
+        request: Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<Response<GrpcCalculatePortfolioResponse>, Status> {
+        self.calculator.calculate_portfolio(request).await
+    }
+}
+
+#[async_trait]
+impl CalculatePortfoliosClient for InProcessCalculator {
+    async fn calculate_portfolios_request(
+        mut self,
+        request: Request<GrpcCalculatePortfoliosRequest>,
+    ) -> Result<Response<GrpcCalculatePortfoliosResponse>, Status> {
+        self.calculator.calculate_portfolios(request).await
+    }
+}
+
+#[async_trait]
+impl CalculatePortfolioStreamClient for InProcessCalculator {
+    async fn calculate_portfolio_stream_request(
+        mut self,
+        request: Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<GrpcStreamResponses, Status> {
+        let streaming = self
+            .calculator
+            .calculate_portfolio_stream(request)
+            .await?
+            .into_inner();
+        Ok(Box::pin(streaming))
+    }
+}
+
+// What the handlers calculate with, kept in axum State; chosen by CALCULATION_MODE
+#[derive(Clone)]
+pub enum CalculationBackend {
+    // boxed, the channel and its settings are much larger than the in-process calculator
+    Grpc(Box<DriveDepositsGrpcClient>),
+    InProcess(InProcessCalculator),
+}
+
+impl CalculationBackend {
+    // calculations only tracks in-process work; in grpc mode the grpc server drains its own
+    pub async fn from_env(calculations: TaskTracker) -> Result<Self, Error> {
+        let calculation_mode = var("CALCULATION_MODE").unwrap_or_else(|_| "grpc".to_string());
+        info!(
+            "calculation_mode based on CALCULATION_MODE: {}",
+            calculation_mode
+        );
+        match calculation_mode.trim().to_lowercase().as_str() {
+            "grpc" => Ok(Self::Grpc(Box::new(DriveDepositsGrpcClient::from_env()?))),
+            "in_process" => {
+                let event_publisher = create_publisher().await?;
+                let event_options = EventOptions::from(&EventPublisherConfig::load()?);
+                Ok(Self::InProcess(InProcessCalculator::new(
+                    event_publisher,
+                    event_options,
+                    calculations,
+                )))
+            }
+            _ => Err(Error::InvalidMode(calculation_mode)),
+        }
+    }
+}
+
+#[async_trait]
+impl CalculatePortfolioClient for CalculationBackend {
+    async fn calculate_portfolio_request(
+        mut self,
+        request: Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<Response<GrpcCalculatePortfolioResponse>, Status> {
+        match self {
+            Self::Grpc(client) => client.calculate_portfolio_request(request).await,
+            Self::InProcess(calculator) => calculator.calculate_portfolio_request(request).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CalculatePortfoliosClient for CalculationBackend {
+    async fn calculate_portfolios_request(
+        mut self,
+        request: Request<GrpcCalculatePortfoliosRequest>,
+    ) -> Result<Response<GrpcCalculatePortfoliosResponse>, Status> {
+        match self {
+            Self::Grpc(client) => client.calculate_portfolios_request(request).await,
+            Self::InProcess(calculator) => calculator.calculate_portfolios_request(request).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CalculatePortfolioStreamClient for CalculationBackend {
+    async fn calculate_portfolio_stream_request(
+        mut self,
+        request: Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<GrpcStreamResponses, Status> {
+        match self {
+            Self::Grpc(client) => client.calculate_portfolio_stream_request(request).await,
+            Self::InProcess(calculator) => {
+                calculator.calculate_portfolio_stream_request(request).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use tokio_stream::StreamExt;
+    use tokio_util::task::TaskTracker;
+    use tonic::Code;
+
+    use drive_deposits_event_source::publisher::{ChannelPublisher, BANK_LEVEL, PORTFOLIO_LEVEL};
+    use drive_deposits_proto_grpc_types::generated::{
+        correlated_portfolio_response::Result as CorrelatedResult,
+        CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
+        CalculatePortfoliosRequest as GrpcCalculatePortfoliosRequest,
+        CorrelatedPortfolioRequest as GrpcCorrelatedPortfolioRequest,
+    };
+    use drive_deposits_rest_types::auth::CORRELATION_ID_HEADER;
+    use drive_deposits_rest_types::rest_types::{
+        CalculatePortfolioRequest as RestCalculatePortfolioRequest,
+        CalculatePortfoliosRequest as RestCalculatePortfoliosRequest,
+        CorrelatedPortfolioRequest as RestCorrelatedPortfolioRequest, NewBank as RestNewBank,
+        NewDelta as RestNewDelta, NewDeposit as RestNewDeposit,
+    };
+
+    use super::{EventOptions, InProcessCalculator};
+    use crate::auth::Caller;
+    use crate::drive_deposits_client::{
+        calculate_portfolio_stream_with_client, calculate_portfolio_with_client,
+        calculate_portfolios_with_client, CalculatePortfolioClient, CalculatePortfoliosClient,
+    };
+
+    fn rest_request(bank_names: &[&str]) -> RestCalculatePortfolioRequest {
+        RestCalculatePortfolioRequest {
+            new_banks: bank_names
+                .iter()
+                .map(|name| RestNewBank {
+                    name: name.to_string(),
+                    bank_tz: "America/New_York".to_string(),
+                    new_deposits: vec![RestNewDeposit {
+                        account: "1234".to_string(),
+                        account_type: "Checking".to_string(),
+                        apy: "2.4".to_string(),
+                        years: "1".to_string(),
+                        amount: "1000".to_string(),
+                        start_date_in_bank_tz: "2024-02-16".to_string(),
+                    }],
+                })
+                .collect(),
+            new_delta: RestNewDelta {
+                period: "1".to_string(),
+                period_unit: "Month".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_process_calculate_portfolio_without_events() {
+        let calculator =
+            InProcessCalculator::new(None, EventOptions::default(), TaskTracker::new());
+        let response = calculate_portfolio_with_client(
+            rest_request(&["VISION-BANK"]),
+            &Caller::default(),
+            calculator,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.0.banks.len(), 1);
+        let maturity = response.0.banks[0].deposits[0]
+            .outcome
+            .as_ref()
+            .and_then(|outcome| outcome.maturity.as_ref())
+            .unwrap();
+        assert_eq!(maturity.interest, "24.00");
+    }
+
+    #[tokio::test]
+    async fn test_in_process_calculate_portfolio_stream_without_events() {
+        let calculator =
+            InProcessCalculator::new(None, EventOptions::default(), TaskTracker::new());
+        let events: Vec<String> = calculate_portfolio_stream_with_client(
+            rest_request(&["VISION-BANK", "PENSFED"]),
+            &Caller::default(),
+            calculator,
+        )
+        .await
+        .map(|event| format!("{:?}", event.unwrap()))
+        .collect()
+        .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(events[2].contains("portfolio-completed"));
+    }
+
+    #[tokio::test]
+    async fn test_in_process_expired_grpc_timeout_is_deadline_exceeded() {
+        let calculator =
+            InProcessCalculator::new(None, EventOptions::default(), TaskTracker::new());
+        let grpc_request: GrpcCalculatePortfolioRequest =
+            rest_request(&["VISION-BANK"]).try_into().unwrap();
+        let mut request = Caller::default().grpc_request(grpc_request);
+        request.set_timeout(Duration::ZERO);
+
+        let status = calculator
+            .calculate_portfolio_request(request)
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_in_process_events_are_cloud_events_with_correlation_id() {
+        let (publisher, mut published) = ChannelPublisher::new();
+        let calculator = InProcessCalculator::new(
+            Some(Arc::new(publisher)),
+            EventOptions::default(),
+            TaskTracker::new(),
+        );
+        let grpc_request: GrpcCalculatePortfolioRequest =
+            rest_request(&["VISION-BANK"]).try_into().unwrap();
+        let mut request = Caller::default().grpc_request(grpc_request);
+        request
+            .metadata_mut()
+            .insert(CORRELATION_ID_HEADER, "request-7".parse().unwrap());
+
+        let response = calculator
+            .calculate_portfolio_request(request)
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut events = vec![];
+        while let Ok(event) = published.try_recv() {
+            let detail: serde_json::Value = serde_json::from_str(&event.detail).unwrap();
+            events.push((event.detail_type, detail));
+        }
+        let detail_types: Vec<&str> = events
+            .iter()
+            .map(|(detail_type, _)| detail_type.as_str())
+            .collect();
+        assert_eq!(detail_types, vec![BANK_LEVEL, PORTFOLIO_LEVEL]);
+        for (detail_type, detail) in &events {
+            assert_eq!(detail["specversion"], "1.0");
+            assert_eq!(detail["type"], format!("drive-deposits.{}.v1", detail_type));
+            assert_eq!(detail["subject"], response.uuid);
+            assert_eq!(detail["correlationid"], "request-7");
+        }
+        assert_ne!(events[0].1["id"], events[1].1["id"]);
+        assert_eq!(events[1].1["data"]["uuid"], response.uuid);
+    }
+
+    #[tokio::test]
+    async fn test_in_process_batch_events_carry_each_portfolio_correlation_id() {
+        let (publisher, mut published) = ChannelPublisher::new();
+        let calculator = InProcessCalculator::new(
+            Some(Arc::new(publisher)),
+            EventOptions::default(),
+            TaskTracker::new(),
+        );
+        let portfolio_requests = ["client-1", "client-2"]
+            .into_iter()
+            .map(|correlation_id| GrpcCorrelatedPortfolioRequest {
+                correlation_id: correlation_id.to_string(),
+                portfolio_request: Some(rest_request(&["VISION-BANK"]).try_into().unwrap()),
+            })
+            .collect();
+        let mut request =
+            Caller::default().grpc_request(GrpcCalculatePortfoliosRequest { portfolio_requests });
+        request
+            .metadata_mut()
+            .insert(CORRELATION_ID_HEADER, "request-7".parse().unwrap());
+
+        let response = calculator
+            .calculate_portfolios_request(request)
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut portfolio_correlation_ids = vec![];
+        while let Ok(event) = published.try_recv() {
+            let detail: serde_json::Value = serde_json::from_str(&event.detail).unwrap();
+            if event.detail_type == PORTFOLIO_LEVEL {
+                portfolio_correlation_ids.push((
+                    detail["subject"].as_str().unwrap().to_string(),
+                    detail["correlationid"].as_str().unwrap().to_string(),
+                ));
+            }
+        }
+        portfolio_correlation_ids.sort_by(|a, b| a.1.cmp(&b.1));
+        let expected = response
+            .portfolio_responses
+            .iter()
+            .map(|portfolio_response| {
+                let Some(CorrelatedResult::PortfolioResponse(portfolio)) =
+                    &portfolio_response.result
+                else {
+                    panic!("expected {} to succeed", portfolio_response.correlation_id);
+                };
+                (
+                    portfolio.uuid.clone(),
+                    portfolio_response.correlation_id.clone(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(portfolio_correlation_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_in_process_invalid_request_same_status_as_grpc_server() {
+        let calculator =
+            InProcessCalculator::new(None, EventOptions::default(), TaskTracker::new());
+        let result =
+            calculate_portfolio_with_client(rest_request(&[]), &Caller::default(), calculator)
+                .await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("request contains invalid arguments"));
+    }
+
+    #[tokio::test]
+    async fn test_in_process_calculate_portfolios_one_bad_portfolio_does_not_fail_batch() {
+        let calculator =
+            InProcessCalculator::new(None, EventOptions::default(), TaskTracker::new());
+        let mut bad_request = rest_request(&["PENSFED"]);
+        bad_request.new_banks[0].bank_tz = "Mars/Base".to_string();
+        let batch_request = RestCalculatePortfoliosRequest {
+            portfolio_requests: vec![
+                RestCorrelatedPortfolioRequest {
+                    correlation_id: "client-1".to_string(),
+                    portfolio_request: rest_request(&["VISION-BANK"]),
+                },
+                RestCorrelatedPortfolioRequest {
+                    correlation_id: "client-2".to_string(),
+                    portfolio_request: bad_request,
+                },
+                RestCorrelatedPortfolioRequest {
+                    correlation_id: "client-3".to_string(),
+                    portfolio_request: rest_request(&["VISION-BANK", "PENSFED"]),
+                },
+            ],
+        };
+
+        let response =
+            calculate_portfolios_with_client(batch_request, &Caller::default(), calculator)
+                .await
+                .unwrap()
+                .0;
+
+        assert_eq!((response.succeeded, response.failed), (2, 1));
+        let correlation_ids = response
+            .portfolio_responses
+            .iter()
+            .map(|portfolio_response| portfolio_response.correlation_id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(correlation_ids, vec!["client-1", "client-2", "client-3"]);
+        let failed = response.portfolio_responses[1].error.as_ref().unwrap();
+        assert_eq!(failed.code, "InvalidArgument");
+        assert_eq!(failed.violations[0].pointer, "/new_banks/0/bank_tz");
+        let last = response.portfolio_responses[2]
+            .portfolio_response
+            .as_ref()
+            .unwrap();
+        assert_eq!(last.banks.len(), 2);
+    }
+}
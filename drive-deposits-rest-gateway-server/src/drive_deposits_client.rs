@@ -1,8 +1,7 @@
 use async_trait::async_trait;
-use axum::Json;
+use axum::{extract::State, http::HeaderMap, response::Response, Extension, Json};
 use mockall::automock;
-use std::env::var;
-use tracing::{debug, debug_span, error, info};
+use tracing::{debug, debug_span, info};
 
 use app_error::Error as AppError;
 use drive_deposits_proto_grpc_types::generated::{
@@ -14,12 +13,34 @@ use drive_deposits_rest_types::rest_types::{
     CalculatePortfolioRequest as RestCalculatePortfolioRequest,
     CalculatePortfolioResponse as RestCalculatePortfolioResponse,
 };
+use report_response::negotiate_report;
 use request_error::ValidateCalculateRequest;
 use tonic::transport::Channel;
 
+use crate::auth::Caller;
+use crate::calculation_mode::CalculationBackend;
+
 mod app_error;
+pub(crate) mod portfolio_batch;
+pub(crate) mod portfolio_csv;
+pub(crate) mod portfolio_stream;
+mod problem_details;
+mod report_response;
 mod request_error;
 
+pub use portfolio_batch::{
+    calculate_portfolios, calculate_portfolios_with_client, CalculatePortfoliosClient,
+};
+pub use portfolio_csv::calculate_portfolio_csv;
+pub use portfolio_stream::{
+    calculate_portfolio_stream, calculate_portfolio_stream_with_client,
+    CalculatePortfolioStreamClient, GrpcStreamResponses, BANK_COMPLETED_EVENT,
+    CALCULATION_ERROR_EVENT, PORTFOLIO_COMPLETED_EVENT,
+};
+pub use problem_details::{ProblemDetails, Violation, PROBLEM_JSON_CONTENT_TYPE};
+
+// tonic::Status is large, but it is what the generated grpc client returns
+#[allow(clippy::result_large_err)]
 #[automock]
 #[async_trait]
 pub trait CalculatePortfolioClient {
@@ -43,9 +64,30 @@ impl CalculatePortfolioClient for DriveDepositsServiceClient<Channel> {
 }
 
 // #[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/api/drive-deposits/calculate-portfolio",
+    request_body = RestCalculatePortfolioRequest,
+    responses(
+        (status = 200, description = "Calculated portfolio; a csv, markdown or html report with bank subtotals and portfolio totals when the Accept header prefers one", content(
+            (RestCalculatePortfolioResponse = "application/json"),
+            (String = "text/csv"),
+            (String = "text/markdown"),
+            (String = "text/html")
+        )),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 502, description = "Calculation server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Calculation server unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    tag = "calculate"
+)]
 pub async fn calculate_portfolio(
+    State(calculation_backend): State<CalculationBackend>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
     ValidateCalculateRequest(rest_delta_request): ValidateCalculateRequest,
-) -> Result<Json<RestCalculatePortfolioResponse>, AppError> {
+) -> Result<Response, AppError> {
     let span = debug_span!("calculate_portfolio");
     span.in_scope(|| {
         debug!(
@@ -54,20 +96,14 @@ pub async fn calculate_portfolio(
         )
     });
 
-    // for docker compose dns GRPC_SERVER_ADDRESS=http://drive-deposits-grpc-server:50052
-    let grpc_server_address =
-        var("GRPC_SERVER_ADDRESS").unwrap_or_else(|_| "http://[::]:50052".to_string());
-    info!("grpc_server_address is: {}", grpc_server_address);
-    let client = DriveDepositsServiceClient::connect(grpc_server_address)
-        .await
-        .inspect_err(|err| {
-            span.in_scope(|| error!("grpc client connection error: {:?}", err));
-        })?;
-    calculate_portfolio_with_client(rest_delta_request, client).await
+    let rest_response =
+        calculate_portfolio_with_client(rest_delta_request, &caller, calculation_backend).await?;
+    negotiate_report(&headers, rest_response)
 }
 
 pub async fn calculate_portfolio_with_client(
     rest_delta_request: RestCalculatePortfolioRequest,
+    caller: &Caller,
     client: impl CalculatePortfolioClient,
 ) -> Result<Json<RestCalculatePortfolioResponse>, AppError> {
     let span = debug_span!("calculate_portfolio_with_client");
@@ -78,9 +114,9 @@ pub async fn calculate_portfolio_with_client(
         )
     });
 
-    let grpc_delta_request = span.in_scope(|| rest_delta_request.into());
+    let grpc_delta_request = span.in_scope(|| rest_delta_request.try_into())?;
 
-    let grpc_request = tonic::Request::new(grpc_delta_request);
+    let grpc_request = caller.grpc_request(grpc_delta_request);
     let grpc_response = client.calculate_portfolio_request(grpc_request).await?;
 
     span.in_scope(|| {
@@ -99,8 +135,11 @@ pub async fn calculate_portfolio_with_client(
 }
 
 #[cfg(test)]
+// the mock expectations return tonic::Status like the client they stand in for
+#[allow(clippy::result_large_err)]
 mod tests {
     use super::calculate_portfolio_with_client;
+    use crate::auth::Caller;
     use crate::drive_deposits_client::MockCalculatePortfolioClient;
     use drive_deposits_proto_grpc_types::generated::{
         Bank as GrpcBank, CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
@@ -157,7 +196,8 @@ mod tests {
                 period_unit: "Month".to_string(),
             },
         };
-        let result = calculate_portfolio_with_client(rest_request, mock_client).await;
+        let result =
+            calculate_portfolio_with_client(rest_request, &Caller::default(), mock_client).await;
 
         assert!(result.is_ok());
         let response = result.unwrap();
@@ -172,6 +212,7 @@ mod tests {
                 maturity: None,
                 errors: vec![],
             }),
+            tenant_id: None,
         };
         let actual_bank_tz = response.0.banks.first().unwrap().bank_tz.clone();
         assert_eq!(actual_bank_tz, expected_bank.bank_tz);
@@ -1,11 +1,15 @@
 use axum::{
     http::StatusCode,
-    response::{IntoResponse, Json, Response},
+    response::{IntoResponse, Response},
 };
-use serde::Serialize;
 use thiserror::Error;
 use tracing::error;
 
+use drive_deposits_proto_grpc_types::convert::from_rest_grpc_request::Error as RestToGrpcError;
+use drive_deposits_rest_types::rest_types::field_path_to_pointer;
+
+use super::problem_details::{status_code_for_grpc, status_violations, ProblemDetails, Violation};
+
 #[derive(Default, Debug, Error)]
 pub enum Error {
     #[default]
@@ -13,40 +17,65 @@ pub enum Error {
     InternalServer,
     #[error("Tonic transport error: {0}")]
     Transport(#[from] tonic::transport::Error),
+    // boxed, tonic::Status would make every Result carrying this error very large
     #[error("Tonic status error:  {0}")]
-    Status(#[from] tonic::Status),
+    Status(Box<tonic::Status>),
+    #[error("Report error: {0}")]
+    Report(#[from] drive_deposits_rest_types::report::Error),
+    #[error("Rest to grpc request conversion error: {0}")]
+    RestToGrpc(#[from] RestToGrpcError),
 }
 
-#[derive(Serialize)]
-pub struct SerializableError {
-    error: String,
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Self::Status(Box::new(status))
+    }
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
-        error!("IntoResponse for Error is {:?}", self);
-
-        let (status, error_message) = match self {
-            Error::InternalServer => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Internal Server Error".to_string(),
-            ),
-            Error::Transport(err) => (
+impl From<Error> for ProblemDetails {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InternalServer => {
+                ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+            Error::Transport(err) => ProblemDetails::new(
                 StatusCode::SERVICE_UNAVAILABLE,
-                format!("Connect Server Error {:?}", err),
+                format!("Connect Server Error {}", err),
             ),
-            Error::Status(err) => (
-                StatusCode::BAD_GATEWAY,
-                format!("gRPC Server Error {:?}", err),
+            Error::Status(status) => ProblemDetails::new(
+                status_code_for_grpc(status.code()),
+                format!("gRPC Server Error {}", status.message()),
+            )
+            .with_violations(status_violations(&status)),
+            Error::Report(err) => ProblemDetails::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Report Rendering Error {}", err),
             ),
-        };
+            Error::RestToGrpc(err) => {
+                ProblemDetails::new(StatusCode::BAD_REQUEST, "Input validation error")
+                    .with_violations(vec![rest_to_grpc_violation(err)])
+            }
+        }
+    }
+}
 
-        error!("{}", error_message);
+pub fn rest_to_grpc_violation(err: RestToGrpcError) -> Violation {
+    Violation {
+        pointer: field_path_to_pointer(&err.field),
+        code: err.code.to_string(),
+        message: err.message,
+        row: None,
+        column: None,
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        error!("IntoResponse for Error is {:?}", self);
 
-        let error = SerializableError {
-            error: error_message,
-        };
+        let problem_details = ProblemDetails::from(self);
+        error!("{}", problem_details.detail);
 
-        (status, Json(error)).into_response()
+        problem_details.into_response()
     }
 }
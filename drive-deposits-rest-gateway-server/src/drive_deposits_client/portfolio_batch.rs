@@ -0,0 +1,393 @@
+use async_trait::async_trait;
+use axum::{extract::State, Extension, Json};
+use mockall::automock;
+use tonic::transport::Channel;
+use tonic::Code;
+use tracing::{debug, debug_span, info, Instrument};
+use validator::Validate;
+
+use drive_deposits_proto_grpc_types::generated::{
+    drive_deposits_service_client::DriveDepositsServiceClient,
+    CalculatePortfoliosRequest as GrpcCalculatePortfoliosRequest,
+    CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+    CorrelatedPortfolioRequest as GrpcCorrelatedPortfolioRequest,
+};
+use drive_deposits_rest_types::rest_types::{
+    CalculatePortfoliosRequest as RestCalculatePortfoliosRequest,
+    CalculatePortfoliosResponse as RestCalculatePortfoliosResponse,
+    CorrelatedPortfolioRequest as RestCorrelatedPortfolioRequest,
+    CorrelatedPortfolioResponse as RestCorrelatedPortfolioResponse,
+    PortfolioError as RestPortfolioError, PortfolioViolation as RestPortfolioViolation,
+};
+
+use super::app_error::{rest_to_grpc_violation, Error as AppError};
+use super::problem_details::{validation_violations, ProblemDetails};
+use super::request_error::ValidateCalculatePortfoliosRequest;
+use crate::auth::Caller;
+use crate::calculation_mode::CalculationBackend;
+
+// same tonic::Status error as CalculatePortfolioClient
+#[allow(clippy::result_large_err)]
+#[automock]
+#[async_trait]
+pub trait CalculatePortfoliosClient {
+    async fn calculate_portfolios_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfoliosRequest>,
+    ) -> Result<tonic::Response<GrpcCalculatePortfoliosResponse>, tonic::Status>;
+}
+
+#[async_trait]
+impl CalculatePortfoliosClient for DriveDepositsServiceClient<Channel> {
+    async fn calculate_portfolios_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfoliosRequest>,
+    ) -> Result<tonic::Response<GrpcCalculatePortfoliosResponse>, tonic::Status> {
+        self.calculate_portfolios(request).await
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/drive-deposits/calculate-portfolios",
+    request_body = RestCalculatePortfoliosRequest,
+    responses(
+        (status = 200, description = "One result per portfolio request, in the same order, each with either the calculated portfolio or the error it failed with", body = RestCalculatePortfoliosResponse),
+        (status = 400, description = "Invalid batch: no portfolio requests, more than 100, or missing or duplicate correlation_id", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 502, description = "Calculation server error", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 503, description = "Calculation server unavailable", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    tag = "calculate"
+)]
+pub async fn calculate_portfolios(
+    State(calculation_backend): State<CalculationBackend>,
+    Extension(caller): Extension<Caller>,
+    ValidateCalculatePortfoliosRequest(rest_batch_request): ValidateCalculatePortfoliosRequest,
+) -> Result<Json<RestCalculatePortfoliosResponse>, AppError> {
+    let span = debug_span!("calculate_portfolios");
+    span.in_scope(|| {
+        debug!(
+            "calculate_portfolios request incoming is : {:#?}",
+            rest_batch_request
+        )
+    });
+
+    calculate_portfolios_with_client(rest_batch_request, &caller, calculation_backend)
+        .instrument(span)
+        .await
+}
+
+pub async fn calculate_portfolios_with_client(
+    rest_batch_request: RestCalculatePortfoliosRequest,
+    caller: &Caller,
+    client: impl CalculatePortfoliosClient,
+) -> Result<Json<RestCalculatePortfoliosResponse>, AppError> {
+    // an invalid portfolio gets its error in place; None marks the ones sent to be calculated
+    let mut invalid_responses = Vec::with_capacity(rest_batch_request.portfolio_requests.len());
+    let mut grpc_portfolio_requests = vec![];
+    for rest_portfolio_request in rest_batch_request.portfolio_requests {
+        let correlation_id = rest_portfolio_request.correlation_id.clone();
+        match to_grpc_portfolio_request(rest_portfolio_request) {
+            Ok(grpc_portfolio_request) => {
+                grpc_portfolio_requests.push(grpc_portfolio_request);
+                invalid_responses.push(None);
+            }
+            Err(error) => {
+                debug!(
+                    "portfolio request {} is invalid: {:?}",
+                    correlation_id, error
+                );
+                invalid_responses.push(Some(RestCorrelatedPortfolioResponse {
+                    correlation_id,
+                    portfolio_response: None,
+                    error: Some(error),
+                }));
+            }
+        }
+    }
+
+    let mut calculated_responses = if grpc_portfolio_requests.is_empty() {
+        vec![]
+    } else {
+        let grpc_request = caller.grpc_request(GrpcCalculatePortfoliosRequest {
+            portfolio_requests: grpc_portfolio_requests,
+        });
+        let grpc_response = client.calculate_portfolios_request(grpc_request).await?;
+        RestCalculatePortfoliosResponse::from(grpc_response.into_inner()).portfolio_responses
+    }
+    .into_iter();
+    // the grpc server answers every portfolio it was sent, in order
+    let portfolio_responses = invalid_responses
+        .into_iter()
+        .map(|invalid_response| invalid_response.or_else(|| calculated_responses.next()))
+        .collect::<Option<Vec<RestCorrelatedPortfolioResponse>>>()
+        .ok_or(AppError::InternalServer)?;
+
+    let succeeded = portfolio_responses
+        .iter()
+        .filter(|portfolio_response| portfolio_response.portfolio_response.is_some())
+        .count() as u32;
+    let rest_response = RestCalculatePortfoliosResponse {
+        failed: portfolio_responses.len() as u32 - succeeded,
+        portfolio_responses,
+        succeeded,
+    };
+    info!(
+        "batch calculated with {} succeeded and {} failed",
+        rest_response.succeeded, rest_response.failed
+    );
+    Ok(Json(rest_response))
+}
+
+// validated like a single portfolio request, with the violations in the item's own error
+fn to_grpc_portfolio_request(
+    rest_portfolio_request: RestCorrelatedPortfolioRequest,
+) -> Result<GrpcCorrelatedPortfolioRequest, RestPortfolioError> {
+    let violations = match rest_portfolio_request.portfolio_request.validate() {
+        Ok(()) => match GrpcCorrelatedPortfolioRequest::try_from(rest_portfolio_request) {
+            Ok(grpc_portfolio_request) => return Ok(grpc_portfolio_request),
+            Err(err) => vec![rest_to_grpc_violation(err)],
+        },
+        Err(errors) => validation_violations(&errors),
+    };
+    Err(RestPortfolioError {
+        code: format!("{:?}", Code::InvalidArgument),
+        message: "Input validation error".to_string(),
+        violations: violations
+            .into_iter()
+            .map(|violation| RestPortfolioViolation {
+                pointer: violation.pointer,
+                code: violation.code,
+                message: violation.message,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+// the mock expectations return tonic::Status like the client they stand in for
+#[allow(clippy::result_large_err)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tonic::Code;
+
+    use drive_deposits_proto_grpc_types::generated::{
+        correlated_portfolio_response::Result as CorrelatedResult,
+        CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
+        CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+        CorrelatedPortfolioResponse as GrpcCorrelatedPortfolioResponse,
+        PortfolioError as GrpcPortfolioError, PortfolioViolation as GrpcPortfolioViolation,
+    };
+    use drive_deposits_rest_types::rest_types::{
+        CalculatePortfolioRequest as RestCalculatePortfolioRequest,
+        CalculatePortfoliosRequest as RestCalculatePortfoliosRequest,
+        CorrelatedPortfolioRequest as RestCorrelatedPortfolioRequest, NewBank as RestNewBank,
+        NewDelta as RestNewDelta, NewDeposit as RestNewDeposit,
+    };
+
+    use super::{calculate_portfolios_with_client, MockCalculatePortfoliosClient};
+    use crate::auth::Caller;
+
+    fn rest_portfolio_request() -> RestCalculatePortfolioRequest {
+        RestCalculatePortfolioRequest {
+            new_banks: vec![RestNewBank {
+                name: "VISION-BANK".to_string(),
+                bank_tz: "America/New_York".to_string(),
+                new_deposits: vec![RestNewDeposit {
+                    account: "1234".to_string(),
+                    account_type: "Checking".to_string(),
+                    apy: "2.4".to_string(),
+                    years: "1".to_string(),
+                    amount: "1000".to_string(),
+                    start_date_in_bank_tz: "2024-02-16".to_string(),
+                }],
+            }],
+            new_delta: RestNewDelta {
+                period: "1".to_string(),
+                period_unit: "Month".to_string(),
+            },
+        }
+    }
+
+    fn rest_batch_request(
+        portfolio_requests: Vec<(&str, RestCalculatePortfolioRequest)>,
+    ) -> RestCalculatePortfoliosRequest {
+        RestCalculatePortfoliosRequest {
+            portfolio_requests: portfolio_requests
+                .into_iter()
+                .map(
+                    |(correlation_id, portfolio_request)| RestCorrelatedPortfolioRequest {
+                        correlation_id: correlation_id.to_string(),
+                        portfolio_request,
+                    },
+                )
+                .collect(),
+        }
+    }
+
+    fn calculated(correlation_id: String) -> GrpcCorrelatedPortfolioResponse {
+        GrpcCorrelatedPortfolioResponse {
+            correlation_id,
+            result: Some(CorrelatedResult::PortfolioResponse(
+                GrpcCalculatePortfolioResponse {
+                    uuid: "uuid".to_string(),
+                    ..Default::default()
+                },
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calculate_portfolios_keeps_per_item_failures() {
+        let mut mock_client = MockCalculatePortfoliosClient::new();
+        mock_client
+            .expect_calculate_portfolios_request()
+            .returning(|grpc_request| {
+                let correlation_ids = grpc_request
+                    .into_inner()
+                    .portfolio_requests
+                    .into_iter()
+                    .map(|portfolio_request| portfolio_request.correlation_id)
+                    .collect::<Vec<String>>();
+                assert_eq!(correlation_ids, vec!["client-1", "client-2"]);
+                let grpc_response = GrpcCalculatePortfoliosResponse {
+                    portfolio_responses: vec![
+                        GrpcCorrelatedPortfolioResponse {
+                            correlation_id: "client-1".to_string(),
+                            result: Some(CorrelatedResult::PortfolioResponse(
+                                GrpcCalculatePortfolioResponse {
+                                    uuid: "uuid".to_string(),
+                                    ..Default::default()
+                                },
+                            )),
+                        },
+                        GrpcCorrelatedPortfolioResponse {
+                            correlation_id: "client-2".to_string(),
+                            result: Some(CorrelatedResult::Error(GrpcPortfolioError {
+                                code: Code::InvalidArgument as i32,
+                                message: "request contains invalid arguments".to_string(),
+                                violations: vec![GrpcPortfolioViolation {
+                                    field: "new_banks[0].bank_tz".to_string(),
+                                    code: "invalid_tz".to_string(),
+                                    description: "Incorrect bank_tz: Mars/Base.".to_string(),
+                                }],
+                            })),
+                        },
+                    ],
+                    succeeded: 1,
+                    failed: 1,
+                };
+                Ok(tonic::Response::new(grpc_response))
+            });
+
+        let rest_request = rest_batch_request(vec![
+            ("client-1", rest_portfolio_request()),
+            ("client-2", rest_portfolio_request()),
+        ]);
+        let response =
+            calculate_portfolios_with_client(rest_request, &Caller::default(), mock_client)
+                .await
+                .unwrap()
+                .0;
+
+        assert_eq!((response.succeeded, response.failed), (1, 1));
+        let succeeded = &response.portfolio_responses[0];
+        assert_eq!(succeeded.portfolio_response.as_ref().unwrap().uuid, "uuid");
+        assert!(succeeded.error.is_none());
+        let failed = response.portfolio_responses[1].error.as_ref().unwrap();
+        assert_eq!(failed.code, "InvalidArgument");
+        assert_eq!(failed.violations[0].pointer, "/new_banks/0/bank_tz");
+        assert_eq!(failed.violations[0].code, "invalid_tz");
+    }
+
+    #[tokio::test]
+    async fn test_calculate_portfolios_fails_invalid_items_in_place_without_sending_them() {
+        let mut mock_client = MockCalculatePortfoliosClient::new();
+        mock_client
+            .expect_calculate_portfolios_request()
+            .times(1)
+            .returning(|grpc_request| {
+                let portfolio_responses = grpc_request
+                    .into_inner()
+                    .portfolio_requests
+                    .into_iter()
+                    .map(|portfolio_request| calculated(portfolio_request.correlation_id))
+                    .collect::<Vec<GrpcCorrelatedPortfolioResponse>>();
+                assert_eq!(portfolio_responses.len(), 2);
+                Ok(tonic::Response::new(GrpcCalculatePortfoliosResponse {
+                    succeeded: portfolio_responses.len() as u32,
+                    portfolio_responses,
+                    failed: 0,
+                }))
+            });
+
+        let mut unknown_account_type = rest_portfolio_request();
+        unknown_account_type.new_banks[0].new_deposits[0].account_type = "Crypto".to_string();
+        let mut unknown_period_unit = rest_portfolio_request();
+        unknown_period_unit.new_delta.period_unit = "Decade".to_string();
+        let rest_request = rest_batch_request(vec![
+            ("client-1", rest_portfolio_request()),
+            ("client-2", unknown_account_type),
+            ("client-3", rest_portfolio_request()),
+            ("client-4", unknown_period_unit),
+        ]);
+        let response =
+            calculate_portfolios_with_client(rest_request, &Caller::default(), mock_client)
+                .await
+                .unwrap()
+                .0;
+
+        assert_eq!((response.succeeded, response.failed), (2, 2));
+        let correlation_ids = response
+            .portfolio_responses
+            .iter()
+            .map(|portfolio_response| portfolio_response.correlation_id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            correlation_ids,
+            vec!["client-1", "client-2", "client-3", "client-4"]
+        );
+        assert!(response.portfolio_responses[0].portfolio_response.is_some());
+        assert!(response.portfolio_responses[2].portfolio_response.is_some());
+        let account_type_error = response.portfolio_responses[1].error.as_ref().unwrap();
+        assert_eq!(account_type_error.code, "InvalidArgument");
+        assert_eq!(
+            account_type_error.violations[0].pointer,
+            "/new_banks/0/new_deposits/0/account_type"
+        );
+        assert_eq!(
+            account_type_error.violations[0].code,
+            "invalid_account_type"
+        );
+        let period_unit_error = response.portfolio_responses[3].error.as_ref().unwrap();
+        assert_eq!(
+            period_unit_error.violations[0].pointer,
+            "/new_delta/period_unit"
+        );
+        assert_eq!(period_unit_error.violations[0].code, "invalid_period_unit");
+    }
+
+    #[tokio::test]
+    async fn test_calculate_portfolios_without_valid_items_does_not_call_the_server() {
+        let mut mock_client = MockCalculatePortfoliosClient::new();
+        mock_client.expect_calculate_portfolios_request().never();
+
+        let mut unspecified_account_type = rest_portfolio_request();
+        unspecified_account_type.new_banks[0].new_deposits[0].account_type =
+            "Unspecified".to_string();
+        let rest_request = rest_batch_request(vec![("client-1", unspecified_account_type)]);
+        let response =
+            calculate_portfolios_with_client(rest_request, &Caller::default(), mock_client)
+                .await
+                .unwrap()
+                .0;
+
+        assert_eq!((response.succeeded, response.failed), (0, 1));
+        let error = response.portfolio_responses[0].error.as_ref().unwrap();
+        assert_eq!(
+            error.violations[0].pointer,
+            "/new_banks/0/new_deposits/0/account_type"
+        );
+    }
+}
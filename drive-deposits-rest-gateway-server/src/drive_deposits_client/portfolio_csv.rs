@@ -0,0 +1,124 @@
+use axum::{extract::State, http::HeaderMap, response::Response, Extension};
+use tracing::{debug, debug_span, Instrument};
+
+use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse as RestCalculatePortfolioResponse;
+
+use super::app_error::Error as AppError;
+use super::calculate_portfolio_with_client;
+use super::problem_details::ProblemDetails;
+use super::report_response::negotiate_report;
+use super::request_error::{CsvImportParams, ImportCsvRequest};
+use crate::auth::Caller;
+use crate::calculation_mode::CalculationBackend;
+
+#[utoipa::path(
+    post,
+    path = "/api/drive-deposits/calculate-portfolio/csv",
+    params(CsvImportParams),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "Header row, then one row per deposit with bank, bank_tz, account, account_type, apy, years, amount and start_date_in_bank_tz columns; rows with the same bank are grouped into one bank"
+    ),
+    responses(
+        (status = 200, description = "Calculated portfolio; a csv, markdown or html report with bank subtotals and portfolio totals when the Accept header prefers one", content(
+            (RestCalculatePortfolioResponse = "application/json"),
+            (String = "text/csv"),
+            (String = "text/markdown"),
+            (String = "text/html")
+        )),
+        (status = 400, description = "Invalid CSV; violations have the CSV row and column", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 415, description = "Content-Type is not text/csv", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 502, description = "Calculation server error", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    tag = "calculate"
+)]
+pub async fn calculate_portfolio_csv(
+    State(calculation_backend): State<CalculationBackend>,
+    Extension(caller): Extension<Caller>,
+    headers: HeaderMap,
+    ImportCsvRequest(rest_delta_request): ImportCsvRequest,
+) -> Result<Response, AppError> {
+    let span = debug_span!("calculate_portfolio_csv");
+    span.in_scope(|| {
+        debug!(
+            "calculate_portfolio_csv request imported is : {:#?}",
+            rest_delta_request
+        )
+    });
+
+    let rest_response =
+        calculate_portfolio_with_client(rest_delta_request, &caller, calculation_backend)
+            .instrument(span)
+            .await?;
+    negotiate_report(&headers, rest_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::extract::FromRequest;
+    use axum::http::{header, Request, StatusCode};
+    use axum::response::IntoResponse;
+    use pretty_assertions::assert_eq;
+
+    use super::ImportCsvRequest;
+    use crate::drive_deposits_client::ProblemDetails;
+
+    const CSV: &str = "\
+Bank Name,bank_tz,account,account_type,apy,years,amount,start_date_in_bank_tz
+VISION-BANK,America/Los_Angeles,1234,Savings,5,7,10990,2023-02-16
+PEACEMAKER,America/New_York,1234,Checking,2.4,7,10990,2024-02-16
+VISION-BANK,America/Los_Angeles,9898,CertificateOfDeposit,abc,1,5500,2020-02-16
+";
+
+    fn csv_request(content_type: &str, body: &str) -> Request<Body> {
+        Request::post("/api/drive-deposits/calculate-portfolio/csv?period=1&period_unit=Month&columns=bank%3DBank%20Name")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn problem_details(request: Request<Body>) -> ProblemDetails {
+        let rejection = ImportCsvRequest::from_request(request, &())
+            .await
+            .unwrap_err();
+        let body = to_bytes(rejection.into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_groups_rows_into_banks() {
+        let valid_csv = CSV.replace("abc", "2.22");
+        let ImportCsvRequest(rest_request) =
+            ImportCsvRequest::from_request(csv_request("text/csv", &valid_csv), &())
+                .await
+                .unwrap();
+
+        assert_eq!(rest_request.new_banks.len(), 2);
+        assert_eq!(rest_request.new_banks[0].name, "VISION-BANK");
+        assert_eq!(rest_request.new_banks[0].new_deposits.len(), 2);
+        assert_eq!(rest_request.new_banks[1].name, "PEACEMAKER");
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_violation_has_row_and_column() {
+        let problem = problem_details(csv_request("text/csv; charset=utf-8", CSV)).await;
+
+        assert_eq!(problem.status, StatusCode::BAD_REQUEST.as_u16());
+        assert_eq!(problem.violations.len(), 1);
+        let violation = &problem.violations[0];
+        assert_eq!(violation.pointer, "/new_banks/0/new_deposits/1/apy");
+        assert_eq!((violation.row, violation.column), (Some(4), Some(5)));
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_rejects_other_content_types() {
+        let problem = problem_details(csv_request("application/json", CSV)).await;
+
+        assert_eq!(problem.status, StatusCode::UNSUPPORTED_MEDIA_TYPE.as_u16());
+    }
+}
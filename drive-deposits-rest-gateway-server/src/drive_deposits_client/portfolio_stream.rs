@@ -0,0 +1,242 @@
+use std::convert::Infallible;
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use mockall::automock;
+use tokio_stream::{once, Stream, StreamExt};
+use tonic::transport::Channel;
+use tracing::{debug, debug_span, error, Instrument};
+
+use drive_deposits_proto_grpc_types::generated::{
+    calculate_portfolio_stream_response::Result as StreamResult,
+    drive_deposits_service_client::DriveDepositsServiceClient,
+    CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
+    CalculatePortfolioStreamResponse as GrpcCalculatePortfolioStreamResponse,
+};
+use drive_deposits_rest_types::rest_types::{
+    Bank as RestBank, CalculatePortfolioRequest as RestCalculatePortfolioRequest,
+    PortfolioSummary as RestPortfolioSummary,
+};
+
+use super::app_error::Error as AppError;
+use super::problem_details::ProblemDetails;
+use super::request_error::ValidateCalculateRequest;
+use crate::auth::Caller;
+use crate::calculation_mode::CalculationBackend;
+
+pub const BANK_COMPLETED_EVENT: &str = "bank-completed";
+pub const PORTFOLIO_COMPLETED_EVENT: &str = "portfolio-completed";
+// not named "error" so it does not collide with the EventSource connection error listener
+pub const CALCULATION_ERROR_EVENT: &str = "calculation-error";
+
+pub type GrpcStreamResponses =
+    Pin<Box<dyn Stream<Item = Result<GrpcCalculatePortfolioStreamResponse, tonic::Status>> + Send>>;
+
+pub type SseEvents = Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>;
+
+// same tonic::Status error as CalculatePortfolioClient
+#[allow(clippy::result_large_err)]
+#[automock]
+#[async_trait]
+pub trait CalculatePortfolioStreamClient {
+    async fn calculate_portfolio_stream_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<GrpcStreamResponses, tonic::Status>;
+}
+
+#[async_trait]
+impl CalculatePortfolioStreamClient for DriveDepositsServiceClient<Channel> {
+    async fn calculate_portfolio_stream_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<GrpcStreamResponses, tonic::Status> {
+        let streaming = self.calculate_portfolio_stream(request).await?.into_inner();
+        Ok(Box::pin(streaming))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/drive-deposits/calculate-portfolio/stream",
+    request_body = RestCalculatePortfolioRequest,
+    responses(
+        (status = 200, description = "Server-Sent Events: bank-completed with a Bank for each bank as calculated, then portfolio-completed with a PortfolioSummary, or calculation-error with ProblemDetails", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid credentials", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    tag = "calculate"
+)]
+// Once the request is validated the response is always an event stream: gRPC errors are sent
+// as a calculation-error event instead of closing the connection.
+pub async fn calculate_portfolio_stream(
+    State(calculation_backend): State<CalculationBackend>,
+    Extension(caller): Extension<Caller>,
+    ValidateCalculateRequest(rest_delta_request): ValidateCalculateRequest,
+) -> Sse<SseEvents> {
+    let span = debug_span!("calculate_portfolio_stream");
+    span.in_scope(|| {
+        debug!(
+            "calculate_portfolio_stream request incoming is : {:#?}",
+            rest_delta_request
+        )
+    });
+
+    let events =
+        calculate_portfolio_stream_with_client(rest_delta_request, &caller, calculation_backend)
+            .instrument(span)
+            .await;
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+pub async fn calculate_portfolio_stream_with_client(
+    rest_delta_request: RestCalculatePortfolioRequest,
+    caller: &Caller,
+    client: impl CalculatePortfolioStreamClient,
+) -> SseEvents {
+    let grpc_delta_request: GrpcCalculatePortfolioRequest = match rest_delta_request.try_into() {
+        Ok(grpc_delta_request) => grpc_delta_request,
+        Err(err) => return error_event_stream(AppError::from(err)),
+    };
+    let grpc_request = caller.grpc_request(grpc_delta_request);
+    match client
+        .calculate_portfolio_stream_request(grpc_request)
+        .await
+    {
+        Ok(grpc_responses) => Box::pin(grpc_responses.map(|grpc_response| {
+            Ok(match grpc_response {
+                Ok(grpc_response) => to_event(grpc_response),
+                Err(status) => error_event(AppError::from(status)),
+            })
+        })),
+        Err(status) => error_event_stream(AppError::from(status)),
+    }
+}
+
+fn to_event(grpc_response: GrpcCalculatePortfolioStreamResponse) -> Event {
+    let event = match grpc_response.result {
+        Some(StreamResult::Bank(grpc_bank)) => {
+            let rest_bank: RestBank = grpc_bank.into();
+            Event::default()
+                .event(BANK_COMPLETED_EVENT)
+                .json_data(rest_bank)
+        }
+        Some(StreamResult::PortfolioSummary(grpc_summary)) => {
+            let rest_summary: RestPortfolioSummary = grpc_summary.into();
+            Event::default()
+                .event(PORTFOLIO_COMPLETED_EVENT)
+                .json_data(rest_summary)
+        }
+        None => return error_event(AppError::InternalServer),
+    };
+    event.unwrap_or_else(|err| {
+        error!("serializing sse event data error: {:?}", err);
+        error_event(AppError::InternalServer)
+    })
+}
+
+fn error_event(app_error: AppError) -> Event {
+    error!("calculation error sent as sse event: {:?}", app_error);
+    // same problem details body as the non streaming endpoint returns
+    let calculation_error = ProblemDetails::from(app_error);
+    Event::default()
+        .event(CALCULATION_ERROR_EVENT)
+        .json_data(calculation_error)
+        .unwrap_or_else(|_| Event::default().event(CALCULATION_ERROR_EVENT))
+}
+
+fn error_event_stream(app_error: AppError) -> SseEvents {
+    Box::pin(once(Ok(error_event(app_error))))
+}
+
+#[cfg(test)]
+// the mock expectations return tonic::Status like the client they stand in for
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::{calculate_portfolio_stream_with_client, MockCalculatePortfolioStreamClient};
+    use crate::auth::Caller;
+    use drive_deposits_proto_grpc_types::generated::{
+        calculate_portfolio_stream_response::Result as StreamResult, Bank as GrpcBank,
+        CalculatePortfolioStreamResponse as GrpcCalculatePortfolioStreamResponse,
+        PortfolioSummary as GrpcPortfolioSummary,
+    };
+    use drive_deposits_rest_types::rest_types::{
+        CalculatePortfolioRequest as RestCalculatePortfolioRequest, NewDelta as RestNewDelta,
+    };
+    use pretty_assertions::assert_eq;
+    use tokio_stream::StreamExt;
+
+    fn rest_request() -> RestCalculatePortfolioRequest {
+        RestCalculatePortfolioRequest {
+            new_banks: vec![],
+            new_delta: RestNewDelta {
+                period: "1".to_string(),
+                period_unit: "Month".to_string(),
+            },
+        }
+    }
+
+    // axum Event only implements Debug, which is enough to check the event name and data
+    async fn collect_events(client: MockCalculatePortfolioStreamClient) -> Vec<String> {
+        calculate_portfolio_stream_with_client(rest_request(), &Caller::default(), client)
+            .await
+            .map(|event| format!("{:?}", event.unwrap()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_calculate_portfolio_stream_bank_then_portfolio_events() {
+        let mut mock_client = MockCalculatePortfolioStreamClient::new();
+        mock_client
+            .expect_calculate_portfolio_stream_request()
+            .returning(|_grpc_request| {
+                let bank = GrpcCalculatePortfolioStreamResponse {
+                    result: Some(StreamResult::Bank(GrpcBank {
+                        name: "VISION-BANK".to_string(),
+                        ..Default::default()
+                    })),
+                };
+                let summary = GrpcCalculatePortfolioStreamResponse {
+                    result: Some(StreamResult::PortfolioSummary(GrpcPortfolioSummary {
+                        uuid: "uuid".to_string(),
+                        bank_count: 1,
+                        ..Default::default()
+                    })),
+                };
+                Ok(Box::pin(tokio_stream::iter(vec![Ok(bank), Ok(summary)])))
+            });
+
+        let events = collect_events(mock_client).await;
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains("bank-completed"));
+        assert!(events[0].contains("VISION-BANK"));
+        assert!(events[1].contains("portfolio-completed"));
+    }
+
+    #[tokio::test]
+    async fn test_calculate_portfolio_stream_status_sent_as_error_event() {
+        let mut mock_client = MockCalculatePortfolioStreamClient::new();
+        mock_client
+            .expect_calculate_portfolio_stream_request()
+            .returning(|_grpc_request| {
+                let bank = GrpcCalculatePortfolioStreamResponse {
+                    result: Some(StreamResult::Bank(GrpcBank::default())),
+                };
+                let halted = tonic::Status::internal("could not send events");
+                Ok(Box::pin(tokio_stream::iter(vec![Ok(bank), Err(halted)])))
+            });
+
+        let events = collect_events(mock_client).await;
+
+        assert_eq!(events.len(), 2);
+        assert!(events[1].contains("calculation-error"));
+        assert!(events[1].contains("could not send events"));
+    }
+}
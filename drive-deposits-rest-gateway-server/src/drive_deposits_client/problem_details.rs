@@ -0,0 +1,284 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tonic::Code;
+use tonic_types::StatusExt;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use drive_deposits_rest_types::csv_import::CsvViolation;
+use drive_deposits_rest_types::rest_types::{escape_pointer_token, field_path_to_pointer};
+
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+// RFC 7807 problem details; type is always about:blank so title is the HTTP status phrase
+// and detail plus violations carry the specifics
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Violation {
+    // RFC 6901 JSON pointer into the request body, for example /new_banks/0/new_deposits/2/apy
+    pub pointer: String,
+    pub code: String,
+    pub message: String,
+    // 1-based CSV row and column when the request was imported from CSV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl From<CsvViolation> for Violation {
+    fn from(csv_violation: CsvViolation) -> Self {
+        Self {
+            pointer: csv_violation.pointer,
+            code: csv_violation.code,
+            message: csv_violation.message,
+            row: csv_violation.row,
+            column: csv_violation.column,
+        }
+    }
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status
+                .canonical_reason()
+                .unwrap_or("Unknown Error")
+                .to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            violations: vec![],
+        }
+    }
+
+    pub fn with_violations(mut self, mut violations: Vec<Violation>) -> Self {
+        violations.sort_by(|first, second| first.pointer.cmp(&second.pointer));
+        self.violations = violations;
+        self
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        (
+            self.status_code(),
+            [(header::CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)],
+            Json(self),
+        )
+            .into_response()
+    }
+}
+
+fn validation_message(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.trim().to_string();
+    }
+    match (error.code.as_ref(), error.params.get("min")) {
+        ("length", Some(min)) => format!("Incorrect length. Must be at least {}.", min),
+        _ => format!("Failed {} validation.", error.code),
+    }
+}
+
+fn collect_validation_violations(
+    parent_pointer: &str,
+    errors: &ValidationErrors,
+    violations: &mut Vec<Violation>,
+) {
+    for (field, kind) in errors.errors() {
+        let pointer = format!("{}/{}", parent_pointer, escape_pointer_token(field));
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                violations.extend(field_errors.iter().map(|error| Violation {
+                    pointer: pointer.clone(),
+                    code: error.code.to_string(),
+                    message: validation_message(error),
+                    row: None,
+                    column: None,
+                }))
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                collect_validation_violations(&pointer, nested, violations)
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect_validation_violations(
+                        &format!("{}/{}", pointer, index),
+                        nested,
+                        violations,
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub fn validation_violations(errors: &ValidationErrors) -> Vec<Violation> {
+    let mut violations = vec![];
+    collect_validation_violations("", errors, &mut violations);
+    violations
+}
+
+// BadRequest details from the calculation server, with codes from the ErrorInfo metadata
+pub fn status_violations(status: &tonic::Status) -> Vec<Violation> {
+    let field_codes = status
+        .get_details_error_info()
+        .map(|error_info| error_info.metadata)
+        .unwrap_or_default();
+    status
+        .get_details_bad_request()
+        .map(|bad_request| {
+            bad_request
+                .field_violations
+                .into_iter()
+                .map(|violation| Violation {
+                    pointer: field_path_to_pointer(&violation.field),
+                    code: field_codes
+                        .get(&violation.field)
+                        .cloned()
+                        .unwrap_or_else(|| "invalid_argument".to_string()),
+                    message: violation.description,
+                    row: None,
+                    column: None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn status_code_for_grpc(code: Code) -> StatusCode {
+    match code {
+        Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::http::{header, StatusCode};
+    use axum::response::IntoResponse;
+    use pretty_assertions::assert_eq;
+    use tonic::Code;
+    use tonic_types::{BadRequest, ErrorInfo, StatusExt};
+    use validator::Validate;
+
+    use drive_deposits_rest_types::rest_types::{
+        CalculatePortfolioRequest as RestCalculatePortfolioRequest, NewBank as RestNewBank,
+        NewDelta as RestNewDelta, NewDeposit as RestNewDeposit,
+    };
+
+    use super::{
+        field_path_to_pointer, status_violations, validation_violations, ProblemDetails,
+        PROBLEM_JSON_CONTENT_TYPE,
+    };
+
+    #[test]
+    fn test_validation_violations_point_into_nested_lists() {
+        let rest_request = RestCalculatePortfolioRequest {
+            new_banks: vec![RestNewBank {
+                name: "VISION-BANK".to_string(),
+                bank_tz: "America/New_York".to_string(),
+                new_deposits: vec![RestNewDeposit {
+                    account: "12".to_string(),
+                    account_type: "Checking".to_string(),
+                    apy: "abc".to_string(),
+                    years: "1".to_string(),
+                    amount: "1000".to_string(),
+                    start_date_in_bank_tz: "2024-02-16".to_string(),
+                }],
+            }],
+            new_delta: RestNewDelta {
+                period: "1".to_string(),
+                period_unit: "Month".to_string(),
+            },
+        };
+        let errors = rest_request.validate().unwrap_err();
+
+        let violations = ProblemDetails::new(StatusCode::BAD_REQUEST, "Input validation error")
+            .with_violations(validation_violations(&errors))
+            .violations;
+        let pointers = violations
+            .iter()
+            .map(|violation| (violation.pointer.as_str(), violation.code.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            pointers,
+            vec![
+                ("/new_banks/0/new_deposits/0/account", "length"),
+                ("/new_banks/0/new_deposits/0/apy", "invalid_decimal"),
+            ]
+        );
+        assert_eq!(
+            violations[0].message,
+            "Incorrect length. Must be at least 4."
+        );
+    }
+
+    #[test]
+    fn test_field_path_to_pointer() {
+        assert_eq!(
+            field_path_to_pointer("new_banks[2].new_deposits[0].apy"),
+            "/new_banks/2/new_deposits/0/apy"
+        );
+        assert_eq!(field_path_to_pointer("new_delta"), "/new_delta");
+    }
+
+    #[test]
+    fn test_status_violations_from_bad_request_details() {
+        let mut bad_request = BadRequest::new(vec![]);
+        bad_request.add_violation("new_banks[1].bank_tz", "Incorrect bank_tz: Mars/Base.");
+        bad_request.add_violation("new_delta", "Missing new_delta.");
+        let error_info = ErrorInfo::new(
+            "FIELD_VIOLATION_CODES",
+            "drinnovations.us",
+            HashMap::from([("new_banks[1].bank_tz".to_string(), "invalid_tz".to_string())]),
+        );
+        let status = tonic::Status::with_error_details_vec(
+            Code::InvalidArgument,
+            "request contains invalid arguments",
+            vec![bad_request.into(), error_info.into()],
+        );
+
+        let violations = status_violations(&status);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].pointer, "/new_banks/1/bank_tz");
+        assert_eq!(violations[0].code, "invalid_tz");
+        assert_eq!(violations[1].code, "invalid_argument");
+    }
+
+    #[test]
+    fn test_problem_details_response_content_type() {
+        let response =
+            ProblemDetails::new(StatusCode::BAD_GATEWAY, "gRPC Server Error").into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+    }
+}
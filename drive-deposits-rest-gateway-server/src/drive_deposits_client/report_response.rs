@@ -0,0 +1,137 @@
+use axum::{
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::debug;
+
+use drive_deposits_rest_types::report::{render_report, ReportFormat};
+use drive_deposits_rest_types::rest_types::CalculatePortfolioResponse as RestCalculatePortfolioResponse;
+
+use super::app_error::Error as AppError;
+
+// json unless the Accept header prefers a csv, markdown or html report of the same response
+pub fn negotiate_report(
+    headers: &HeaderMap,
+    Json(rest_response): Json<RestCalculatePortfolioResponse>,
+) -> Result<Response, AppError> {
+    let report_format = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .and_then(ReportFormat::from_accept);
+    let vary = [(header::VARY, header::ACCEPT.as_str())];
+    match report_format {
+        Some(report_format) => {
+            debug!("rendering {} report", report_format);
+            let report = render_report(&rest_response, report_format)?;
+            Ok((
+                vary,
+                [(header::CONTENT_TYPE, report_format.media_type())],
+                report,
+            )
+                .into_response())
+        }
+        None => Ok((vary, Json(rest_response)).into_response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use axum::Json;
+    use pretty_assertions::assert_eq;
+
+    use drive_deposits_rest_types::rest_types::{
+        Bank as RestBank, CalculatePortfolioResponse as RestCalculatePortfolioResponse,
+        Deposit as RestDeposit, Maturity as RestMaturity, Outcome as RestOutcome,
+        ProcessingError as RestProcessingError,
+    };
+
+    use super::negotiate_report;
+
+    fn rest_response() -> RestCalculatePortfolioResponse {
+        let maturity = RestMaturity {
+            amount: "1000".to_string(),
+            interest: "24.00".to_string(),
+            total: "1024.00".to_string(),
+        };
+        RestCalculatePortfolioResponse {
+            uuid: "uuid".to_string(),
+            banks: vec![RestBank {
+                name: "VISION <BANK>".to_string(),
+                bank_tz: "America/New_York".to_string(),
+                deposits: vec![RestDeposit {
+                    account: "1234".to_string(),
+                    account_type: "Checking".to_string(),
+                    outcome: Some(RestOutcome {
+                        maturity: Some(maturity.clone()),
+                        errors: vec![RestProcessingError {
+                            uuid: "error-uuid".to_string(),
+                            message: "delta growth could not be calculated".to_string(),
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                outcome: Some(RestOutcome {
+                    maturity: Some(maturity.clone()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            outcome: Some(RestOutcome {
+                maturity: Some(maturity),
+                ..Default::default()
+            }),
+            created_at: "created_at".to_string(),
+            tenant_id: None,
+        }
+    }
+
+    async fn negotiate(accept: &str) -> (String, String) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        let response = negotiate_report(&headers, Json(rest_response())).unwrap();
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_report_csv_has_subtotal_and_total_rows() {
+        let (content_type, body) = negotiate("text/csv").await;
+
+        assert_eq!(content_type, "text/csv; charset=utf-8");
+        let lines = body.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("deposit,VISION <BANK>,America/New_York,1234,Checking"));
+        assert!(lines[1].ends_with("1000,24.00,1024.00,delta growth could not be calculated"));
+        assert!(lines[2].starts_with("bank_subtotal,VISION <BANK>,America/New_York,Subtotal"));
+        assert!(lines[3].starts_with("portfolio_total,,,Total,1 banks"));
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_report_html_escapes_and_shows_errors_inline() {
+        let (content_type, body) = negotiate("text/html,application/xhtml+xml,*/*;q=0.8").await;
+
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(body.starts_with("<!DOCTYPE html>"));
+        assert!(body.contains("<h2>VISION &lt;BANK&gt; (America/New_York)</h2>"));
+        assert!(body.contains(r#"<td class="errors">delta growth could not be calculated</td>"#));
+        assert!(body.contains("<tfoot>"));
+    }
+
+    #[tokio::test]
+    async fn test_negotiate_report_markdown_and_json_by_quality() {
+        let (content_type, body) = negotiate("application/json;q=0.5, text/markdown").await;
+        assert_eq!(content_type, "text/markdown; charset=utf-8");
+        assert!(body.contains("| **Subtotal** | **1 deposits** |"));
+
+        let (content_type, _) = negotiate("text/markdown;q=0.5, application/json").await;
+        assert_eq!(content_type, "application/json");
+    }
+}
@@ -1,13 +1,23 @@
-use axum::extract::rejection::JsonRejection;
-use axum::extract::{FromRequest, Request};
-use axum::http::StatusCode;
+use axum::body::Bytes;
+use axum::extract::rejection::{BytesRejection, JsonRejection, QueryRejection};
+use axum::extract::{FromRequest, Query, Request};
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::Deserialize;
 use thiserror::Error as thisError;
 use tracing::info;
+use utoipa::IntoParams;
 use validator::Validate;
 
-use drive_deposits_rest_types::rest_types::CalculatePortfolioRequest;
+use drive_deposits_rest_types::csv_import::{
+    import_portfolio_request, CsvColumnMapping, Error as CsvImportError,
+};
+use drive_deposits_rest_types::rest_types::{
+    CalculatePortfolioRequest, CalculatePortfoliosRequest, NewDelta,
+};
+
+use super::problem_details::{validation_violations, ProblemDetails};
 
 #[derive(Debug, thisError)]
 pub enum Error {
@@ -16,21 +26,55 @@ pub enum Error {
 
     #[error(transparent)]
     RequestJsonRejection(#[from] JsonRejection),
+
+    #[error(transparent)]
+    RequestQueryRejection(#[from] QueryRejection),
+
+    #[error(transparent)]
+    RequestBytesRejection(#[from] BytesRejection),
+
+    #[error("Expected Content-Type text/csv, got {0:?}")]
+    UnsupportedContentType(String),
+
+    #[error(transparent)]
+    CsvImport(#[from] CsvImportError),
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         info!("into response self is {:?}", self);
         match self {
-            Error::Validation(_) => {
-                let message = format!("Input validation error: [{self}]").replace('\n', ", ");
-                (StatusCode::BAD_REQUEST, message)
+            Error::Validation(errors) => {
+                ProblemDetails::new(StatusCode::BAD_REQUEST, "Input validation error")
+                    .with_violations(validation_violations(&errors))
             }
 
-            Error::RequestJsonRejection(err) => {
-                let message = format!("Axum Json Rejection error: {:?}", err);
-                (StatusCode::BAD_REQUEST, message)
+            Error::RequestJsonRejection(err) => ProblemDetails::new(
+                err.status(),
+                format!("Axum Json Rejection error: {}", err.body_text()),
+            ),
+
+            Error::RequestQueryRejection(err) => ProblemDetails::new(
+                err.status(),
+                format!("Axum Query Rejection error: {}", err.body_text()),
+            ),
+
+            Error::RequestBytesRejection(err) => ProblemDetails::new(
+                err.status(),
+                format!("Axum Bytes Rejection error: {}", err.body_text()),
+            ),
+
+            Error::UnsupportedContentType(content_type) => ProblemDetails::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Expected Content-Type text/csv, got {:?}", content_type),
+            ),
+
+            Error::CsvImport(CsvImportError::Violations(violations)) => {
+                ProblemDetails::new(StatusCode::BAD_REQUEST, "CSV import validation error")
+                    .with_violations(violations.into_iter().map(Into::into).collect())
             }
+
+            Error::CsvImport(err) => ProblemDetails::new(StatusCode::BAD_REQUEST, err.to_string()),
         }
         .into_response()
     }
@@ -52,3 +96,67 @@ where
         Ok(ValidateCalculateRequest(value))
     }
 }
+
+#[derive(Debug)]
+pub struct ValidateCalculatePortfoliosRequest(pub CalculatePortfoliosRequest);
+
+impl<S> FromRequest<S> for ValidateCalculatePortfoliosRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<CalculatePortfoliosRequest>::from_request(req, state).await?;
+
+        value.validate()?;
+        Ok(ValidateCalculatePortfoliosRequest(value))
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CsvImportParams {
+    /// Delta period, for example 1
+    pub period: String,
+    /// Delta period unit: Day, Week, Month or Year
+    pub period_unit: String,
+    /// Header names that differ from the defaults (bank, bank_tz, account, account_type, apy,
+    /// years, amount, start_date_in_bank_tz) as field=Header pairs, for example
+    /// bank=Bank Name,apy=APY %
+    #[serde(default)]
+    pub columns: String,
+}
+
+// text/csv body with one row per deposit, grouped into banks and validated like the json request
+#[derive(Debug)]
+pub struct ImportCsvRequest(pub CalculatePortfolioRequest);
+
+impl<S> FromRequest<S> for ImportCsvRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if !content_type.starts_with("text/csv") {
+            return Err(Error::UnsupportedContentType(content_type));
+        }
+        let Query(params) = Query::<CsvImportParams>::try_from_uri(req.uri())?;
+        let body = Bytes::from_request(req, state).await?;
+
+        let mapping: CsvColumnMapping = params.columns.parse()?;
+        let new_delta = NewDelta {
+            period: params.period,
+            period_unit: params.period_unit,
+        };
+        let value = import_portfolio_request(body.as_ref(), &mapping, new_delta)?;
+        Ok(ImportCsvRequest(value))
+    }
+}
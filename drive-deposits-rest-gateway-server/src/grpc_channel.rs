@@ -0,0 +1,558 @@
+use std::env::var;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tonic::metadata::MetadataMap;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+use tonic::{Code, Extensions, Status};
+use tracing::{debug, info, warn};
+
+use drive_deposits_proto_grpc_types::generated::{
+    drive_deposits_service_client::DriveDepositsServiceClient,
+    CalculatePortfolioRequest as GrpcCalculatePortfolioRequest,
+    CalculatePortfolioResponse as GrpcCalculatePortfolioResponse,
+    CalculatePortfoliosRequest as GrpcCalculatePortfoliosRequest,
+    CalculatePortfoliosResponse as GrpcCalculatePortfoliosResponse,
+};
+
+use drive_deposits_rest_types::server_config::{
+    read_pem, ClientTlsFiles, Error as ServerConfigError, ServerConfig,
+};
+
+use crate::drive_deposits_client::{
+    CalculatePortfolioClient, CalculatePortfolioStreamClient, CalculatePortfoliosClient,
+    GrpcStreamResponses,
+};
+use crate::service_router::GATEWAY_REQUEST_TIMEOUT;
+
+// upper bound of the exponential backoff between retries, however many are configured
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid value {value:?} for env variable {name}")]
+    Config { name: &'static str, value: String },
+    #[error("No grpc server address configured in GRPC_SERVER_ADDRESS")]
+    NoAddress,
+    #[error("Tonic transport error: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("Server config error: {0}")]
+    ServerConfig(#[from] ServerConfigError),
+    #[error("Failed to read grpc client TLS file {0}")]
+    TlsFile(#[from] std::io::Error),
+    #[error("grpc_ca_path is set so GRPC_SERVER_ADDRESS {0:?} must be https://")]
+    TlsNeedsHttps(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcChannelConfig {
+    pub addresses: Vec<String>,
+    pub connect_timeout: Duration,
+    // deadline of a single portfolio calculation; batches get the whole gateway timeout and
+    // streams are not limited once they are open
+    pub request_timeout: Duration,
+    pub max_retries: u32,
+    pub retry_backoff: Duration,
+    pub breaker_failure_threshold: u32,
+    pub breaker_open_duration: Duration,
+    // from the [rest_gateway] config; addresses are https:// when set
+    pub tls: Option<ClientTlsFiles>,
+}
+
+impl Default for GrpcChannelConfig {
+    fn default() -> Self {
+        Self {
+            addresses: vec!["http://[::]:50052".to_string()],
+            connect_timeout: Duration::from_secs(2),
+            // the TimeoutLayer of the router, which bounded a calculation before the deadline
+            // was sent; lower it to leave room for retries
+            request_timeout: GATEWAY_REQUEST_TIMEOUT,
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(100),
+            breaker_failure_threshold: 5,
+            breaker_open_duration: Duration::from_secs(30),
+            tls: None,
+        }
+    }
+}
+
+impl GrpcChannelConfig {
+    // GRPC_SERVER_ADDRESS can list several comma separated backends to load balance across,
+    // for docker compose dns GRPC_SERVER_ADDRESS=http://drive-deposits-grpc-server:50052
+    pub fn from_env() -> Result<Self, Error> {
+        let default = Self::default();
+        let addresses = match var("GRPC_SERVER_ADDRESS") {
+            Ok(addresses) => addresses
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => default.addresses,
+        };
+        Ok(Self {
+            addresses,
+            connect_timeout: env_millis("GRPC_CONNECT_TIMEOUT_MS", default.connect_timeout)?,
+            request_timeout: env_millis("GRPC_REQUEST_TIMEOUT_MS", default.request_timeout)?,
+            max_retries: env_parse("GRPC_MAX_RETRIES", default.max_retries)?,
+            retry_backoff: env_millis("GRPC_RETRY_BACKOFF_MS", default.retry_backoff)?,
+            breaker_failure_threshold: env_parse(
+                "GRPC_BREAKER_FAILURE_THRESHOLD",
+                default.breaker_failure_threshold,
+            )?,
+            breaker_open_duration: env_millis(
+                "GRPC_BREAKER_OPEN_MS",
+                default.breaker_open_duration,
+            )?,
+            tls: ServerConfig::load()?.rest_gateway.grpc_client_tls()?,
+        })
+    }
+
+    // no connection is made here; the channel connects on first use and reconnects as needed
+    pub fn lazy_channel(&self) -> Result<Channel, Error> {
+        // tonic would otherwise connect in plaintext and ignore the TLS settings
+        if self.tls.is_some() {
+            if let Some(address) = self
+                .addresses
+                .iter()
+                .find(|address| !address.to_ascii_lowercase().starts_with("https://"))
+            {
+                return Err(Error::TlsNeedsHttps(address.clone()));
+            }
+        }
+        let tls_config = self.tls.as_ref().map(client_tls_config).transpose()?;
+        let endpoints = self
+            .addresses
+            .iter()
+            .map(|address| {
+                let endpoint =
+                    Endpoint::from_shared(address.clone())?.connect_timeout(self.connect_timeout);
+                match &tls_config {
+                    Some(tls_config) => endpoint.tls_config(tls_config.clone()),
+                    None => Ok(endpoint),
+                }
+            })
+            .collect::<Result<Vec<Endpoint>, tonic::transport::Error>>()?;
+        info!("grpc channel endpoints are: {:?}", self.addresses);
+        match endpoints.len() {
+            0 => Err(Error::NoAddress),
+            1 => Ok(endpoints[0].connect_lazy()),
+            _ => Ok(Channel::balance_list(endpoints.into_iter())),
+        }
+    }
+}
+
+// CA bundle to verify the grpc server, and a client certificate when it requires mTLS
+fn client_tls_config(tls: &ClientTlsFiles) -> Result<ClientTlsConfig, Error> {
+    let mut tls_config =
+        ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read_pem(&tls.ca_path)?));
+    if let Some(identity) = &tls.client_identity {
+        tls_config = tls_config.identity(Identity::from_pem(
+            read_pem(&identity.cert_path)?,
+            read_pem(&identity.key_path)?,
+        ));
+    }
+    if let Some(domain_name) = &tls.domain_name {
+        tls_config = tls_config.domain_name(domain_name);
+    }
+    info!("grpc channel uses TLS with CA {:?}", tls.ca_path);
+    Ok(tls_config)
+}
+
+fn env_parse<T: FromStr>(name: &'static str, default: T) -> Result<T, Error> {
+    match var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| Error::Config { name, value }),
+        Err(_) => Ok(default),
+    }
+}
+
+fn env_millis(name: &'static str, default: Duration) -> Result<Duration, Error> {
+    env_parse(name, default.as_millis() as u64).map(Duration::from_millis)
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+// Opens after failure_threshold consecutive backend failures and rejects calls without
+// touching the network until open_duration passes; then calls are let through again and
+// the first success closes it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn allows_call(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        match state.opened_at {
+            Some(opened_at) => opened_at.elapsed() >= self.open_duration,
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.opened_at.is_some() {
+            info!("circuit breaker closed after successful call");
+        }
+        *state = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            warn!(
+                "circuit breaker open after {} consecutive failures",
+                state.consecutive_failures
+            );
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+// only failures that say something about backend health count against the breaker;
+// an InvalidArgument for example is the caller's problem, and DeadlineExceeded or Cancelled
+// come from the caller's deadline or disconnect, so a few slow portfolios do not open it for
+// every caller
+fn is_backend_failure(status: &Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::Unknown)
+}
+
+// Shared by every request through axum State: cloning is cheap and all clones use the same
+// channel and circuit breaker.
+#[derive(Debug, Clone)]
+pub struct DriveDepositsGrpcClient {
+    client: DriveDepositsServiceClient<Channel>,
+    breaker: Arc<CircuitBreaker>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    request_timeout: Duration,
+}
+
+impl DriveDepositsGrpcClient {
+    pub fn new(config: &GrpcChannelConfig) -> Result<Self, Error> {
+        Ok(Self {
+            client: DriveDepositsServiceClient::new(config.lazy_channel()?),
+            breaker: Arc::new(CircuitBreaker::new(
+                config.breaker_failure_threshold,
+                config.breaker_open_duration,
+            )),
+            max_retries: config.max_retries,
+            retry_backoff: config.retry_backoff,
+            request_timeout: config.request_timeout,
+        })
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        Self::new(&GrpcChannelConfig::from_env()?)
+    }
+}
+
+// retries Unavailable with exponential backoff, as nothing reached the server in that case
+pub async fn call_with_retry<T, F, Fut>(
+    breaker: &CircuitBreaker,
+    max_retries: u32,
+    retry_backoff: Duration,
+    mut call: F,
+) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut attempt = 0;
+    loop {
+        if !breaker.allows_call() {
+            return Err(Status::unavailable(
+                "circuit breaker open: drive deposits grpc server is failing, try again later",
+            ));
+        }
+        match call().await {
+            Ok(response) => {
+                breaker.record_success();
+                return Ok(response);
+            }
+            Err(status) => {
+                if is_backend_failure(&status) {
+                    breaker.record_failure();
+                }
+                if status.code() != Code::Unavailable || attempt >= max_retries {
+                    return Err(status);
+                }
+                let backoff = exponential_backoff(retry_backoff, attempt);
+                attempt += 1;
+                debug!(
+                    "grpc call unavailable, retry {} of {} after {:?}: {}",
+                    attempt,
+                    max_retries,
+                    backoff,
+                    status.message()
+                );
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+}
+
+// saturating, so a large GRPC_MAX_RETRIES cannot overflow
+fn exponential_backoff(retry_backoff: Duration, attempt: u32) -> Duration {
+    retry_backoff
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+// a fresh request per attempt that still carries the caller's credentials metadata
+fn retry_request<T: Clone>(metadata: &MetadataMap, message: &T) -> tonic::Request<T> {
+    tonic::Request::from_parts(metadata.clone(), Extensions::default(), message.clone())
+}
+
+#[async_trait]
+impl CalculatePortfolioClient for DriveDepositsGrpcClient {
+    async fn calculate_portfolio_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<tonic::Response<GrpcCalculatePortfolioResponse>, tonic::Status> {
+        let (metadata, _, grpc_request) = request.into_parts();
+        call_with_retry(&self.breaker, self.max_retries, self.retry_backoff, || {
+            let mut client = self.client.clone();
+            let mut grpc_request = retry_request(&metadata, &grpc_request);
+            // sent as grpc-timeout so the server stops calculating once nobody waits for it
+            grpc_request.set_timeout(self.request_timeout);
+            async move { client.calculate_portfolio(grpc_request).await }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl CalculatePortfoliosClient for DriveDepositsGrpcClient {
+    async fn calculate_portfolios_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfoliosRequest>,
+    ) -> Result<tonic::Response<GrpcCalculatePortfoliosResponse>, tonic::Status> {
+        let (metadata, _, grpc_request) = request.into_parts();
+        call_with_retry(&self.breaker, self.max_retries, self.retry_backoff, || {
+            let mut client = self.client.clone();
+            let mut grpc_request = retry_request(&metadata, &grpc_request);
+            // a batch takes longer than one portfolio, so only the gateway timeout bounds it
+            grpc_request.set_timeout(GATEWAY_REQUEST_TIMEOUT);
+            async move { client.calculate_portfolios(grpc_request).await }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl CalculatePortfolioStreamClient for DriveDepositsGrpcClient {
+    // only opening the stream is retried; once banks are flowing a failure is reported as is
+    async fn calculate_portfolio_stream_request(
+        mut self,
+        request: tonic::Request<GrpcCalculatePortfolioRequest>,
+    ) -> Result<GrpcStreamResponses, tonic::Status> {
+        let (metadata, _, grpc_request) = request.into_parts();
+        call_with_retry(&self.breaker, self.max_retries, self.retry_backoff, || {
+            let client = self.client.clone();
+            client.calculate_portfolio_stream_request(retry_request(&metadata, &grpc_request))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+    use tonic::{Code, Status};
+
+    use drive_deposits_rest_types::server_config::ClientTlsFiles;
+
+    use super::{
+        call_with_retry, exponential_backoff, CircuitBreaker, Error, GrpcChannelConfig,
+        MAX_RETRY_BACKOFF,
+    };
+    use crate::service_router::GATEWAY_REQUEST_TIMEOUT;
+
+    #[tokio::test]
+    async fn test_call_with_retry_retries_unavailable_then_succeeds() {
+        let breaker = CircuitBreaker::new(5, Duration::from_secs(30));
+        let attempts = AtomicU32::new(0);
+        let result = call_with_retry(&breaker, 2, Duration::from_millis(1), || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(Status::unavailable("connection refused")),
+                _ => Ok("calculated"),
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "calculated");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(breaker.allows_call());
+    }
+
+    #[tokio::test]
+    async fn test_call_with_retry_does_not_retry_invalid_argument() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Status> =
+            call_with_retry(&breaker, 2, Duration::from_millis(1), || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(Status::invalid_argument("new_banks cannot be empty"))
+            })
+            .await;
+
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(breaker.allows_call());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_rejects_without_calling() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let attempts = AtomicU32::new(0);
+        let unavailable = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), Status>(Status::unavailable("connection refused"))
+        };
+        let _ = call_with_retry(&breaker, 3, Duration::from_millis(1), unavailable).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(!breaker.allows_call());
+
+        let rejected = call_with_retry(&breaker, 3, Duration::from_millis(1), unavailable).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(rejected
+            .unwrap_err()
+            .message()
+            .contains("circuit breaker open"));
+    }
+
+    #[tokio::test]
+    async fn test_caller_deadline_and_cancellation_do_not_open_circuit_breaker() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        for status in [
+            Status::deadline_exceeded("deadline has elapsed"),
+            Status::cancelled("client disconnected"),
+        ] {
+            let result: Result<(), Status> =
+                call_with_retry(&breaker, 2, Duration::from_millis(1), || {
+                    let status = status.clone();
+                    async move { Err(status) }
+                })
+                .await;
+            assert!(result.is_err());
+        }
+
+        assert!(breaker.allows_call());
+    }
+
+    #[test]
+    fn test_exponential_backoff_capped_without_overflow() {
+        let retry_backoff = Duration::from_millis(100);
+        assert_eq!(exponential_backoff(retry_backoff, 0), retry_backoff);
+        assert_eq!(
+            exponential_backoff(retry_backoff, 2),
+            Duration::from_millis(400)
+        );
+        for attempt in [5, 31, 32, 64, u32::MAX] {
+            assert_eq!(
+                exponential_backoff(retry_backoff, attempt),
+                MAX_RETRY_BACKOFF
+            );
+        }
+        assert_eq!(
+            exponential_backoff(Duration::MAX, 1),
+            MAX_RETRY_BACKOFF,
+            "Duration multiplication saturates"
+        );
+    }
+
+    #[test]
+    fn test_request_timeout_defaults_to_gateway_timeout() {
+        assert_eq!(
+            GrpcChannelConfig::default().request_timeout,
+            GATEWAY_REQUEST_TIMEOUT
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_lets_calls_through_after_open_duration() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure();
+        assert!(breaker.allows_call());
+        breaker.record_success();
+        assert!(breaker.allows_call());
+    }
+
+    #[tokio::test]
+    async fn test_lazy_channel_balances_without_connecting() {
+        let config = GrpcChannelConfig {
+            addresses: vec![
+                "http://[::]:50052".to_string(),
+                "http://[::]:50053".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert!(config.lazy_channel().is_ok());
+
+        let config = GrpcChannelConfig {
+            addresses: vec![],
+            ..Default::default()
+        };
+        assert!(config.lazy_channel().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lazy_channel_tls_error_names_the_missing_ca_file() {
+        let config = GrpcChannelConfig {
+            addresses: vec!["https://[::]:50052".to_string()],
+            tls: Some(ClientTlsFiles {
+                ca_path: PathBuf::from("missing/ca.pem"),
+                client_identity: None,
+                domain_name: None,
+            }),
+            ..Default::default()
+        };
+        let err = config.lazy_channel().unwrap_err();
+        assert!(err.to_string().contains("missing/ca.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_lazy_channel_tls_rejects_plaintext_addresses() {
+        let config = GrpcChannelConfig {
+            addresses: vec![
+                "https://[::]:50052".to_string(),
+                "http://[::]:50053".to_string(),
+            ],
+            tls: Some(ClientTlsFiles {
+                ca_path: PathBuf::from("missing/ca.pem"),
+                client_identity: None,
+                domain_name: None,
+            }),
+            ..Default::default()
+        };
+        let err = config.lazy_channel().unwrap_err();
+        assert!(matches!(err, Error::TlsNeedsHttps(address) if address == "http://[::]:50053"));
+    }
+}
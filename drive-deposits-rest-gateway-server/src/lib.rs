@@ -1,2 +1,6 @@
+pub mod auth;
+pub mod calculation_mode;
 pub mod drive_deposits_client;
+pub mod grpc_channel;
+pub mod listener;
 pub mod service_router;
@@ -0,0 +1,64 @@
+use std::future::IntoFuture;
+
+use axum::Router;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::info;
+
+use drive_deposits_grpc_server::shutdown::run_until_shutdown;
+use drive_deposits_rest_types::server_config::{Error as ServerConfigError, RestGatewayConfig};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Server config error: {0}")]
+    ServerConfig(#[from] ServerConfigError),
+
+    #[error("Listener error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// plaintext, or TLS when the [rest_gateway] config has a certificate and key; once shutdown is
+// cancelled no new connections are accepted and in-flight requests get shutdown_timeout_ms
+pub async fn serve(
+    app_router: Router,
+    config: &RestGatewayConfig,
+    shutdown: CancellationToken,
+    calculations: &TaskTracker,
+) -> Result<(), Error> {
+    let bind_address = config.bind_address;
+    let shutdown_timeout = config.shutdown_timeout();
+    match config.tls()? {
+        Some(tls) => {
+            let rustls_config = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+                .map_err(|err| {
+                    std::io::Error::new(err.kind(), format!("{:?}: {}", tls.cert_path, err))
+                })?;
+            info!("listening with TLS on {}", bind_address);
+            let handle = Handle::new();
+            let graceful = handle.clone();
+            let cancelled = shutdown.clone();
+            tokio::spawn(async move {
+                cancelled.cancelled().await;
+                // run_until_shutdown enforces the deadline
+                graceful.graceful_shutdown(None);
+            });
+            let serve = axum_server::bind_rustls(bind_address, rustls_config)
+                .handle(handle)
+                .serve(app_router.into_make_service());
+            run_until_shutdown(serve, &shutdown, calculations, shutdown_timeout).await?;
+        }
+        None => {
+            let listener = TcpListener::bind(bind_address).await?;
+            info!("listening on {}", listener.local_addr()?);
+            let serve = axum::serve(listener, app_router)
+                .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                .into_future();
+            run_until_shutdown(serve, &shutdown, calculations, shutdown_timeout).await?;
+        }
+    }
+    Ok(())
+}
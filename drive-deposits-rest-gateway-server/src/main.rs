@@ -1,9 +1,13 @@
 use std::error::Error;
 
-use tracing::{debug, debug_span, info, Instrument};
+use tokio_util::task::TaskTracker;
+use tracing::{debug, debug_span, Instrument};
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
 
+use drive_deposits_grpc_server::shutdown::shutdown_on_signal;
+use drive_deposits_rest_gateway_server::listener::serve;
 use drive_deposits_rest_gateway_server::service_router::router;
+use drive_deposits_rest_types::server_config::ServerConfig;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -31,13 +35,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
         None
     });
     span.in_scope(|| debug!("router is being set up first up"));
-    let app_router = router().instrument(span.clone()).await;
+    let shutdown = shutdown_on_signal();
+    let calculations = TaskTracker::new();
+    let app_router = router(calculations.clone())
+        .instrument(span.clone())
+        .await?;
 
     let span = tracing::span!(tracing::Level::INFO, "server");
     // run it
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    let listening_on = listener.local_addr()?;
-    span.in_scope(|| info!("listening on {}", listening_on));
-    axum::serve(listener, app_router).await?;
+    let config = ServerConfig::load()?.rest_gateway;
+    serve(app_router, &config, shutdown, &calculations)
+        .instrument(span)
+        .await?;
     Ok(())
 }
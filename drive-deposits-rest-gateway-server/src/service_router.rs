@@ -3,10 +3,14 @@ use std::time::Duration;
 
 use axum::{
     body::Bytes,
-    http::header,
+    http::{header, HeaderName},
+    middleware::from_fn_with_state,
+    response::Html,
     routing::{get, post},
-    Router,
+    Json, Router,
 };
+use thiserror::Error;
+use tokio_util::task::TaskTracker;
 use tower::ServiceBuilder;
 use tower_http::{
     timeout::TimeoutLayer,
@@ -14,21 +18,94 @@ use tower_http::{
     LatencyUnit, ServiceBuilderExt,
 };
 use tracing::{info, instrument};
+use utoipa::OpenApi;
 
-use crate::drive_deposits_client::calculate_portfolio;
+use drive_deposits_rest_types::auth::{Authenticator, Error as AuthError, API_KEY_HEADER};
+use drive_deposits_rest_types::openapi::{swagger_ui_html, SecurityAddon};
+use drive_deposits_rest_types::rest_types::{
+    Bank, CalculatePortfolioRequest, CalculatePortfolioResponse, CalculatePortfoliosRequest,
+    CalculatePortfoliosResponse, PortfolioSummary,
+};
+
+use crate::auth::authenticate;
+use crate::calculation_mode::{CalculationBackend, Error as CalculationModeError};
+use crate::drive_deposits_client::{
+    self, calculate_portfolio, calculate_portfolio_csv, calculate_portfolio_stream,
+    calculate_portfolios, ProblemDetails, Violation,
+};
+
+// every request through the gateway; calls to the grpc server must finish within it
+pub const GATEWAY_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 const CALCULATE_PORTFOLIO: &str = "/api/drive-deposits/calculate-portfolio";
+const CALCULATE_PORTFOLIO_STREAM: &str = "/api/drive-deposits/calculate-portfolio/stream";
+const CALCULATE_PORTFOLIO_CSV: &str = "/api/drive-deposits/calculate-portfolio/csv";
+const CALCULATE_PORTFOLIOS: &str = "/api/drive-deposits/calculate-portfolios";
+const OPENAPI_JSON: &str = "/api-docs/openapi.json";
+const SWAGGER_UI: &str = "/swagger-ui";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Calculation mode error: {0}")]
+    CalculationMode(#[from] CalculationModeError),
+
+    #[error("Authentication config error: {0}")]
+    Auth(#[from] AuthError),
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Drive Deposits REST Gateway"),
+    modifiers(&SecurityAddon),
+    paths(
+        drive_deposits_client::calculate_portfolio,
+        drive_deposits_client::portfolio_stream::calculate_portfolio_stream,
+        drive_deposits_client::portfolio_batch::calculate_portfolios,
+        drive_deposits_client::portfolio_csv::calculate_portfolio_csv
+    ),
+    components(schemas(
+        CalculatePortfolioRequest,
+        CalculatePortfolioResponse,
+        CalculatePortfoliosRequest,
+        CalculatePortfoliosResponse,
+        Bank,
+        PortfolioSummary,
+        ProblemDetails,
+        Violation
+    ))
+)]
+pub struct ApiDoc;
+
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+async fn swagger_ui() -> Html<String> {
+    // relative to /swagger-ui so a path prefix in front of the gateway still works
+    Html(swagger_ui_html(
+        "Drive Deposits REST Gateway",
+        OPENAPI_JSON.trim_start_matches('/'),
+    ))
+}
 
 pub async fn root() -> String {
     format!(
-        "For Calculate Drive Deposits API, use POST with Path {}",
-        CALCULATE_PORTFOLIO
-    )
+        "For Calculate Drive Deposits API, use POST with Path {}; for Server-Sent Events per bank as calculated, use POST with Path {}; for a batch of portfolios, use POST with Path {}; for deposits as text/csv rows, use POST with Path {}",
+        CALCULATE_PORTFOLIO, CALCULATE_PORTFOLIO_STREAM, CALCULATE_PORTFOLIOS, CALCULATE_PORTFOLIO_CSV
+    ) + &format!("; API contract at {} and Swagger UI at {}", OPENAPI_JSON, SWAGGER_UI)
 }
 
-#[instrument]
-pub async fn router() -> Router {
-    let sensitive_headers: Arc<[_]> = vec![header::AUTHORIZATION].into();
+#[instrument(skip(calculations))]
+pub async fn router(calculations: TaskTracker) -> Result<Router, Error> {
+    // built once so every request shares the same lazily connected channel or in-process calculator
+    let calculation_backend = CalculationBackend::from_env(calculations).await?;
+    let authenticator = Authenticator::from_env()?;
+    info!("authenticator is {:?}", authenticator);
+    let sensitive_headers: Arc<[_]> = vec![
+        header::AUTHORIZATION,
+        HeaderName::from_static(API_KEY_HEADER),
+    ]
+    .into();
 
     let middleware = ServiceBuilder::new()
         .sensitive_request_headers(sensitive_headers.clone())
@@ -41,11 +118,44 @@ pub async fn router() -> Router {
                 .on_response(DefaultOnResponse::new().include_headers(true).latency_unit(LatencyUnit::Micros)),
         )
         .sensitive_response_headers(sensitive_headers)
-        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        .layer(TimeoutLayer::new(GATEWAY_REQUEST_TIMEOUT))
         .compression();
     info!("Creating router");
-    Router::new()
-        .route("/", get(root).post(root))
+    // only the calculate routes need credentials; root and the API docs stay open
+    let api_routes = Router::new()
         .route(CALCULATE_PORTFOLIO, post(calculate_portfolio))
+        .route(CALCULATE_PORTFOLIO_STREAM, post(calculate_portfolio_stream))
+        .route(CALCULATE_PORTFOLIOS, post(calculate_portfolios))
+        .route(CALCULATE_PORTFOLIO_CSV, post(calculate_portfolio_csv))
+        .route_layer(from_fn_with_state(authenticator, authenticate));
+    Ok(Router::new()
+        .route("/", get(root).post(root))
+        .merge(api_routes)
+        .route(OPENAPI_JSON, get(openapi_json))
+        .route(SWAGGER_UI, get(swagger_ui))
         .layer(middleware)
+        .with_state(calculation_backend))
+}
+
+#[cfg(test)]
+mod tests {
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+
+    #[test]
+    fn test_openapi_document_has_paths_and_enum_values() {
+        let openapi_json = ApiDoc::openapi().to_json().unwrap();
+
+        assert!(openapi_json.contains(r#""openapi":"3.1.0""#));
+        assert!(openapi_json.contains("/api/drive-deposits/calculate-portfolio/stream"));
+        assert!(openapi_json.contains("/api/drive-deposits/calculate-portfolios"));
+        assert!(openapi_json.contains(r#""text/csv""#));
+        assert!(openapi_json.contains(
+            r#""enum":["Checking","Savings","CertificateOfDeposit","BrokerageCertificateOfDeposit"]"#
+        ));
+        assert!(openapi_json.contains(r#""enum":["Day","Week","Month","Year"]"#));
+        assert!(openapi_json
+            .contains(r#""bearer_jwt":{"type":"http","scheme":"bearer","bearerFormat":"JWT"}"#));
+    }
 }
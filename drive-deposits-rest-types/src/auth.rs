@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::env::var;
+use std::fmt;
+use std::str::FromStr;
+
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// the same credentials are accepted by the rest gateway, the grpc server and the dynamodb reader;
+// the gateway forwards them to the grpc server as metadata with the same names
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+pub const API_KEY_HEADER: &str = "x-api-key";
+// forwarded the same way, and carried into the events of the calculation it started
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+// tenant of every caller when authentication is disabled, and of events sent without a tenant
+pub const DEFAULT_TENANT_ID: &str = "default";
+const MAX_TENANT_ID_LENGTH: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("No credentials configured; set DRIVE_DEPOSITS_JWT_SECRET or DRIVE_DEPOSITS_API_KEYS, or DRIVE_DEPOSITS_AUTH_DISABLED=true for local development")]
+    NotConfigured,
+
+    #[error("Invalid DRIVE_DEPOSITS_API_KEYS entry {0:?}; expected tenant_id=api_key pairs separated by commas")]
+    InvalidApiKeys(String),
+
+    #[error("Missing credentials; send Authorization: Bearer <jwt> or an x-api-key header")]
+    MissingCredentials,
+
+    #[error("Unsupported Authorization scheme; expected Bearer")]
+    UnsupportedScheme,
+
+    #[error("Bearer tokens are not accepted, only API keys")]
+    TokensNotAccepted,
+
+    #[error("Invalid bearer token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+
+    #[error("Unknown API key")]
+    UnknownApiKey,
+
+    #[error("Invalid tenant_id {0:?}; use 1 to 64 ASCII letters, digits, '-' or '_'")]
+    InvalidTenantId(String),
+}
+
+// Restricted to characters that can go as is into the # separated DynamoDB keys
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TenantId(String);
+
+impl TenantId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for TenantId {
+    fn default() -> Self {
+        Self(DEFAULT_TENANT_ID.to_string())
+    }
+}
+
+impl FromStr for TenantId {
+    type Err = Error;
+
+    fn from_str(tenant_id: &str) -> Result<Self, Self::Err> {
+        let valid = !tenant_id.is_empty()
+            && tenant_id.len() <= MAX_TENANT_ID_LENGTH
+            && tenant_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::InvalidTenantId(tenant_id.to_string()));
+        }
+        Ok(Self(tenant_id.to_string()))
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// HS256 bearer token claims; exp is required
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub tenant_id: String,
+    pub exp: u64,
+}
+
+pub fn encode_token(jwt_secret: &str, claims: &Claims) -> Result<String, Error> {
+    Ok(encode(
+        &Header::new(Algorithm::HS256),
+        claims,
+        &EncodingKey::from_secret(jwt_secret.as_bytes()),
+    )?)
+}
+
+#[derive(Clone)]
+pub struct Authenticator {
+    jwt_key: Option<DecodingKey>,
+    // api key to the tenant it belongs to
+    api_keys: HashMap<String, TenantId>,
+    disabled: bool,
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("accepts_tokens", &self.jwt_key.is_some())
+            .field("api_key_count", &self.api_keys.len())
+            .field("disabled", &self.disabled)
+            .finish()
+    }
+}
+
+impl Authenticator {
+    pub fn new(jwt_secret: Option<&str>, api_keys: HashMap<String, TenantId>) -> Self {
+        Self {
+            jwt_key: jwt_secret.map(|secret| DecodingKey::from_secret(secret.as_bytes())),
+            api_keys,
+            disabled: false,
+        }
+    }
+
+    // every caller is the default tenant, credentials or not
+    pub fn disabled() -> Self {
+        Self {
+            jwt_key: None,
+            api_keys: HashMap::new(),
+            disabled: true,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        let disabled = var("DRIVE_DEPOSITS_AUTH_DISABLED")
+            .map(|disabled| disabled.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if disabled {
+            return Ok(Self::disabled());
+        }
+        let jwt_secret = var("DRIVE_DEPOSITS_JWT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let api_keys = match var("DRIVE_DEPOSITS_API_KEYS") {
+            Ok(api_keys) => parse_api_keys(&api_keys)?,
+            Err(_) => HashMap::new(),
+        };
+        if jwt_secret.is_none() && api_keys.is_empty() {
+            return Err(Error::NotConfigured);
+        }
+        Ok(Self::new(jwt_secret.as_deref(), api_keys))
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    // authorization is the full Authorization header value; a bearer token wins over an api key
+    pub fn authenticate(
+        &self,
+        authorization: Option<&str>,
+        api_key: Option<&str>,
+    ) -> Result<TenantId, Error> {
+        if self.disabled {
+            return Ok(TenantId::default());
+        }
+        match (authorization, api_key) {
+            (Some(authorization), _) => self.authenticate_token(authorization),
+            (None, Some(api_key)) => self
+                .api_keys
+                .get(api_key.trim())
+                .cloned()
+                .ok_or(Error::UnknownApiKey),
+            (None, None) => Err(Error::MissingCredentials),
+        }
+    }
+
+    fn authenticate_token(&self, authorization: &str) -> Result<TenantId, Error> {
+        let (scheme, token) = authorization
+            .trim()
+            .split_once(' ')
+            .ok_or(Error::UnsupportedScheme)?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return Err(Error::UnsupportedScheme);
+        }
+        let jwt_key = self.jwt_key.as_ref().ok_or(Error::TokensNotAccepted)?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        let claims = decode::<Claims>(token.trim(), jwt_key, &validation)?.claims;
+        claims.tenant_id.parse()
+    }
+}
+
+// tenant-a=key-1,tenant-b=key-2; a tenant can have several keys
+pub fn parse_api_keys(api_keys: &str) -> Result<HashMap<String, TenantId>, Error> {
+    api_keys
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (tenant_id, api_key) = pair
+                .split_once('=')
+                .filter(|(_, api_key)| !api_key.trim().is_empty())
+                .ok_or_else(|| Error::InvalidApiKeys(pair.to_string()))?;
+            Ok((api_key.trim().to_string(), tenant_id.trim().parse()?))
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+// source of every event and of the CloudEvents envelope around its detail, unless configured otherwise
+pub const EVENT_SOURCE: &str = "drive-deposits";
+pub const SPEC_VERSION: &str = "1.0";
+pub const DATA_CONTENT_TYPE: &str = "application/json";
+// bumped with any breaking change to the payload types sent as data
+pub const DATA_VERSION: u32 = 1;
+
+// CloudEvents 1.0 JSON envelope sent as the EventBridge detail. EventBridge rules still match on
+// source and detail-type, which stay as they were.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudEvent<T> {
+    pub specversion: String,
+    pub id: String,
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub time: String,
+    pub datacontenttype: String,
+    pub dataschema: String,
+    // uuid of the portfolio the event belongs to
+    pub subject: String,
+    // extension attribute; CloudEvents attribute names are lowercase letters and digits only
+    pub correlationid: String,
+    // the payload, unless it was too large for an event and is stored at dataref instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    // claim check reference to the stored payload
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dataref: Option<String>,
+}
+
+impl<T> CloudEvent<T> {
+    pub fn new(
+        id: String,
+        source: &str,
+        detail_type: &str,
+        subject: String,
+        correlation_id: String,
+        data: T,
+    ) -> Self {
+        Self {
+            specversion: SPEC_VERSION.to_string(),
+            id,
+            source: source.to_string(),
+            event_type: event_type(source, detail_type),
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            datacontenttype: DATA_CONTENT_TYPE.to_string(),
+            dataschema: data_schema(source, detail_type),
+            subject,
+            correlationid: correlation_id,
+            data: Some(data),
+            dataref: None,
+        }
+    }
+}
+
+// for example drive-deposits.portfolio-level.v1
+pub fn event_type(source: &str, detail_type: &str) -> String {
+    format!("{}.{}.v{}", source, detail_type, DATA_VERSION)
+}
+
+// the version an event type ends with, the 1 of drive-deposits.portfolio-level.v1
+pub fn data_version(event_type: &str) -> Option<u32> {
+    event_type.rsplit_once(".v")?.1.parse().ok()
+}
+
+pub fn data_schema(source: &str, detail_type: &str) -> String {
+    format!("urn:{}:schema:{}:v{}", source, detail_type, DATA_VERSION)
+}
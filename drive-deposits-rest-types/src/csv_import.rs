@@ -0,0 +1,358 @@
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+use csv::{ReaderBuilder, StringRecord, Trim};
+use thiserror::Error;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::rest_types::{
+    escape_pointer_token, CalculatePortfolioRequest, NewBank, NewDelta, NewDeposit,
+};
+
+// one flat CSV row per deposit; rows of the same bank are grouped into one NewBank in the order
+// the banks first appear
+pub const CSV_FIELDS: [&str; 8] = [
+    "bank",
+    "bank_tz",
+    "account",
+    "account_type",
+    "apy",
+    "years",
+    "amount",
+    "start_date_in_bank_tz",
+];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("Invalid column mapping {0:?}; expected field=Header pairs separated by commas, with fields from: {fields}", fields = CSV_FIELDS.join(", "))]
+    InvalidMapping(String),
+
+    #[error("Missing column {column:?} for {field} in CSV header")]
+    MissingColumn { field: &'static str, column: String },
+
+    #[error("{}", CsvViolations(.0))]
+    Violations(Vec<CsvViolation>),
+}
+
+// row and column are 1-based like in a spreadsheet, with the header on row 1; violations that
+// are not about a cell, such as new_delta, have neither
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvViolation {
+    pub row: Option<u64>,
+    pub column: Option<usize>,
+    // RFC 6901 JSON pointer into the imported request, for example /new_banks/0/new_deposits/2/apy
+    pub pointer: String,
+    pub code: String,
+    pub message: String,
+}
+
+struct CsvViolations<'a>(&'a [CsvViolation]);
+
+impl fmt::Display for CsvViolations<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self
+            .0
+            .iter()
+            .map(|violation| {
+                let field = violation.pointer.rsplit('/').next().unwrap_or_default();
+                match (violation.row, violation.column) {
+                    (Some(row), Some(column)) => format!(
+                        "row {}, column {} ({}): {}",
+                        row, column, field, violation.message
+                    ),
+                    (Some(row), None) => format!("row {} ({}): {}", row, field, violation.message),
+                    _ => format!("{}: {}", violation.pointer, violation.message),
+                }
+            })
+            .collect::<Vec<String>>();
+        write!(f, "CSV import errors: {}", lines.join("; "))
+    }
+}
+
+// Header name of each field; defaults to the field name itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvColumnMapping {
+    pub bank: String,
+    pub bank_tz: String,
+    pub account: String,
+    pub account_type: String,
+    pub apy: String,
+    pub years: String,
+    pub amount: String,
+    pub start_date_in_bank_tz: String,
+}
+
+impl Default for CsvColumnMapping {
+    fn default() -> Self {
+        let [bank, bank_tz, account, account_type, apy, years, amount, start_date_in_bank_tz] =
+            CSV_FIELDS.map(str::to_string);
+        Self {
+            bank,
+            bank_tz,
+            account,
+            account_type,
+            apy,
+            years,
+            amount,
+            start_date_in_bank_tz,
+        }
+    }
+}
+
+// bank=Bank Name,apy=APY %  overrides only the listed fields
+impl FromStr for CsvColumnMapping {
+    type Err = Error;
+
+    fn from_str(mapping: &str) -> Result<Self, Self::Err> {
+        let mut column_mapping = Self::default();
+        for pair in mapping.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (field, column) = pair
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidMapping(mapping.to_string()))?;
+            let column = column.trim().to_string();
+            match field.trim() {
+                "bank" => column_mapping.bank = column,
+                "bank_tz" => column_mapping.bank_tz = column,
+                "account" => column_mapping.account = column,
+                "account_type" => column_mapping.account_type = column,
+                "apy" => column_mapping.apy = column,
+                "years" => column_mapping.years = column,
+                "amount" => column_mapping.amount = column,
+                "start_date_in_bank_tz" => column_mapping.start_date_in_bank_tz = column,
+                _ => return Err(Error::InvalidMapping(mapping.to_string())),
+            }
+        }
+        Ok(column_mapping)
+    }
+}
+
+impl CsvColumnMapping {
+    fn columns(&self) -> [(&'static str, &str); 8] {
+        [
+            (CSV_FIELDS[0], self.bank.as_str()),
+            (CSV_FIELDS[1], self.bank_tz.as_str()),
+            (CSV_FIELDS[2], self.account.as_str()),
+            (CSV_FIELDS[3], self.account_type.as_str()),
+            (CSV_FIELDS[4], self.apy.as_str()),
+            (CSV_FIELDS[5], self.years.as_str()),
+            (CSV_FIELDS[6], self.amount.as_str()),
+            (CSV_FIELDS[7], self.start_date_in_bank_tz.as_str()),
+        ]
+    }
+}
+
+// 0-based header index of each field, in CSV_FIELDS order
+struct ColumnIndexes([usize; 8]);
+
+impl ColumnIndexes {
+    fn from_headers(headers: &StringRecord, mapping: &CsvColumnMapping) -> Result<Self, Error> {
+        let mut indexes = [0; 8];
+        for (index, (field, column)) in mapping.columns().into_iter().enumerate() {
+            indexes[index] = headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+                .ok_or_else(|| Error::MissingColumn {
+                    field,
+                    column: column.to_string(),
+                })?;
+        }
+        Ok(Self(indexes))
+    }
+
+    fn value(&self, record: &StringRecord, field: &str) -> String {
+        self.index(field)
+            .and_then(|index| record.get(index))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn index(&self, field: &str) -> Option<usize> {
+        let position = CSV_FIELDS.iter().position(|name| *name == field)?;
+        Some(self.0[position])
+    }
+
+    // 1-based for spreadsheets; name is the NewBank field for the bank column
+    fn column(&self, field: &str) -> Option<usize> {
+        let field = if field == "name" { "bank" } else { field };
+        self.index(field).map(|index| index + 1)
+    }
+}
+
+// CSV row numbers of each bank's deposits, to point validation errors back at the rows
+struct RowNumbers(Vec<Vec<u64>>);
+
+pub fn import_portfolio_request<R: Read>(
+    reader: R,
+    mapping: &CsvColumnMapping,
+    new_delta: NewDelta,
+) -> Result<CalculatePortfolioRequest, Error> {
+    let mut csv_reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+    let columns = ColumnIndexes::from_headers(csv_reader.headers()?, mapping)?;
+
+    let mut new_banks: Vec<NewBank> = vec![];
+    let mut row_numbers = RowNumbers(vec![]);
+    let mut violations = vec![];
+    for record in csv_reader.records() {
+        let record = record?;
+        let row = record
+            .position()
+            .map(|position| position.line())
+            .unwrap_or_default();
+        let name = columns.value(&record, "bank");
+        let bank_tz = columns.value(&record, "bank_tz");
+        let new_deposit = NewDeposit {
+            account: columns.value(&record, "account"),
+            account_type: columns.value(&record, "account_type"),
+            apy: columns.value(&record, "apy"),
+            years: columns.value(&record, "years"),
+            amount: columns.value(&record, "amount"),
+            start_date_in_bank_tz: columns.value(&record, "start_date_in_bank_tz"),
+        };
+
+        let bank_index = match new_banks.iter().position(|bank| bank.name == name) {
+            Some(bank_index) => bank_index,
+            None => {
+                new_banks.push(NewBank {
+                    name,
+                    bank_tz: bank_tz.clone(),
+                    new_deposits: vec![],
+                });
+                row_numbers.0.push(vec![]);
+                new_banks.len() - 1
+            }
+        };
+        let bank = &mut new_banks[bank_index];
+        if bank.bank_tz != bank_tz {
+            violations.push(CsvViolation {
+                row: Some(row),
+                column: columns.column("bank_tz"),
+                pointer: format!("/new_banks/{}/bank_tz", bank_index),
+                code: "inconsistent_bank_tz".to_string(),
+                message: format!(
+                    "Incorrect bank_tz: {}. Bank {} already has bank_tz {} on an earlier row.",
+                    bank_tz, bank.name, bank.bank_tz
+                ),
+            });
+        }
+        bank.new_deposits.push(new_deposit);
+        row_numbers.0[bank_index].push(row);
+    }
+
+    let portfolio_request = CalculatePortfolioRequest {
+        new_banks,
+        new_delta,
+    };
+    if let Err(errors) = portfolio_request.validate() {
+        collect_violations(&errors, &columns, &row_numbers, &mut violations);
+    }
+    if !violations.is_empty() {
+        violations.sort_by_key(|violation| (violation.row, violation.column));
+        return Err(Error::Violations(violations));
+    }
+    Ok(portfolio_request)
+}
+
+fn collect_violations(
+    errors: &ValidationErrors,
+    columns: &ColumnIndexes,
+    row_numbers: &RowNumbers,
+    violations: &mut Vec<CsvViolation>,
+) {
+    for (field, kind) in errors.errors() {
+        match (field.as_ref(), kind) {
+            ("new_banks", ValidationErrorsKind::List(banks)) => {
+                for (bank_index, bank_errors) in banks {
+                    collect_bank_violations(
+                        *bank_index,
+                        bank_errors,
+                        columns,
+                        row_numbers,
+                        violations,
+                    );
+                }
+            }
+            (_, ValidationErrorsKind::Struct(nested)) => {
+                push_field_violations(
+                    nested,
+                    &format!("/{}", escape_pointer_token(field)),
+                    None,
+                    |_| None,
+                    violations,
+                );
+            }
+            (_, ValidationErrorsKind::Field(field_errors)) => {
+                let pointer = format!("/{}", escape_pointer_token(field));
+                violations.extend(field_errors.iter().map(|error| CsvViolation {
+                    row: None,
+                    column: None,
+                    pointer: pointer.clone(),
+                    code: error.code.to_string(),
+                    message: message(error, field),
+                }));
+            }
+            (_, ValidationErrorsKind::List(_)) => {}
+        }
+    }
+}
+
+fn collect_bank_violations(
+    bank_index: usize,
+    bank_errors: &ValidationErrors,
+    columns: &ColumnIndexes,
+    row_numbers: &RowNumbers,
+    violations: &mut Vec<CsvViolation>,
+) {
+    let bank_rows = &row_numbers.0[bank_index];
+    let bank_pointer = format!("/new_banks/{}", bank_index);
+    // name and bank_tz are taken from the first row of the bank
+    push_field_violations(
+        bank_errors,
+        &bank_pointer,
+        bank_rows.first().copied(),
+        |field| columns.column(field),
+        violations,
+    );
+    if let Some(ValidationErrorsKind::List(deposits)) = bank_errors.errors().get("new_deposits") {
+        for (deposit_index, deposit_errors) in deposits {
+            push_field_violations(
+                deposit_errors,
+                &format!("{}/new_deposits/{}", bank_pointer, deposit_index),
+                bank_rows.get(*deposit_index).copied(),
+                |field| columns.column(field),
+                violations,
+            );
+        }
+    }
+}
+
+fn push_field_violations(
+    errors: &ValidationErrors,
+    parent_pointer: &str,
+    row: Option<u64>,
+    column: impl Fn(&str) -> Option<usize>,
+    violations: &mut Vec<CsvViolation>,
+) {
+    for (field, kind) in errors.errors() {
+        if let ValidationErrorsKind::Field(field_errors) = kind {
+            let pointer = format!("{}/{}", parent_pointer, escape_pointer_token(field));
+            violations.extend(field_errors.iter().map(|error| CsvViolation {
+                row,
+                column: row.and(column(field)),
+                pointer: pointer.clone(),
+                code: error.code.to_string(),
+                message: message(error, field),
+            }));
+        }
+    }
+}
+
+fn message(error: &validator::ValidationError, field: &str) -> String {
+    match &error.message {
+        Some(message) => message.trim().to_string(),
+        None => format!("Incorrect {}. Failed {} validation.", field, error.code),
+    }
+}
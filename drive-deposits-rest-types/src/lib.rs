@@ -1 +1,7 @@
+pub mod auth;
+pub mod cloud_event;
+pub mod csv_import;
+pub mod openapi;
+pub mod report;
 pub mod rest_types;
+pub mod server_config;
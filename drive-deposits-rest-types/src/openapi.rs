@@ -0,0 +1,86 @@
+use strum::VariantNames;
+use utoipa::openapi::schema::{Object, ObjectBuilder, Type};
+use utoipa::openapi::security::{
+    ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme,
+};
+use utoipa::Modify;
+
+use crate::auth::API_KEY_HEADER;
+use crate::rest_types::{AccountType, DeltaPeriodUnit};
+
+// account_type and period_unit are strings on the wire, validated against these enums
+fn string_enum_schema(variant_names: &[&'static str], description: &str) -> Object {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .description(Some(description))
+        .enum_values(Some(
+            variant_names
+                .iter()
+                .copied()
+                .filter(|name| *name != "Unspecified"),
+        ))
+        .build()
+}
+
+pub fn account_type_schema() -> Object {
+    string_enum_schema(AccountType::VARIANTS, "Type of the deposit account")
+}
+
+pub fn period_unit_schema() -> Object {
+    string_enum_schema(
+        DeltaPeriodUnit::VARIANTS,
+        "Unit of the period the delta growth is calculated for",
+    )
+}
+
+// Bearer JWT or x-api-key on every path, as documented for both the gateway and the reader;
+// add with #[openapi(modifiers(&SecurityAddon))]
+pub struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_jwt",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        openapi.security = Some(vec![
+            SecurityRequirement::new("bearer_jwt", Vec::<String>::new()),
+            SecurityRequirement::new("api_key", Vec::<String>::new()),
+        ]);
+    }
+}
+
+// Swagger UI assets come from the swagger-ui-dist package on a CDN, so nothing is bundled.
+// openapi_url is relative so it still resolves behind an API Gateway stage prefix.
+pub fn swagger_ui_html(title: &str, openapi_url: &str) -> String {
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>{title}</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {{
+      window.ui = SwaggerUIBundle({{ url: "{openapi_url}", dom_id: "#swagger-ui" }});
+    }};
+  </script>
+</body>
+</html>
+"##
+    )
+}
@@ -0,0 +1,372 @@
+use std::fmt::Write;
+
+use strum_macros::{Display, EnumString, VariantNames};
+use thiserror::Error;
+
+use crate::rest_types::{Bank, CalculatePortfolioResponse, Deposit, Outcome, ProcessingError};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("CSV report error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("CSV report is not valid UTF-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, VariantNames)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReportFormat {
+    Csv,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "text/csv; charset=utf-8",
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    // The report format the Accept header prefers, or None when json (or anything) ranks at
+    // least as high, so clients that send no Accept header or */* keep getting json
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut media_ranges = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parts = media_range.split(';').map(str::trim);
+                let media_type = parts.next().filter(|media_type| !media_type.is_empty())?;
+                let quality = parts
+                    .filter_map(|param| param.strip_prefix("q="))
+                    .find_map(|quality| quality.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((media_type.to_ascii_lowercase(), quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<(String, f32)>>();
+        // stable, so equally ranked media ranges keep the order they were sent in
+        media_ranges.sort_by(|first, second| second.1.total_cmp(&first.1));
+
+        for (media_type, _) in media_ranges {
+            match media_type.as_str() {
+                "text/csv" => return Some(ReportFormat::Csv),
+                "text/markdown" => return Some(ReportFormat::Markdown),
+                "text/html" => return Some(ReportFormat::Html),
+                "application/json" | "application/*" | "*/*" => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+pub fn render_report(
+    response: &CalculatePortfolioResponse,
+    report_format: ReportFormat,
+) -> Result<String, Error> {
+    match report_format {
+        ReportFormat::Csv => render_csv(response),
+        ReportFormat::Markdown => Ok(render_markdown(response)),
+        ReportFormat::Html => Ok(render_html(response)),
+    }
+}
+
+const DEPOSIT_HEADERS: [&str; 12] = [
+    "Account",
+    "Account type",
+    "APY",
+    "Years",
+    "Start date",
+    "Maturity date",
+    "Delta period",
+    "Delta growth",
+    "Amount",
+    "Interest",
+    "Total",
+    "Errors",
+];
+
+const TOTAL_HEADERS: [&str; 7] = [
+    "Banks",
+    "Delta period",
+    "Delta growth",
+    "Amount",
+    "Interest",
+    "Total",
+    "Errors",
+];
+
+// same columns as DEPOSIT_HEADERS
+fn deposit_cells(deposit: &Deposit) -> Vec<String> {
+    let outcome_with_dates = deposit.outcome_with_dates.as_ref();
+    let mut cells = vec![
+        deposit.account.clone(),
+        deposit.account_type.clone(),
+        deposit.apy.clone(),
+        deposit.years.clone(),
+        outcome_with_dates
+            .map(|dates| dates.start_date_in_bank_tz.clone())
+            .unwrap_or_default(),
+        outcome_with_dates
+            .and_then(|dates| dates.maturity_date_in_bank_tz.clone())
+            .unwrap_or_default(),
+    ];
+    cells.extend(outcome_cells(deposit.outcome.as_ref()));
+    let errors = [
+        errors_text(deposit.outcome.as_ref().map(|outcome| &outcome.errors)),
+        errors_text(outcome_with_dates.map(|dates| &dates.errors)),
+    ]
+    .into_iter()
+    .filter(|text| !text.is_empty())
+    .collect::<Vec<String>>()
+    .join("; ");
+    cells.push(errors);
+    cells
+}
+
+// delta period, delta growth, amount, interest, total
+fn outcome_cells(outcome: Option<&Outcome>) -> Vec<String> {
+    let delta = outcome.and_then(|outcome| outcome.delta.as_ref());
+    let maturity = outcome.and_then(|outcome| outcome.maturity.as_ref());
+    vec![
+        delta
+            .map(|delta| format!("{} {}", delta.period, delta.period_unit))
+            .unwrap_or_default(),
+        delta.map(|delta| delta.growth.clone()).unwrap_or_default(),
+        maturity
+            .map(|maturity| maturity.amount.clone())
+            .unwrap_or_default(),
+        maturity
+            .map(|maturity| maturity.interest.clone())
+            .unwrap_or_default(),
+        maturity
+            .map(|maturity| maturity.total.clone())
+            .unwrap_or_default(),
+    ]
+}
+
+fn errors_text(errors: Option<&Vec<ProcessingError>>) -> String {
+    errors
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|error| error.message.trim())
+                .collect::<Vec<&str>>()
+                .join("; ")
+        })
+        .unwrap_or_default()
+}
+
+// bank subtotal in the same columns as the deposits, with the outcome under the delta columns
+fn bank_subtotal_cells(bank: &Bank) -> Vec<String> {
+    let mut cells = vec![
+        "Subtotal".to_string(),
+        format!("{} deposits", bank.deposits.len()),
+    ];
+    cells.extend(std::iter::repeat_n(String::new(), 4));
+    cells.extend(outcome_cells(bank.outcome.as_ref()));
+    cells.push(errors_text(
+        bank.outcome.as_ref().map(|outcome| &outcome.errors),
+    ));
+    cells
+}
+
+fn portfolio_total_cells(response: &CalculatePortfolioResponse) -> Vec<String> {
+    let mut cells = vec![response.banks.len().to_string()];
+    cells.extend(outcome_cells(response.outcome.as_ref()));
+    cells.push(errors_text(
+        response.outcome.as_ref().map(|outcome| &outcome.errors),
+    ));
+    cells
+}
+
+// one row per deposit, followed by its bank subtotal, and the portfolio total last;
+// row_type tells them apart for spreadsheet filters
+fn render_csv(response: &CalculatePortfolioResponse) -> Result<String, Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let mut headers = vec!["Row type", "Bank", "Bank tz"];
+    headers.extend(DEPOSIT_HEADERS);
+    writer.write_record(&headers)?;
+    for bank in &response.banks {
+        for deposit in &bank.deposits {
+            let mut record = vec![
+                "deposit".to_string(),
+                bank.name.clone(),
+                bank.bank_tz.clone(),
+            ];
+            record.extend(deposit_cells(deposit));
+            writer.write_record(&record)?;
+        }
+        let mut record = vec![
+            "bank_subtotal".to_string(),
+            bank.name.clone(),
+            bank.bank_tz.clone(),
+        ];
+        record.extend(bank_subtotal_cells(bank));
+        writer.write_record(&record)?;
+    }
+    // the portfolio total laid out like a bank subtotal, with the bank count instead
+    let mut record = vec![
+        "portfolio_total".to_string(),
+        String::new(),
+        String::new(),
+        "Total".to_string(),
+        format!("{} banks", response.banks.len()),
+    ];
+    record.extend(std::iter::repeat_n(String::new(), 4));
+    record.extend(portfolio_total_cells(response).into_iter().skip(1));
+    writer.write_record(&record)?;
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
+
+fn markdown_row(cells: &[String]) -> String {
+    let cells = cells
+        .iter()
+        .map(|cell| markdown_cell(cell))
+        .collect::<Vec<String>>();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+fn markdown_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let headers = headers
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<String>>();
+    let mut table = markdown_row(&headers);
+    table.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+    for row in rows {
+        table.push_str(&markdown_row(row));
+    }
+    table
+}
+
+fn render_markdown(response: &CalculatePortfolioResponse) -> String {
+    let mut markdown = String::from("# Drive Deposits Portfolio Report\n\n");
+    let _ = writeln!(
+        markdown,
+        "Portfolio `{}` calculated at {}\n",
+        response.uuid, response.created_at
+    );
+    for bank in &response.banks {
+        let _ = writeln!(markdown, "## {} ({})\n", bank.name, bank.bank_tz);
+        let mut rows = bank.deposits.iter().map(deposit_cells).collect::<Vec<_>>();
+        rows.push(
+            bank_subtotal_cells(bank)
+                .into_iter()
+                .map(|cell| {
+                    if cell.is_empty() {
+                        cell
+                    } else {
+                        format!("**{}**", cell)
+                    }
+                })
+                .collect(),
+        );
+        markdown.push_str(&markdown_table(&DEPOSIT_HEADERS, &rows));
+        markdown.push('\n');
+    }
+    markdown.push_str("## Portfolio total\n\n");
+    markdown.push_str(&markdown_table(
+        &TOTAL_HEADERS,
+        &[portfolio_total_cells(response)],
+    ));
+    markdown
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// the errors column is the last one and is highlighted when not empty
+fn html_row(cells: &[String], cell_tag: &str) -> String {
+    let last = cells.len().saturating_sub(1);
+    let cells = cells
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| {
+            if index == last && !cell.is_empty() {
+                format!(
+                    "<{tag} class=\"errors\">{}</{tag}>",
+                    html_escape(cell),
+                    tag = cell_tag
+                )
+            } else {
+                format!("<{tag}>{}</{tag}>", html_escape(cell), tag = cell_tag)
+            }
+        })
+        .collect::<String>();
+    format!("<tr>{}</tr>\n", cells)
+}
+
+fn html_table(headers: &[&str], rows: &[Vec<String>], footer: Option<Vec<String>>) -> String {
+    let headers = headers
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<String>>();
+    let mut table = format!(
+        "<table>\n<thead>\n{}</thead>\n<tbody>\n",
+        html_row(&headers, "th")
+    );
+    for row in rows {
+        table.push_str(&html_row(row, "td"));
+    }
+    table.push_str("</tbody>\n");
+    if let Some(footer) = footer {
+        let _ = write!(table, "<tfoot>\n{}</tfoot>\n", html_row(&footer, "td"));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2rem}\
+table{border-collapse:collapse;margin-bottom:2rem}\
+th,td{border:1px solid #ccc;padding:.3rem .6rem;text-align:left}\
+th{background:#f3f3f3}tfoot td{font-weight:bold}.errors{color:#b00020}";
+
+fn render_html(response: &CalculatePortfolioResponse) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\" />\n\
+<title>Drive Deposits Portfolio Report</title>\n<style>{}</style>\n</head>\n<body>\n\
+<h1>Drive Deposits Portfolio Report</h1>\n<p>Portfolio <code>{}</code> calculated at {}</p>\n",
+        HTML_STYLE,
+        html_escape(&response.uuid),
+        html_escape(&response.created_at)
+    );
+    for bank in &response.banks {
+        let _ = writeln!(
+            html,
+            "<h2>{} ({})</h2>",
+            html_escape(&bank.name),
+            html_escape(&bank.bank_tz)
+        );
+        let rows = bank.deposits.iter().map(deposit_cells).collect::<Vec<_>>();
+        html.push_str(&html_table(
+            &DEPOSIT_HEADERS,
+            &rows,
+            Some(bank_subtotal_cells(bank)),
+        ));
+    }
+    html.push_str("<h2>Portfolio total</h2>\n");
+    html.push_str(&html_table(
+        &TOTAL_HEADERS,
+        &[portfolio_total_cells(response)],
+        None,
+    ));
+    html.push_str("</body>\n</html>\n");
+    html
+}
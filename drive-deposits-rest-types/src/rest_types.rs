@@ -1,15 +1,20 @@
+use std::collections::HashSet;
 use std::str::FromStr;
 
 use chrono::NaiveDate;
 use chrono_tz::Tz;
 use rust_decimal::Decimal;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use strum_macros::EnumString;
+use strum_macros::{EnumString, VariantNames};
+use utoipa::ToSchema;
 use validator::Validate;
 use validator::ValidationError;
 
+use crate::openapi::{account_type_schema, period_unit_schema};
+
 // Request sections
-#[derive(Default, Debug, Deserialize, Validate)]
+#[derive(Default, Debug, Deserialize, Serialize, Validate, ToSchema)]
 pub struct CalculatePortfolioRequest {
     #[validate(length(min = 1), nested)]
     pub new_banks: Vec<NewBank>,
@@ -17,11 +22,51 @@ pub struct CalculatePortfolioRequest {
     pub new_delta: NewDelta,
 }
 
-#[derive(Default, Debug, Deserialize, Validate)]
+// each portfolio_request is validated on its own by the gateway, so an invalid portfolio only
+// fails its own result; only the batch envelope is validated up front
+#[derive(Default, Debug, Deserialize, Validate, ToSchema)]
+pub struct CalculatePortfoliosRequest {
+    #[validate(
+        length(min = 1, max = 100),
+        custom(function = "validate_unique_correlation_ids"),
+        nested
+    )]
+    pub portfolio_requests: Vec<CorrelatedPortfolioRequest>,
+}
+
+#[derive(Default, Debug, Deserialize, Serialize, Validate, ToSchema)]
+pub struct CorrelatedPortfolioRequest {
+    #[validate(length(min = 1))]
+    pub correlation_id: String,
+    pub portfolio_request: CalculatePortfolioRequest,
+}
+
+pub fn validate_unique_correlation_ids(
+    portfolio_requests: &[CorrelatedPortfolioRequest],
+) -> Result<(), ValidationError> {
+    let mut correlation_ids = HashSet::new();
+    for portfolio_request in portfolio_requests {
+        if !correlation_ids.insert(portfolio_request.correlation_id.as_str()) {
+            let mut error = ValidationError::new("duplicate_correlation_id");
+            error.message = Some(
+                format!(
+                    "Duplicate correlation_id: {}. Each portfolio request needs its own.\n",
+                    portfolio_request.correlation_id
+                )
+                .into(),
+            );
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default, Debug, Deserialize, Serialize, Validate, ToSchema)]
 pub struct NewDelta {
     #[validate(length(min = 1), custom(function = "validate_positive_decimal"))]
     pub period: String,
     #[validate(custom(function = "validate_period_unit"))]
+    #[schema(schema_with = period_unit_schema)]
     pub period_unit: String,
 }
 
@@ -31,7 +76,7 @@ pub struct NewDelta {
 // #[derive(Deserialize)]: Derives the Deserialize trait from the serde crate, enabling your enum to be deserialized from formats like JSON.
 // #[derive(EnumString)]:Converts strings to enum variants based on their name.auto-derives std::str::FromStr on the enum and std::convert::TryFrom<&str> will be derived as well per docs
 // #[derive(Serialize) : since used in response as well
-#[derive(Default, Debug, Deserialize, EnumString, Serialize)]
+#[derive(Default, Debug, Deserialize, EnumString, Serialize, VariantNames)]
 pub enum DeltaPeriodUnit {
     #[default]
     Unspecified = 0,
@@ -41,7 +86,7 @@ pub enum DeltaPeriodUnit {
     Year = 4,
 }
 
-fn validate_period_unit(period_unit: &str) -> Result<(), ValidationError> {
+pub fn validate_period_unit(period_unit: &str) -> Result<(), ValidationError> {
     // DeltaPeriodUnit::from_str(period_unit).map_err(|_| {
     //     let mut error = ValidationError::new("invalid_period_unit");
     //     error.message = Some(
@@ -70,7 +115,7 @@ fn validate_period_unit(period_unit: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
-#[derive(Default, Debug, Deserialize, Validate, Serialize)]
+#[derive(Default, Debug, Deserialize, Validate, Serialize, ToSchema)]
 pub struct NewBank {
     #[validate(length(min = 1))]
     pub name: String,
@@ -81,7 +126,7 @@ pub struct NewBank {
     pub new_deposits: Vec<NewDeposit>,
 }
 
-fn validate_bank_tz(bank_tz: &str) -> Result<(), ValidationError> {
+pub fn validate_bank_tz(bank_tz: &str) -> Result<(), ValidationError> {
     bank_tz.parse::<Tz>().map_err(|e| {
         let mut error = ValidationError::new("invalid_tz");
         error.message = Some(
@@ -95,11 +140,12 @@ fn validate_bank_tz(bank_tz: &str) -> Result<(), ValidationError> {
     })?;
     Ok(())
 }
-#[derive(Default, Debug, Deserialize, Validate, Serialize)]
+#[derive(Default, Debug, Deserialize, Validate, Serialize, ToSchema)]
 pub struct NewDeposit {
     #[validate(length(min = 4))]
     pub account: String,
     #[validate(custom(function = "validate_account_type"))]
+    #[schema(schema_with = account_type_schema)]
     pub account_type: String,
     #[validate(custom(function = "validate_decimal"))]
     pub apy: String,
@@ -111,7 +157,7 @@ pub struct NewDeposit {
     pub start_date_in_bank_tz: String,
 }
 
-fn validate_iso8601_date(date_str: &str) -> Result<(), ValidationError> {
+pub fn validate_iso8601_date(date_str: &str) -> Result<(), ValidationError> {
     NaiveDate::parse_from_str(date_str, "%Y-%m-%d").map_err(|e| {
         let mut error = ValidationError::new("invalid_iso8601_datetime");
         error.message = Some(
@@ -126,7 +172,7 @@ fn validate_iso8601_date(date_str: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
-#[derive(Default, Deserialize, Debug, EnumString)]
+#[derive(Default, Deserialize, Debug, EnumString, VariantNames)]
 pub enum AccountType {
     #[default]
     Unspecified = 0,
@@ -136,7 +182,7 @@ pub enum AccountType {
     BrokerageCertificateOfDeposit = 4,
 }
 
-fn validate_account_type(account_type: &str) -> Result<(), ValidationError> {
+pub fn validate_account_type(account_type: &str) -> Result<(), ValidationError> {
     AccountType::from_str(account_type).map_err(|e| {
         let mut error = ValidationError::new("invalid_account_type");
         error.message = Some(
@@ -151,7 +197,7 @@ fn validate_account_type(account_type: &str) -> Result<(), ValidationError> {
     Ok(())
 }
 
-fn validate_decimal(value: &str) -> Result<(), ValidationError> {
+pub fn validate_decimal(value: &str) -> Result<(), ValidationError> {
     let v = match value.parse::<Decimal>() {
         Ok(val) => val,
         Err(_) => {
@@ -181,7 +227,7 @@ fn validate_decimal(value: &str) -> Result<(), ValidationError> {
     }
 }
 
-fn validate_positive_decimal(value: &str) -> Result<(), ValidationError> {
+pub fn validate_positive_decimal(value: &str) -> Result<(), ValidationError> {
     validate_decimal(value)?;
     if value.parse::<Decimal>().unwrap().is_zero() {
         let mut error = ValidationError::new("invalid_positive_decimal");
@@ -198,42 +244,113 @@ fn validate_positive_decimal(value: &str) -> Result<(), ValidationError> {
 }
 
 // Response sections
+// JsonSchema derives are the event payload schemas of drive-deposits-event-source::schema
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct CalculatePortfolioResponse {
     pub uuid: String,
     pub banks: Vec<Bank>,
     pub outcome: Option<Outcome>,
     pub created_at: String,
+    // only set in event payloads, to the tenant of the caller the portfolio was calculated for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(ignore)]
+    pub tenant_id: Option<String>,
+}
+
+// last event of a streamed calculation; the banks were already sent one by one
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PortfolioSummary {
+    pub uuid: String,
+    pub outcome: Option<Outcome>,
+    pub created_at: String,
+    pub bank_count: u32,
+}
+
+// results are in the same order as the portfolio_requests; each has either a portfolio_response
+// or an error
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CalculatePortfoliosResponse {
+    pub portfolio_responses: Vec<CorrelatedPortfolioResponse>,
+    pub succeeded: u32,
+    pub failed: u32,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CorrelatedPortfolioResponse {
+    pub correlation_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portfolio_response: Option<CalculatePortfolioResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<PortfolioError>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PortfolioError {
+    // gRPC status code name, for example InvalidArgument
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<PortfolioViolation>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PortfolioViolation {
+    // RFC 6901 JSON pointer into the portfolio_request, for example /new_banks/0/new_deposits/2/apy
+    pub pointer: String,
+    pub code: String,
+    pub message: String,
+}
+
+pub fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+// grpc field paths look like new_banks[2].new_deposits[0].apy
+pub fn field_path_to_pointer(field_path: &str) -> String {
+    field_path
+        .split('.')
+        .flat_map(|segment| segment.split('['))
+        .map(|token| token.trim_end_matches(']'))
+        .filter(|token| !token.is_empty())
+        .fold(String::new(), |pointer, token| {
+            format!("{}/{}", pointer, escape_pointer_token(token))
+        })
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct Delta {
     pub period: String,
+    #[schema(schema_with = period_unit_schema)]
     pub period_unit: String,
     pub growth: String,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct Maturity {
     pub amount: String,
     pub interest: String,
     pub total: String,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct Bank {
     pub uuid: String,
     pub name: String,
     pub bank_tz: String,
     pub deposits: Vec<Deposit>,
     pub outcome: Option<Outcome>,
+    // only set in bank-level event payloads, same as CalculatePortfolioResponse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(ignore)]
+    pub tenant_id: Option<String>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct Deposit {
     pub uuid: String,
     pub account: String,
+    #[schema(schema_with = account_type_schema)]
     pub account_type: String,
     pub apy: String,
     pub years: String,
@@ -241,21 +358,21 @@ pub struct Deposit {
     pub outcome_with_dates: Option<OutcomeWithDates>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct Outcome {
     pub delta: Option<Delta>,
     pub maturity: Option<Maturity>,
     pub errors: Vec<ProcessingError>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct OutcomeWithDates {
     pub start_date_in_bank_tz: String,
     pub maturity_date_in_bank_tz: Option<String>,
     pub errors: Vec<ProcessingError>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, JsonSchema)]
 pub struct ProcessingError {
     pub uuid: String,
     pub message: String,
@@ -0,0 +1,457 @@
+use std::env::var;
+use std::fs::{read, read_to_string};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::cloud_event::EVENT_SOURCE;
+
+// path of the optional TOML config file; env variables override what it sets
+pub const CONFIG_FILE_ENV: &str = "DRIVE_DEPOSITS_CONFIG";
+// below the 30 second default termination grace period of docker and kubernetes
+const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 25_000;
+// endpoint used with USE_LOCALSTACK=true when no endpoint_url is configured
+pub const LOCALSTACK_ENDPOINT: &str = "http://localhost.localstack.cloud:4566/";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read config file {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid config file {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        // boxed, toml errors are large and this error is carried by most startup Results
+        source: Box<toml::de::Error>,
+    },
+
+    #[error("Invalid value {value:?} for env variable {name}")]
+    Env { name: &'static str, value: String },
+
+    #[error("{0} needs both a certificate and a key")]
+    IncompleteTls(&'static str),
+}
+
+// [grpc_server], [rest_gateway] and [event_publisher] sections of the config file, each server
+// reads its own
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub grpc_server: GrpcServerConfig,
+    pub rest_gateway: RestGatewayConfig,
+    pub event_publisher: EventPublisherConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrpcServerConfig {
+    pub bind_address: SocketAddr,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    // with TLS on, clients must present a certificate signed by this CA (mTLS)
+    pub client_ca_path: Option<PathBuf>,
+    // after SIGTERM, how long in-flight requests and calculations get to finish
+    pub shutdown_timeout_ms: u64,
+}
+
+impl Default for GrpcServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([0u16; 8], 50052)),
+            tls_cert_path: None,
+            tls_key_path: None,
+            client_ca_path: None,
+            shutdown_timeout_ms: DEFAULT_SHUTDOWN_TIMEOUT_MS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestGatewayConfig {
+    pub bind_address: SocketAddr,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    // outbound channel to the grpc server; TLS is used once grpc_ca_path is set
+    pub grpc_ca_path: Option<PathBuf>,
+    pub grpc_client_cert_path: Option<PathBuf>,
+    pub grpc_client_key_path: Option<PathBuf>,
+    // expected name in the grpc server certificate when it differs from the address host
+    pub grpc_tls_domain_name: Option<String>,
+    pub shutdown_timeout_ms: u64,
+}
+
+impl Default for RestGatewayConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            tls_cert_path: None,
+            tls_key_path: None,
+            grpc_ca_path: None,
+            grpc_client_cert_path: None,
+            grpc_client_key_path: None,
+            grpc_tls_domain_name: None,
+            shutdown_timeout_ms: DEFAULT_SHUTDOWN_TIMEOUT_MS,
+        }
+    }
+}
+
+// Where EVENT_PUBLISHER=eventbridge sends events, so staging and prod buses can share an account
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventPublisherConfig {
+    // name or ARN of the event bus
+    pub event_bus: String,
+    pub source: String,
+    // put in front of bank-level and portfolio-level, for example "staging-"
+    pub detail_type_prefix: String,
+    // the AWS default region when unset
+    pub region: Option<String>,
+    pub endpoint_url: Option<String>,
+    // rules the calculation events depend on, checked by name at startup and by health checks
+    pub required_rules: Vec<String>,
+    // one event per calculated deposit as well, off by default since a portfolio has many deposits
+    pub deposit_level_events: bool,
+    // check event payloads against their JSON Schemas before sending; on in debug builds
+    pub validate_payloads: bool,
+}
+
+impl Default for EventPublisherConfig {
+    fn default() -> Self {
+        Self {
+            event_bus: "DriveDepositsEventBus".to_string(),
+            source: EVENT_SOURCE.to_string(),
+            detail_type_prefix: String::new(),
+            region: None,
+            endpoint_url: None,
+            // from the drive-deposits-logs-lambda-target SAM template
+            required_rules: vec![
+                "drive-deposits-bank-level".to_string(),
+                "drive-deposits-banks-level".to_string(),
+                "drive-deposits-banks-level-for-lambda".to_string(),
+            ],
+            deposit_level_events: false,
+            validate_payloads: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl EventPublisherConfig {
+    // defaults, then the [event_publisher] section of the DRIVE_DEPOSITS_CONFIG file if set, then
+    // env variables
+    pub fn load() -> Result<Self, Error> {
+        Ok(ServerConfig::load()?.event_publisher)
+    }
+
+    pub fn detail_type(&self, detail_type: &str) -> String {
+        format!("{}{}", self.detail_type_prefix, detail_type)
+    }
+}
+
+// PEM files a server listens with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerTlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+// PEM files the gateway connects to the grpc server with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientTlsFiles {
+    pub ca_path: PathBuf,
+    pub client_identity: Option<ServerTlsFiles>,
+    pub domain_name: Option<String>,
+}
+
+impl ServerConfig {
+    // defaults, then the DRIVE_DEPOSITS_CONFIG file if set, then env variables
+    pub fn load() -> Result<Self, Error> {
+        let config = match var(CONFIG_FILE_ENV) {
+            Ok(path) => Self::from_file(Path::new(&path))?,
+            Err(_) => Self::default(),
+        };
+        config.with_env_overrides(|name| var(name).ok())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = read_to_string(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&contents).map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source: Box::new(source),
+        })
+    }
+
+    pub fn with_env_overrides(
+        mut self,
+        env: impl Fn(&'static str) -> Option<String>,
+    ) -> Result<Self, Error> {
+        let grpc_server = &mut self.grpc_server;
+        override_parsed(
+            &env,
+            "GRPC_SERVER_BIND_ADDRESS",
+            &mut grpc_server.bind_address,
+        )?;
+        override_path(
+            &env,
+            "GRPC_SERVER_TLS_CERT_PATH",
+            &mut grpc_server.tls_cert_path,
+        );
+        override_path(
+            &env,
+            "GRPC_SERVER_TLS_KEY_PATH",
+            &mut grpc_server.tls_key_path,
+        );
+        override_path(
+            &env,
+            "GRPC_SERVER_CLIENT_CA_PATH",
+            &mut grpc_server.client_ca_path,
+        );
+        override_parsed(
+            &env,
+            "GRPC_SERVER_SHUTDOWN_TIMEOUT_MS",
+            &mut grpc_server.shutdown_timeout_ms,
+        )?;
+
+        let rest_gateway = &mut self.rest_gateway;
+        override_parsed(
+            &env,
+            "REST_GATEWAY_BIND_ADDRESS",
+            &mut rest_gateway.bind_address,
+        )?;
+        override_path(
+            &env,
+            "REST_GATEWAY_TLS_CERT_PATH",
+            &mut rest_gateway.tls_cert_path,
+        );
+        override_path(
+            &env,
+            "REST_GATEWAY_TLS_KEY_PATH",
+            &mut rest_gateway.tls_key_path,
+        );
+        override_path(
+            &env,
+            "REST_GATEWAY_GRPC_CA_PATH",
+            &mut rest_gateway.grpc_ca_path,
+        );
+        override_path(
+            &env,
+            "REST_GATEWAY_GRPC_CLIENT_CERT_PATH",
+            &mut rest_gateway.grpc_client_cert_path,
+        );
+        override_path(
+            &env,
+            "REST_GATEWAY_GRPC_CLIENT_KEY_PATH",
+            &mut rest_gateway.grpc_client_key_path,
+        );
+        if let Some(domain_name) = env("REST_GATEWAY_GRPC_TLS_DOMAIN_NAME") {
+            rest_gateway.grpc_tls_domain_name = Some(domain_name);
+        }
+        override_parsed(
+            &env,
+            "REST_GATEWAY_SHUTDOWN_TIMEOUT_MS",
+            &mut rest_gateway.shutdown_timeout_ms,
+        )?;
+
+        let event_publisher = &mut self.event_publisher;
+        if let Some(event_bus) = env("EVENT_PUBLISHER_EVENT_BUS") {
+            event_publisher.event_bus = event_bus;
+        }
+        if let Some(source) = env("EVENT_PUBLISHER_SOURCE") {
+            event_publisher.source = source;
+        }
+        if let Some(detail_type_prefix) = env("EVENT_PUBLISHER_DETAIL_TYPE_PREFIX") {
+            event_publisher.detail_type_prefix = detail_type_prefix;
+        }
+        override_string(&env, "EVENT_PUBLISHER_REGION", &mut event_publisher.region);
+        override_string(
+            &env,
+            "EVENT_PUBLISHER_ENDPOINT_URL",
+            &mut event_publisher.endpoint_url,
+        );
+        if let Some(required_rules) = env("EVENT_PUBLISHER_REQUIRED_RULES") {
+            event_publisher.required_rules = required_rules
+                .split(',')
+                .map(str::trim)
+                .filter(|rule| !rule.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        override_parsed(
+            &env,
+            "EVENT_PUBLISHER_DEPOSIT_LEVEL_EVENTS",
+            &mut event_publisher.deposit_level_events,
+        )?;
+        override_parsed(
+            &env,
+            "EVENT_PUBLISHER_VALIDATE_PAYLOADS",
+            &mut event_publisher.validate_payloads,
+        )?;
+        // kept working for local development, unless an endpoint is configured
+        let use_localstack =
+            env("USE_LOCALSTACK").is_some_and(|value| value.trim().eq_ignore_ascii_case("true"));
+        if use_localstack && event_publisher.endpoint_url.is_none() {
+            event_publisher.endpoint_url = Some(LOCALSTACK_ENDPOINT.to_string());
+        }
+        Ok(self)
+    }
+}
+
+impl GrpcServerConfig {
+    pub fn tls(&self) -> Result<Option<ServerTlsFiles>, Error> {
+        tls_files(&self.tls_cert_path, &self.tls_key_path, "grpc_server TLS")
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(self.shutdown_timeout_ms)
+    }
+}
+
+impl RestGatewayConfig {
+    pub fn tls(&self) -> Result<Option<ServerTlsFiles>, Error> {
+        tls_files(&self.tls_cert_path, &self.tls_key_path, "rest_gateway TLS")
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(self.shutdown_timeout_ms)
+    }
+
+    pub fn grpc_client_tls(&self) -> Result<Option<ClientTlsFiles>, Error> {
+        let client_identity = tls_files(
+            &self.grpc_client_cert_path,
+            &self.grpc_client_key_path,
+            "rest_gateway grpc client identity",
+        )?;
+        Ok(self.grpc_ca_path.clone().map(|ca_path| ClientTlsFiles {
+            ca_path,
+            client_identity,
+            domain_name: self.grpc_tls_domain_name.clone(),
+        }))
+    }
+}
+
+// io errors do not name the file otherwise
+pub fn read_pem(path: &Path) -> Result<Vec<u8>, io::Error> {
+    read(path).map_err(|err| io::Error::new(err.kind(), format!("{:?}: {}", path, err)))
+}
+
+fn tls_files(
+    cert_path: &Option<PathBuf>,
+    key_path: &Option<PathBuf>,
+    section: &'static str,
+) -> Result<Option<ServerTlsFiles>, Error> {
+    match (cert_path, key_path) {
+        (Some(cert_path), Some(key_path)) => Ok(Some(ServerTlsFiles {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+        })),
+        (None, None) => Ok(None),
+        _ => Err(Error::IncompleteTls(section)),
+    }
+}
+
+fn override_parsed<T: FromStr>(
+    env: &impl Fn(&'static str) -> Option<String>,
+    name: &'static str,
+    field: &mut T,
+) -> Result<(), Error> {
+    if let Some(value) = env(name) {
+        *field = value
+            .trim()
+            .parse()
+            .map_err(|_| Error::Env { name, value })?;
+    }
+    Ok(())
+}
+
+// an empty value turns off what the file set
+fn override_string(
+    env: &impl Fn(&'static str) -> Option<String>,
+    name: &'static str,
+    field: &mut Option<String>,
+) {
+    if let Some(value) = env(name) {
+        *field = Some(value).filter(|value| !value.trim().is_empty());
+    }
+}
+
+// an empty value turns off what the file set
+fn override_path(
+    env: &impl Fn(&'static str) -> Option<String>,
+    name: &'static str,
+    field: &mut Option<PathBuf>,
+) {
+    if let Some(value) = env(name) {
+        *field = Some(value)
+            .filter(|value| !value.trim().is_empty())
+            .map(PathBuf::from);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::{ServerConfig, ServerTlsFiles};
+
+    fn with_env(vars: &[(&'static str, &str)]) -> ServerConfig {
+        let vars: HashMap<&str, String> = vars
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        ServerConfig::default()
+            .with_env_overrides(|name| vars.get(name).cloned())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rest_gateway_config_env_overrides() {
+        let config = with_env(&[
+            ("REST_GATEWAY_BIND_ADDRESS", "127.0.0.1:3443"),
+            ("REST_GATEWAY_TLS_CERT_PATH", "certs/gateway.pem"),
+            ("REST_GATEWAY_TLS_KEY_PATH", "certs/gateway.key"),
+            ("REST_GATEWAY_GRPC_CA_PATH", "certs/ca.pem"),
+        ])
+        .rest_gateway;
+
+        assert_eq!(config.bind_address.to_string(), "127.0.0.1:3443");
+        assert_eq!(
+            config.tls().unwrap(),
+            Some(ServerTlsFiles {
+                cert_path: PathBuf::from("certs/gateway.pem"),
+                key_path: PathBuf::from("certs/gateway.key"),
+            })
+        );
+        let grpc_client_tls = config.grpc_client_tls().unwrap().unwrap();
+        assert_eq!(grpc_client_tls.ca_path, PathBuf::from("certs/ca.pem"));
+        assert_eq!(grpc_client_tls.client_identity, None);
+    }
+
+    #[test]
+    fn test_rest_gateway_config_defaults_and_incomplete_tls() {
+        let config = with_env(&[]).rest_gateway;
+        assert_eq!(config.bind_address.to_string(), "0.0.0.0:3000");
+        assert_eq!(config.tls().unwrap(), None);
+
+        let config = with_env(&[("REST_GATEWAY_TLS_CERT_PATH", "certs/gateway.pem")]).rest_gateway;
+        assert!(config.tls().is_err());
+
+        let invalid = ServerConfig::default().with_env_overrides(|name| {
+            (name == "REST_GATEWAY_BIND_ADDRESS").then(|| "3000".to_string())
+        });
+        assert!(invalid.is_err());
+    }
+}